@@ -0,0 +1,53 @@
+// The meter's per-window work on a 500ms window of 48kHz stereo, the most a
+// typical USB microphone sends: the vectorized kernels next to the plain
+// loops they replaced, and the low-pass filter. shhh is a binary, so the
+// modules are pulled in by path; they don't depend on the rest of the crate.
+//
+//   cargo bench --bench meter
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// what the bench doesn't use, and their tests' imports (the tests themselves
+// only run under `cargo test`)
+#[allow(dead_code, unused_imports)]
+#[path = "../src/filter.rs"]
+mod filter;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/simd.rs"]
+mod simd;
+
+const RATE: usize = 48_000;
+const CHANNELS: usize = 2;
+
+// a tone with some noise on it
+fn window() -> Vec<f32> {
+    let noise = |i: usize| ((i * 7919 % 2003) as f32 / 2003.0 - 0.5) * 0.1;
+    (0..RATE * CHANNELS / 2).map(|i| (i as f32 * 0.037).sin() * 0.3 + noise(i)).collect()
+}
+
+fn rms(c: &mut Criterion) {
+    let samples = window();
+    c.bench_function("sum of squares, plain", |b| {
+        b.iter(|| black_box(&samples).iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>())
+    });
+    c.bench_function("sum of squares, simd", |b| b.iter(|| simd::sum_squares(black_box(&samples))));
+    c.bench_function("clipping, plain", |b| b.iter(|| black_box(&samples).iter().filter(|s| s.abs() >= 0.999).count()));
+    c.bench_function("clipping, simd", |b| b.iter(|| simd::count_over(black_box(&samples), 0.999)));
+}
+
+fn lowpass(c: &mut Criterion) {
+    let samples = window();
+    let mut filter = filter::LowPass::new(4_000.0, RATE as u32, CHANNELS as u16).unwrap();
+    let mut out = Vec::with_capacity(samples.len());
+    c.bench_function("low-pass", |b| {
+        b.iter(|| {
+            out.clear();
+            filter.process(black_box(&samples), &mut out);
+            black_box(&out);
+        })
+    });
+}
+
+criterion_group!(benches, rms, lowpass);
+criterion_main!(benches);
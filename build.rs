@@ -0,0 +1,11 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // the gRPC service and messages, from the published .proto; protoc comes
+    // with protoc-bin-vendored, so there's nothing to install
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/shhh.proto");
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("no protoc for this platform"));
+        tonic_prost_build::compile_protos("proto/shhh.proto").expect("could not compile proto/shhh.proto");
+    }
+}
@@ -0,0 +1,91 @@
+# Deutsch
+
+## The monitor's log
+
+paused = Pausiert (Steuerbefehl).
+resumed = Fortgesetzt (Steuerbefehl).
+stopped = Beendet (Steuerbefehl).
+restored = Wiederhergestellt (Steuerbefehl).
+no-audio = Seit 3 s kein Ton — wird wiederhergestellt.
+warning-only = Zu laut (nur Warnung).
+countdown = Zu laut: Trennung in { $secs } s, wenn es nicht leiser wird.
+countdown-cancelled = Wieder leiser, keine Trennung.
+cut = Verbindung getrennt.
+lifted = Verbindung wieder da.
+shaped = Verbindung gedrosselt ({ $level } %).
+unshaped = Verbindung wieder in voller Geschwindigkeit.
+tampering = Mögliche Manipulation am Mikrofon: { $what }
+tampering-cleared = Mikrofon wieder normal.
+clipping = Das Mikrofon übersteuert, Lärm wird zu leise gemessen; bitte die Eingangsverstärkung senken.
+clipping-cleared = Das Mikrofon übersteuert nicht mehr.
+gain-drift = Der Raumpegel liegt dauerhaft { $drift } dB neben der Kalibrierung, wie es eine automatische Verstärkungsregelung bewirkt; bitte AGC für das Mikrofon abschalten (siehe README) oder neu kalibrieren.
+gain-compensated = Der Raumpegel liegt dauerhaft { $drift } dB neben der Kalibrierung, wie es eine automatische Verstärkungsregelung bewirkt; die Schwellen wurden mitverschoben.
+heard-keyword = „{ $phrase }“ gehört.
+heard-safe-word = Sicherheitswort gehört — wird wiederhergestellt und pausiert.
+profile = Profil: { $name }
+profile-default = Standard
+thresholds = Schwellen jetzt min { $min }, max { $max }
+script-warning = Skript-Warnung: { $message }
+call-detected = Anruf erkannt ({ $app }).
+call-over = Anruf beendet ({ $app }).
+exiting = Wird beendet — wird wiederhergestellt.
+panicked = Abgestürzt — wird wiederhergestellt.
+stopping = Wird angehalten — wird wiederhergestellt.
+protected = Stoppsignal ignoriert (--protect); bitte `shhh ctl stop` verwenden.
+eventlog-level = Pegel { $db } dBFS, Trennung ab { $max } dBFS.
+
+## Rewards (also the desktop notification)
+
+reward = { $mins ->
+    [one] Eine Minute am Stück leise. Super!
+   *[other] { $mins } Minuten am Stück leise. Super!
+}
+reward-earned = Belohnung verdient: { $message }
+
+## Repeated cuts, with `[power]` (also the desktop notification)
+
+power-warning = { $strikes } Trennungen innerhalb von { $mins } Minuten: { $what ->
+    [sleep] Der Computer geht
+   *[shutdown] Der Computer fährt
+} in { $secs } s { $what ->
+    [sleep] in den Ruhezustand.
+   *[shutdown] herunter.
+}
+power-cancelled = Ruhezustand bzw. Herunterfahren per Steuerbefehl abgebrochen.
+power-now = { $what ->
+    [sleep] Ruhezustand wird eingeleitet.
+   *[shutdown] Wird heruntergefahren.
+}
+
+## `[email]`
+
+email-struck-out = Zu viele Trennungen hintereinander.
+email-events = { $count } Ereignisse
+email-digest = Tageszusammenfassung
+email-digest-cuts = Trennungen: { $count } ({ $mins } Minuten insgesamt)
+email-digest-rewards = Belohnungen: { $count }
+email-digest-tampering = Manipulationen: { $count }
+email-digest-strike-outs = Zu viele Trennungen: { $count }
+
+## `shhh status`
+
+status-state = Zustand
+status-level = Pegel
+status-thresholds = Schwellen
+status-profile = Profil
+status-uptime = Läuft seit
+status-penalty = Reststrafe
+status-quiet = Leise seit
+status-countdown = Trennung in
+status-restore = Aufgehoben nach
+status-action = Aktion
+status-tampering = Manipulation
+status-error = Letzter Fehler
+status-paused = (pausiert)
+status-ambient = Umgebung { $ambient }, min { $min }, max { $max }
+status-quiet-mins = { $mins } min
+status-secs = { $secs } s
+status-uptime-value = { $hours } h { $mins } min
+status-restore-secs = { $secs } s weiterer Ruhe
+status-in-force = aktiv
+status-idle = inaktiv
@@ -0,0 +1,89 @@
+# English, the fallback for every other language. A translation only needs the
+# messages it changes; see the README's "Languages" section.
+
+## The monitor's log
+
+paused = Paused by control request.
+resumed = Resumed by control request.
+stopped = Stopped by control request.
+restored = Restored by control request.
+no-audio = No audio for 3s — restoring.
+warning-only = Too loud (warning only).
+countdown = Too loud: cut in { $secs } s unless it quiets down.
+countdown-cancelled = Quieter again, no cut.
+cut = Cut in force.
+lifted = Lifted.
+shaped = Connection slowed down ({ $level }%).
+unshaped = Connection back to full speed.
+tampering = Possible microphone tampering: { $what }
+tampering-cleared = Microphone back to normal.
+clipping = The microphone is clipping, so loud noise reads too quiet; turn its input gain down.
+clipping-cleared = The microphone has stopped clipping.
+gain-drift = The room's level has settled { $drift } dB off calibration, the way automatic gain control moves it; turn AGC off for the microphone (see the README) or recalibrate.
+gain-compensated = The room's level has settled { $drift } dB off calibration, the way automatic gain control moves it; thresholds moved with it.
+heard-keyword = Heard "{ $phrase }".
+heard-safe-word = Heard the safe word — restoring and pausing.
+profile = Profile: { $name }
+profile-default = default
+thresholds = Thresholds now min { $min }, max { $max }
+script-warning = Script warning: { $message }
+call-detected = Call detected ({ $app }).
+call-over = Call over ({ $app }).
+exiting = Exiting — restoring.
+panicked = Crashed — restoring.
+stopping = Stopping — restoring.
+protected = Ignoring a stop signal (--protect); use `shhh ctl stop`.
+eventlog-level = Level { $db } dBFS, cut at { $max } dBFS.
+
+## Rewards (also the desktop notification)
+
+reward = { $mins ->
+    [one] Quiet for a minute straight. Nice!
+   *[other] Quiet for { $mins } min straight. Nice!
+}
+reward-earned = Reward earned: { $message }
+
+## Repeated cuts, with `[power]` (also the desktop notification)
+
+power-warning = { $strikes } cuts within { $mins } min: { $what ->
+    [sleep] the computer goes to sleep
+   *[shutdown] the computer shuts down
+} in { $secs } s.
+power-cancelled = Sleep or shutdown called off by control request.
+power-now = { $what ->
+    [sleep] Going to sleep.
+   *[shutdown] Shutting down.
+}
+
+## `[email]`
+
+email-struck-out = Strike-out: too many cuts in a row.
+email-events = { $count } events
+email-digest = Daily digest
+email-digest-cuts = Cuts: { $count } ({ $mins } min in all)
+email-digest-rewards = Rewards: { $count }
+email-digest-tampering = Tampering: { $count }
+email-digest-strike-outs = Strike-outs: { $count }
+
+## `shhh status`
+
+status-state = State
+status-level = Level
+status-thresholds = Thresholds
+status-profile = Profile
+status-uptime = Running for
+status-penalty = Penalty left
+status-quiet = Quiet for
+status-countdown = Cut in
+status-restore = Lifted after
+status-action = Action
+status-tampering = Tampering
+status-error = Last error
+status-paused = (paused)
+status-ambient = ambient { $ambient }, min { $min }, max { $max }
+status-quiet-mins = { $mins } min
+status-secs = { $secs } s
+status-uptime-value = { $hours } h { $mins } min
+status-restore-secs = { $secs } s more of quiet
+status-in-force = in force
+status-idle = idle
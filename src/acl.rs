@@ -0,0 +1,139 @@
+// Who may change shhh's files on Windows. The service runs as LocalSystem and
+// takes its config and drop-in plugins from %ProgramData%\shhh, where by
+// default any user can create files; the monitored user could write a config
+// of their own (`mode = "none"`, a log directory anywhere) and have SYSTEM run
+// it. `service install` locks the directory down to SYSTEM and
+// Administrators, and the config and plugins are only loaded from places
+// nobody else can write.
+use std::path::Path;
+use std::ptr::null_mut;
+
+use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+use windows_sys::Win32::Security::Authorization::{
+    ConvertStringSecurityDescriptorToSecurityDescriptorW, GetNamedSecurityInfoW, SetNamedSecurityInfoW,
+    SDDL_REVISION_1, SE_FILE_OBJECT,
+};
+use windows_sys::Win32::Security::{
+    GetAce, GetSecurityDescriptorDacl, GetSecurityDescriptorOwner, IsWellKnownSid, WinBuiltinAdministratorsSid,
+    WinLocalSystemSid, ACCESS_ALLOWED_ACE, ACL, DACL_SECURITY_INFORMATION, INHERIT_ONLY_ACE,
+    OWNER_SECURITY_INFORMATION, PROTECTED_DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID,
+};
+
+// owned by Administrators, full control for SYSTEM and Administrators (passed
+// on to everything inside) and nothing inherited from the parent
+const PRIVATE: &str = "O:BAD:P(A;OICI;FA;;;SY)(A;OICI;FA;;;BA)";
+
+// ACE types that grant access: plain, object, callback and callback object
+const ALLOWED_ACE_TYPES: [u8; 4] = [0, 5, 9, 11];
+// rights that change a file or a directory's contents: write and append data
+// (add file, add subdirectory), write extended attributes, delete child,
+// write attributes, delete, write DACL, write owner, generic all and write
+const WRITE_RIGHTS: u32 = 0x2 | 0x4 | 0x10 | 0x40 | 0x100 | 0x1_0000 | 0x4_0000 | 0x8_0000 | 0x1000_0000 | 0x4000_0000;
+
+fn wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().to_string_lossy().encode_utf16().chain(Some(0)).collect()
+}
+
+fn trusted(sid: PSID) -> bool {
+    unsafe { IsWellKnownSid(sid, WinLocalSystemSid) != 0 || IsWellKnownSid(sid, WinBuiltinAdministratorsSid) != 0 }
+}
+
+// create `dir` if need be and leave it to SYSTEM and Administrators, along
+// with everything already in it
+pub fn lock_down(dir: &Path) -> Result<(), anyhow::Error> {
+    std::fs::create_dir_all(dir).map_err(|e| anyhow::anyhow!("could not create {}: {}", dir.display(), e))?;
+    let sddl: Vec<u16> = PRIVATE.encode_utf16().chain(Some(0)).collect();
+    let mut sd: PSECURITY_DESCRIPTOR = null_mut();
+    let parsed = unsafe {
+        ConvertStringSecurityDescriptorToSecurityDescriptorW(sddl.as_ptr(), SDDL_REVISION_1, &mut sd, null_mut())
+    };
+    if parsed == 0 {
+        anyhow::bail!("could not build a security descriptor: {}", std::io::Error::last_os_error());
+    }
+    let (mut owner, mut dacl): (PSID, *mut ACL) = (null_mut(), null_mut());
+    let (mut present, mut defaulted) = (0, 0);
+    let err = unsafe {
+        GetSecurityDescriptorOwner(sd, &mut owner, &mut defaulted);
+        GetSecurityDescriptorDacl(sd, &mut present, &mut dacl, &mut defaulted);
+        // also replaces what the directory's contents inherited
+        let err = SetNamedSecurityInfoW(
+            wide(dir).as_ptr(),
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+            owner,
+            null_mut(),
+            dacl,
+            null_mut(),
+        );
+        LocalFree(sd);
+        err
+    };
+    if err != ERROR_SUCCESS {
+        anyhow::bail!("could not restrict {}: {}", dir.display(), std::io::Error::from_raw_os_error(err as i32));
+    }
+    Ok(())
+}
+
+// Err unless `path` is owned by SYSTEM or Administrators and nobody else may
+// write to it
+pub fn check(path: &Path) -> Result<(), anyhow::Error> {
+    let (mut owner, mut dacl, mut sd): (PSID, *mut ACL, PSECURITY_DESCRIPTOR) = (null_mut(), null_mut(), null_mut());
+    let err = unsafe {
+        GetNamedSecurityInfoW(
+            wide(path).as_ptr(),
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION,
+            &mut owner,
+            null_mut(),
+            &mut dacl,
+            null_mut(),
+            &mut sd,
+        )
+    };
+    if err != ERROR_SUCCESS {
+        let err = std::io::Error::from_raw_os_error(err as i32);
+        anyhow::bail!("could not check who can change {}: {}", path.display(), err);
+    }
+    let problem = unsafe { problem(owner, dacl) };
+    unsafe { LocalFree(sd) };
+    match problem {
+        Some(problem) => Err(anyhow::anyhow!(
+            "refusing {}: {}; only SYSTEM and Administrators may change it (reinstalling the service sets that up)",
+            path.display(),
+            problem
+        )),
+        None => Ok(()),
+    }
+}
+
+unsafe fn problem(owner: PSID, dacl: *mut ACL) -> Option<&'static str> {
+    if owner.is_null() || !trusted(owner) {
+        return Some("it isn't owned by SYSTEM or Administrators");
+    }
+    // no DACL at all lets everyone in
+    if dacl.is_null() {
+        return Some("anyone may write to it");
+    }
+    for i in 0..(*dacl).AceCount as u32 {
+        let mut ace = null_mut();
+        if GetAce(dacl, i, &mut ace) == 0 {
+            return Some("its permissions can't be read");
+        }
+        let ace = &*(ace as *const ACCESS_ALLOWED_ACE);
+        // inherit-only entries (CREATOR OWNER and the like) are for what's
+        // created inside, not this
+        if !ALLOWED_ACE_TYPES.contains(&ace.Header.AceType) || ace.Header.AceFlags as u32 & INHERIT_ONLY_ACE != 0 {
+            continue;
+        }
+        // the SID of the plain and callback types starts at SidStart; object
+        // ACEs have GUIDs in between and are rare on files, so refuse those
+        // that grant writing outright
+        if ace.Mask & WRITE_RIGHTS == 0 {
+            continue;
+        }
+        if matches!(ace.Header.AceType, 5 | 11) || !trusted(&ace.SidStart as *const u32 as PSID) {
+            return Some("users other than SYSTEM and Administrators may write to it");
+        }
+    }
+    None
+}
@@ -0,0 +1,489 @@
+// What happens when it gets too loud. The monitor only knows "enforce" or
+// "don't"; each action decides what that means for the machine.
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use tracing::info;
+
+use crate::config::{self, Config, DegradeConfig, DimConfig, DuckConfig, Mode, MuteConfig, ObsConfig};
+use crate::display;
+use crate::error::Error;
+use crate::hosts;
+use crate::iface::{self, set_iface, NetController};
+use crate::obs;
+use crate::monitor::RunArgs;
+use crate::plugin;
+use crate::session;
+use crate::shaping;
+use crate::state::{self, State};
+use crate::volume;
+
+pub trait Action: Send + Sync {
+    // short description for logs and `status`
+    fn describe(&self) -> String;
+    // called when a cut starts or ends, and again after a failure (at most
+    // once a second), so it must be safe to repeat
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error>;
+    // what to persist while enforced, so a later run can undo it
+    fn state(&self) -> State;
+    // whether it does anything with `shape`
+    fn shapes(&self) -> bool {
+        false
+    }
+    // how loud it is short of a cut, 0-100 (see shaping.rs); like `apply`,
+    // must be safe to repeat
+    fn shape(&self, _level: u8) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+// Undo the action and forget the persisted state. The state is kept if the
+// undo failed, so the next start tries again.
+pub fn restore(action: &dyn Action) -> Result<(), anyhow::Error> {
+    action.apply(false)?;
+    forget(action)
+}
+
+// an action with nothing to persist (a dry run) has no file to clear, and
+// mustn't clear one left by a real run
+pub fn forget(action: &dyn Action) -> Result<(), anyhow::Error> {
+    if action.state() == State::default() {
+        return Ok(());
+    }
+    state::save(&State::default())
+}
+
+// take the network adapters down
+pub struct Disconnect {
+    pub ifaces: Vec<String>,
+    pub net: Arc<dyn NetController>,
+}
+
+impl Action for Disconnect {
+    fn describe(&self) -> String {
+        format!("disconnect {}", self.ifaces.join(", "))
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        set_iface(self.net.as_ref(), &self.ifaces, !enforce)
+    }
+
+    fn state(&self) -> State {
+        State { disabled_interfaces: self.ifaces.clone(), ..State::default() }
+    }
+}
+
+// point the listed domains at nothing, leaving the rest of the network alone
+pub struct BlockDomains {
+    pub domains: Vec<String>,
+}
+
+impl Action for BlockDomains {
+    fn describe(&self) -> String {
+        format!("block {} domain(s) via {}", self.domains.len(), hosts::hosts_path().display())
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        hosts::set_blocked(if enforce { &self.domains } else { &[] })
+    }
+
+    fn state(&self) -> State {
+        State { hosts_blocked: true, ..State::default() }
+    }
+}
+
+// make the connection bad instead of cutting it
+pub struct Degrade {
+    pub ifaces: Vec<String>,
+    pub config: DegradeConfig,
+}
+
+impl Action for Degrade {
+    fn describe(&self) -> String {
+        format!("degrade {}", self.ifaces.join(", "))
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        self.shape(if enforce { 100 } else { 0 })
+    }
+
+    fn state(&self) -> State {
+        State { shaped_interfaces: self.ifaces.clone(), ..State::default() }
+    }
+
+    fn shapes(&self) -> bool {
+        true
+    }
+
+    fn shape(&self, level: u8) -> Result<(), anyhow::Error> {
+        shaping::set(&self.ifaces, &self.config, level)
+    }
+}
+
+// turn the speakers down the louder it gets, and back up once it's quiet;
+// turning down music or a game often brings the level down on its own
+pub struct Duck {
+    config: DuckConfig,
+    // the volume before ducking, to go back to
+    original: Mutex<Option<u8>>,
+}
+
+impl Duck {
+    pub fn new(config: DuckConfig) -> Duck {
+        Duck { config, original: Mutex::new(None) }
+    }
+}
+
+impl Action for Duck {
+    fn describe(&self) -> String {
+        format!("turn the volume down (to {}% at a cut)", self.config.min_volume_pct)
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        self.shape(if enforce { 100 } else { 0 })
+    }
+
+    fn state(&self) -> State {
+        let original = self.original.lock().ok().and_then(|o| *o);
+        State { ducked_volume: original.or_else(|| volume::get().ok()), ..State::default() }
+    }
+
+    fn shapes(&self) -> bool {
+        true
+    }
+
+    // the volume is only read before the first step down, so turning it up
+    // by hand while ducked doesn't become the new original
+    fn shape(&self, level: u8) -> Result<(), anyhow::Error> {
+        let mut original = self.original.lock().map_err(|_| anyhow::anyhow!("volume state poisoned"))?;
+        if level == 0 {
+            if let Some(volume) = *original {
+                volume::set(volume)?;
+                *original = None;
+            }
+            return Ok(());
+        }
+        let volume = match *original {
+            Some(volume) => volume,
+            None => volume::get()?,
+        };
+        *original = Some(volume);
+        volume::set(shaping::capped(volume, level, self.config.min_volume_pct))
+    }
+}
+
+// turn the screen down the louder it gets, and off at a cut
+pub struct Dim {
+    config: DimConfig,
+    // the brightness before dimming, to go back to
+    original: Mutex<Option<u8>>,
+    blanked: AtomicBool,
+}
+
+impl Dim {
+    pub fn new(config: DimConfig) -> Dim {
+        Dim { config, original: Mutex::new(None), blanked: AtomicBool::new(false) }
+    }
+}
+
+impl Action for Dim {
+    fn describe(&self) -> String {
+        let at_cut = if self.config.blank { "off".to_string() } else { format!("{}%", self.config.min_brightness_pct) };
+        format!("dim the screen ({} at a cut)", at_cut)
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        self.shape(if enforce { 100 } else { 0 })
+    }
+
+    fn state(&self) -> State {
+        let original = self.original.lock().ok().and_then(|o| *o);
+        State { dimmed_brightness: original.or_else(|| display::get().ok()), ..State::default() }
+    }
+
+    fn shapes(&self) -> bool {
+        true
+    }
+
+    // as for Duck, the brightness is only read before the first step down
+    fn shape(&self, level: u8) -> Result<(), anyhow::Error> {
+        let mut original = self.original.lock().map_err(|_| anyhow::anyhow!("brightness state poisoned"))?;
+        let blank = self.config.blank && level >= 100;
+        if self.blanked.load(Ordering::SeqCst) != blank {
+            display::blank(blank)?;
+            self.blanked.store(blank, Ordering::SeqCst);
+        }
+        if level == 0 {
+            if let Some(brightness) = *original {
+                display::set(brightness)?;
+                *original = None;
+            }
+            return Ok(());
+        }
+        let brightness = match *original {
+            Some(brightness) => brightness,
+            None => display::get()?,
+        };
+        *original = Some(brightness);
+        display::set(shaping::capped(brightness, level, self.config.min_brightness_pct))
+    }
+}
+
+// mute the listed apps (a game, a music player), leaving the rest of the
+// sound, calls included, alone
+pub struct MuteApps {
+    pub config: MuteConfig,
+}
+
+impl Action for MuteApps {
+    fn describe(&self) -> String {
+        format!("mute {}", self.config.apps.join(", "))
+    }
+
+    // an app started mid-cut isn't caught until the next one
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        volume::mute_apps(&self.config.apps, enforce).map(|_| ())
+    }
+
+    fn state(&self) -> State {
+        State { muted_apps: self.config.apps.clone(), ..State::default() }
+    }
+}
+
+// lock the screen; there's no unlocking it again, that's up to the user
+#[derive(Default)]
+pub struct LockScreen {
+    // locked for this cut already, so a retried enforce (another action
+    // failing) doesn't lock it again right after they've logged back in
+    locked: AtomicBool,
+}
+
+impl Action for LockScreen {
+    fn describe(&self) -> String {
+        "lock the screen".into()
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        if enforce && !self.locked.load(Ordering::SeqCst) {
+            session::lock()?;
+        }
+        self.locked.store(enforce, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn state(&self) -> State {
+        State::default()
+    }
+}
+
+// show a "too loud" source and/or switch to another scene in OBS
+pub struct Obs {
+    config: ObsConfig,
+    // the scene we switched away from, to go back to
+    previous_scene: Mutex<Option<String>>,
+}
+
+impl Obs {
+    pub fn new(config: ObsConfig) -> Obs {
+        Obs { config, previous_scene: Mutex::new(None) }
+    }
+}
+
+impl Action for Obs {
+    fn describe(&self) -> String {
+        let mut what = Vec::new();
+        if let Some(source) = &self.config.source {
+            what.push(format!("show '{}'", source));
+        }
+        if let Some(scene) = &self.config.cut_scene {
+            what.push(format!("switch to '{}'", scene));
+        }
+        format!("OBS: {}", what.join(" and "))
+    }
+
+    // the source is shown before switching away and hidden after switching
+    // back, so without a configured `scene` it's the streamer's own one
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        let mut client = obs::Client::connect(&self.config.url, self.config.password.as_deref())?;
+        let mut previous = self.previous_scene.lock().map_err(|_| anyhow::anyhow!("OBS state poisoned"))?;
+        if !enforce {
+            if let Some(scene) = previous.take() {
+                client.request("SetCurrentProgramScene", json!({ "sceneName": scene }))?;
+            }
+        }
+        if let Some(source) = &self.config.source {
+            let scene = match &self.config.scene {
+                Some(scene) => scene.clone(),
+                None => client.request("GetCurrentProgramScene", json!({}))?["currentProgramSceneName"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            };
+            let item = client.request("GetSceneItemId", json!({ "sceneName": scene, "sourceName": source }))?;
+            client.request(
+                "SetSceneItemEnabled",
+                json!({ "sceneName": scene, "sceneItemId": item["sceneItemId"], "sceneItemEnabled": enforce }),
+            )?;
+        }
+        if let (true, Some(cut_scene)) = (enforce, &self.config.cut_scene) {
+            let current = client.request("GetCurrentProgramScene", json!({}))?["currentProgramSceneName"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            if current != *cut_scene {
+                client.request("SetCurrentProgramScene", json!({ "sceneName": cut_scene }))?;
+                *previous = Some(current);
+            }
+        }
+        Ok(())
+    }
+
+    // nothing a later run could undo without OBS anyway
+    fn state(&self) -> State {
+        State::default()
+    }
+}
+
+// several actions enforced together, e.g. disconnecting and OBS
+pub struct All(pub Vec<Arc<dyn Action>>);
+
+impl Action for All {
+    fn describe(&self) -> String {
+        self.0.iter().map(|a| a.describe()).collect::<Vec<_>>().join(" + ")
+    }
+
+    // one failing doesn't stop the others
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        let errors: Vec<String> = self.0.iter().filter_map(|a| a.apply(enforce).err()).map(|e| e.to_string()).collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(errors.join("; ")))
+        }
+    }
+
+    fn state(&self) -> State {
+        let states: Vec<State> = self.0.iter().map(|a| a.state()).collect();
+        State {
+            disabled_interfaces: states.iter().flat_map(|s| s.disabled_interfaces.clone()).collect(),
+            hosts_blocked: states.iter().any(|s| s.hosts_blocked),
+            shaped_interfaces: states.iter().flat_map(|s| s.shaped_interfaces.clone()).collect(),
+            ducked_volume: states.iter().find_map(|s| s.ducked_volume),
+            dimmed_brightness: states.iter().find_map(|s| s.dimmed_brightness),
+            muted_apps: states.iter().flat_map(|s| s.muted_apps.clone()).collect(),
+        }
+    }
+
+    fn shapes(&self) -> bool {
+        self.0.iter().any(|a| a.shapes())
+    }
+
+    fn shape(&self, level: u8) -> Result<(), anyhow::Error> {
+        let errors: Vec<String> = self.0.iter().filter_map(|a| a.shape(level).err()).map(|e| e.to_string()).collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(errors.join("; ")))
+        }
+    }
+}
+
+// `--dry-run`: say what the wrapped action would do instead of doing it
+pub struct DryRun {
+    inner: Arc<dyn Action>,
+    enforcing: AtomicBool,
+    level: AtomicU8,
+}
+
+impl Action for DryRun {
+    fn describe(&self) -> String {
+        format!("dry run ({})", self.inner.describe())
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        if self.enforcing.swap(enforce, Ordering::SeqCst) != enforce {
+            let verb = if enforce { "enforce" } else { "restore" };
+            info!("[dry run] would {}: {}", verb, self.inner.describe());
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> State {
+        State::default()
+    }
+
+    fn shapes(&self) -> bool {
+        self.inner.shapes()
+    }
+
+    fn shape(&self, level: u8) -> Result<(), anyhow::Error> {
+        if self.level.swap(level, Ordering::SeqCst) != level {
+            info!("[dry run] would shape at {}%: {}", level, self.inner.describe());
+        }
+        Ok(())
+    }
+}
+
+// the action configured for this run
+pub fn build(args: &RunArgs) -> Result<Arc<dyn Action>, anyhow::Error> {
+    let config = config::load(args.config.as_deref())?;
+    let mut actions: Vec<Arc<dyn Action>> = Vec::new();
+    match config.mode {
+        Mode::Disconnect => {
+            let ifaces = interfaces(&args.interface, &config)?;
+            actions.push(Arc::new(Disconnect { ifaces, net: Arc::new(iface::System) }))
+        }
+        Mode::BlockDomains => actions.push(Arc::new(BlockDomains { domains: config.blocked_domains })),
+        Mode::Degrade => {
+            actions.push(Arc::new(Degrade { ifaces: interfaces(&args.interface, &config)?, config: config.degrade }))
+        }
+        Mode::None => {}
+    }
+    if config.lock_screen {
+        actions.push(Arc::new(LockScreen::default()));
+    }
+    if let Some(duck) = config.duck {
+        actions.push(Arc::new(Duck::new(duck)));
+    }
+    if let Some(dim) = config.dim {
+        actions.push(Arc::new(Dim::new(dim)));
+    }
+    if let Some(mute) = config.mute {
+        actions.push(Arc::new(MuteApps { config: mute }));
+    }
+    if let Some(obs) = config.obs {
+        actions.push(Arc::new(Obs::new(obs)));
+    }
+    for plugin in plugin::load_all(config.plugin_dir.as_deref(), &config.plugins)? {
+        actions.push(Arc::new(plugin));
+    }
+    // checked here rather than in config::load, since drop-in plugins count
+    if actions.is_empty() {
+        let message = "mode = \"none\" needs something else to do at a cut: lock_screen, a [duck], [dim], [mute] or [obs] \
+                       section, or a plugin";
+        return Err(Error::Config(message.into()).into());
+    }
+    let action: Arc<dyn Action> = match actions.len() {
+        1 => actions.remove(0),
+        _ => Arc::new(All(actions)),
+    };
+    if args.dry_run {
+        return Ok(Arc::new(DryRun { inner: action, enforcing: AtomicBool::new(false), level: AtomicU8::new(0) }));
+    }
+    Ok(action)
+}
+
+// OBS, the sound and the screen alone can be driven without administrator rights
+pub fn needs_elevation(args: &RunArgs) -> Result<bool, anyhow::Error> {
+    Ok(!args.dry_run && config::load(args.config.as_deref())?.mode != Mode::None)
+}
+
+// `--interface` wins over the config file, which wins over detection
+pub fn interfaces(cli: &[String], config: &Config) -> Result<Vec<String>, anyhow::Error> {
+    if cli.is_empty() {
+        iface::resolve(&config.interfaces)
+    } else {
+        iface::resolve(cli)
+    }
+}
@@ -0,0 +1,176 @@
+// Optional embedded HTTP API for dashboards and home automation:
+//
+//   GET  /status    current snapshot (same as `shhh status`)
+//   GET  /history   recent windows, `?since=<unix ms>&limit=<n>`
+//   GET  /violations  the start of each cut, `?since=<unix ms>`
+//   POST /pause, /resume, /restore, /recalibrate, /stop
+//   POST /profile/<name>   switch profile (`auto` follows their hours again)
+//   GET  /live      WebSocket, one JSON `{time_ms, db, pct, state}` per window
+//   GET  /          the bundled dashboard page
+//
+// Everything but the dashboard page itself needs `Authorization: Bearer
+// <token>` (or `?token=<token>`, which is the only option for browser
+// WebSockets); the page asks for the token and keeps it in localStorage.
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::bus::Event;
+use crate::ipc;
+use crate::monitor::Handle;
+
+type JsonResponse = Response<Cursor<Vec<u8>>>;
+
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+pub fn serve(addr: SocketAddr, token: String, handle: Handle) -> Result<(), anyhow::Error> {
+    let server = Server::http(addr).map_err(|e| anyhow::anyhow!("could not bind {}: {}", addr, e))?;
+    info!("HTTP API listening on http://{}", addr);
+
+    thread::spawn(move || {
+        for req in server.incoming_requests() {
+            if req.url().split('?').next() == Some("/live") && is_upgrade(&req) {
+                let query = req.url().split_once('?').map(|(_, q)| q.to_string()).unwrap_or_default();
+                if authorized(&req, &query, &token) {
+                    let handle = handle.clone();
+                    thread::spawn(move || live(req, handle));
+                    continue;
+                }
+            }
+            let response = route(&req, &token, &handle);
+            if let Err(e) = req.respond(response) {
+                warn!("HTTP response error: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn route(req: &Request, token: &str, handle: &Handle) -> JsonResponse {
+    let (path, query) = req.url().split_once('?').unwrap_or((req.url(), ""));
+    if *req.method() == Method::Get && (path == "/" || path == "/index.html") {
+        return Response::from_string(DASHBOARD)
+            .with_header(Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap());
+    }
+    if !authorized(req, query, token) {
+        return error(401, "missing or invalid token");
+    }
+
+    match (req.method(), path) {
+        (Method::Get, "/status") => match handle.status.lock() {
+            Ok(s) => json(200, &*s),
+            Err(_) => error(500, "status unavailable"),
+        },
+        (Method::Get, "/history") => {
+            let since = param(query, "since").unwrap_or(0);
+            let limit = param(query, "limit").unwrap_or(usize::MAX as u64) as usize;
+            match handle.history.lock() {
+                Ok(h) => json(200, &h.since(since, limit)),
+                Err(_) => error(500, "history unavailable"),
+            }
+        }
+        (Method::Get, "/violations") => {
+            let since = param(query, "since").unwrap_or(0);
+            match handle.history.lock() {
+                Ok(h) => json(200, &h.violations_since(since)),
+                Err(_) => error(500, "history unavailable"),
+            }
+        }
+        (Method::Post, "/pause") => control(handle, ipc::Request::Pause),
+        (Method::Post, "/resume") => control(handle, ipc::Request::Resume),
+        (Method::Post, "/restore") => control(handle, ipc::Request::Restore),
+        (Method::Post, "/recalibrate") => control(handle, ipc::Request::Recalibrate),
+        (Method::Post, "/stop") => control(handle, ipc::Request::Stop),
+        (Method::Post, p) if p.starts_with("/profile/") => {
+            let name = &p["/profile/".len()..];
+            if !ipc::known_profile(&handle.status, name) {
+                return error(404, "no such profile");
+            }
+            control(handle, ipc::Request::Profile { name: name.to_string() })
+        }
+        (Method::Get, "/live") => error(400, "expected a WebSocket upgrade"),
+        (_, "/status" | "/history" | "/violations" | "/pause" | "/resume" | "/restore" | "/recalibrate" | "/stop" | "/live") => {
+            error(405, "method not allowed")
+        }
+        _ => error(404, "not found"),
+    }
+}
+
+fn is_upgrade(req: &Request) -> bool {
+    req.headers().iter().any(|h| h.field.equiv("Upgrade") && h.value.as_str().eq_ignore_ascii_case("websocket"))
+}
+
+// WebSocket stream of live windows; runs on its own thread per client
+fn live(req: Request, handle: Handle) {
+    let Some(key) = req.headers().iter().find(|h| h.field.equiv("Sec-WebSocket-Key")) else {
+        let _ = req.respond(error(400, "missing Sec-WebSocket-Key"));
+        return;
+    };
+    let accept = tungstenite::handshake::derive_accept_key(key.value.as_bytes());
+    let response = Response::empty(101)
+        .with_header(Header::from_bytes("Upgrade", "websocket").unwrap())
+        .with_header(Header::from_bytes("Sec-WebSocket-Accept", accept).unwrap());
+    let stream = req.upgrade("websocket", response);
+    let mut ws = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+    let events = handle.bus.subscribe();
+    loop {
+        let msg = match events.recv_timeout(Duration::from_secs(10)) {
+            Ok(Event::Window(entry)) => Message::text(serde_json::to_string(&entry).unwrap_or_default()),
+            Ok(_) => continue,
+            // keep idle connections (e.g. while paused in calibration) alive
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => Message::Ping(Default::default()),
+            Err(_) => break,
+        };
+        if ws.send(msg).is_err() {
+            break;
+        }
+    }
+}
+
+fn control(handle: &Handle, req: ipc::Request) -> JsonResponse {
+    match handle.control.send(req) {
+        Ok(()) => json(200, &serde_json::json!({ "ok": true })),
+        Err(_) => error(503, "monitor is shutting down"),
+    }
+}
+
+fn authorized(req: &Request, query: &str, token: &str) -> bool {
+    let bearer = req
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "));
+    let from_query = query.split('&').find_map(|kv| kv.strip_prefix("token="));
+    bearer.or(from_query).is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn param(query: &str, key: &str) -> Option<u64> {
+    query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .find(|(k, _)| *k == key)
+        .and_then(|(_, v)| v.parse().ok())
+}
+
+fn json<T: Serialize + ?Sized>(code: u16, body: &T) -> JsonResponse {
+    let body = serde_json::to_string(body).unwrap_or_else(|_| "null".into());
+    Response::from_string(body)
+        .with_status_code(code)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+fn error(code: u16, msg: &str) -> JsonResponse {
+    json(code, &serde_json::json!({ "error": msg }))
+}
@@ -0,0 +1,168 @@
+// Microphone capture and level math.
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config;
+use crate::error::Error;
+
+pub const SAMPLE_WINDOW_MS: u64 = 500; // window duration for RMS
+
+// a running capture stream (or file replay); samples arrive on `rx` as f32
+// in -1.0..1.0, and `rx` disconnects when a file runs out
+pub struct Input {
+    pub rx: mpsc::Receiver<f32>,
+    pub samples_per_window: usize,
+    pub sample_rate: u32,
+    pub channels: u16,
+    _stream: Option<cpal::Stream>,
+}
+
+// `name` picks a device from `shhh devices`; None is the system default
+pub fn open_input(name: Option<&str>) -> Result<Input, anyhow::Error> {
+    // CPAL setup
+    let host = cpal::default_host();
+    let device = match name {
+        Some(name) => find_device(&host, name)?,
+        None => host.default_input_device().ok_or(Error::NoInputDevice)?,
+    };
+    let config = device.default_input_config().map_err(|e| Error::InputDevice(e.to_string()))?;
+    info!("Using input device: {}", device.name()?);
+    debug!("Input config: {:?}", config);
+
+    // channel: callback will send f32 samples to aggregator
+    let (tx, rx) = mpsc::channel::<f32>();
+    let samples_per_window = (config.sample_rate().0 as u64 * SAMPLE_WINDOW_MS / 1000) as usize;
+    let (sample_rate, channels) = (config.sample_rate().0, config.channels());
+
+    // build input stream depending on sample format
+    let tx_arc = Arc::new(Mutex::new(tx));
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _| {
+                if let Ok(tx) = tx_arc.lock() {
+                    for &s in data { let _ = tx.send(s); }
+                }
+            },
+            move |err| warn!("Stream error: {}", err)
+        ),
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[i16], _| {
+                if let Ok(tx) = tx_arc.lock() {
+                    for &s in data { let _ = tx.send(s as f32 / 32768.0); }
+                }
+            },
+            move |err| warn!("Stream error: {}", err)
+        ),
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[u16], _| {
+                if let Ok(tx) = tx_arc.lock() {
+                    for &s in data {
+                        // convert unsigned 0..65535 to -1.0..1.0
+                        let f = (s as f32 / 65535.0) * 2.0 - 1.0;
+                        let _ = tx.send(f);
+                    }
+                }
+            },
+            move |err| warn!("Stream error: {}", err)
+        ),
+    }
+    .map_err(|e| Error::InputDevice(e.to_string()))?;
+    stream.play().map_err(|e| Error::InputDevice(e.to_string()))?;
+
+    Ok(Input { rx, samples_per_window, sample_rate, channels, _stream: Some(stream) })
+}
+
+// Replay a WAV file as if it were the microphone, `speed` times faster than
+// real time. Channels are interleaved just like a live stream.
+pub fn open_file(path: &Path, speed: f32) -> Result<Input, anyhow::Error> {
+    if !(speed > 0.0 && speed.is_finite()) {
+        anyhow::bail!("replay speed must be a positive number");
+    }
+    let reader = hound::WavReader::open(path).map_err(|e| anyhow::anyhow!("could not open {}: {}", path.display(), e))?;
+    let spec = reader.spec();
+    info!("Using input file: {} ({} Hz, {} ch, {}x)", path.display(), spec.sample_rate, spec.channels, speed);
+
+    let samples_per_second = spec.sample_rate as usize * spec.channels as usize;
+    let samples_per_window = samples_per_second * SAMPLE_WINDOW_MS as usize / 1000;
+    // bounded, so a fast replay can't read the whole file into memory
+    let (tx, rx) = mpsc::sync_channel::<f32>(samples_per_window * 2);
+
+    thread::spawn(move || {
+        let chunk = samples_per_second / 100; // 10ms
+        let pause = Duration::from_secs_f32(0.01 / speed);
+        let scale = 2f32.powi(spec.bits_per_sample as i32 - 1);
+        let samples: Box<dyn Iterator<Item = f32>> = match spec.sample_format {
+            hound::SampleFormat::Float => Box::new(reader.into_samples::<f32>().map_while(Result::ok)),
+            hound::SampleFormat::Int => {
+                Box::new(reader.into_samples::<i32>().map_while(Result::ok).map(move |s| s as f32 / scale))
+            }
+        };
+        for (i, s) in samples.enumerate() {
+            if tx.send(s).is_err() {
+                return;
+            }
+            if i % chunk == chunk - 1 {
+                thread::sleep(pause);
+            }
+        }
+    });
+
+    Ok(Input { rx, samples_per_window, sample_rate: spec.sample_rate, channels: spec.channels, _stream: None })
+}
+
+// by exact name, or a part of the name only one device has
+fn find_device(host: &cpal::Host, name: &str) -> Result<cpal::Device, anyhow::Error> {
+    let mut devices: Vec<(String, cpal::Device)> =
+        host.input_devices()?.filter_map(|d| Some((d.name().ok()?, d))).collect();
+    if let Some(i) = devices.iter().position(|(n, _)| n == name) {
+        return Ok(devices.swap_remove(i).1);
+    }
+    let lower = name.to_lowercase();
+    let partial: Vec<usize> = (0..devices.len()).filter(|&i| devices[i].0.to_lowercase().contains(&lower)).collect();
+    if let [i] = partial[..] {
+        return Ok(devices.swap_remove(i).1);
+    }
+    let names: Vec<&str> = devices.iter().map(|(n, _)| n.as_str()).collect();
+    let problem = match (partial.len(), config::closest(name, names.iter().copied())) {
+        (0, Some(close)) => format!("no input device named \"{}\"; did you mean \"{}\"?", name, close),
+        (0, None) => format!("no input device named \"{}\"", name),
+        (n, _) => format!("\"{}\" matches {} input devices; use more of the name", name, n),
+    };
+    if names.is_empty() {
+        return Err(Error::NoInputDevice.into());
+    }
+    Err(Error::Config(format!(
+        "{} (set by --device or input_device in the config); the input devices are: {}",
+        problem,
+        names.iter().map(|n| format!("\"{}\"", n)).collect::<Vec<_>>().join(", ")
+    ))
+    .into())
+}
+
+// `shhh devices`
+pub fn list_devices() -> Result<(), anyhow::Error> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+    println!("Input devices ({:?} host):", host.id());
+    for device in host.input_devices()? {
+        let name = device.name()?;
+        let marker = if Some(&name) == default_name.as_ref() { "*" } else { " " };
+        match device.default_input_config() {
+            Ok(c) => println!(" {} {} ({} Hz, {} ch, {:?})", marker, name, c.sample_rate().0, c.channels(), c.sample_format()),
+            Err(_) => println!(" {} {}", marker, name),
+        }
+    }
+    Ok(())
+}
+
+pub fn rms_to_db(rms: f32) -> f32 {
+    if rms <= 1e-12 { return -999.0; }
+    20.0 * rms.log10()
+}
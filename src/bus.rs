@@ -0,0 +1,99 @@
+// The monitor's internal event bus. run_loop produces events (each metered
+// window, the policy's decisions, what the enforcer did) and consumers pick
+// them up on their own threads: the log, rewards and notifications, history
+// and the live WebSocket stream. An integration subscribes here instead of
+// growing the loop, and a slow one only delays itself.
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use tracing::warn;
+
+use crate::history::Entry;
+use crate::policy::Decision;
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    // a metered window, with the state the policy is in after it
+    Window(Entry),
+    // what the policy decided, whether from a window, a control request or
+    // the script
+    Decisions(Vec<Decision>),
+    // a backend call finished; `error` is None when it worked
+    Enforced { enforce: bool, error: Option<String> },
+    // a degrading action moved to this level short of a cut
+    Shaped { level: u8, error: Option<String> },
+    // a different profile took over (None: the base rules)
+    Profile { name: Option<String>, min_db: f32, max_db: f32 },
+    // from the script
+    Warning(String),
+    Notify(String),
+}
+
+#[derive(Clone, Default)]
+pub struct Bus {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<Event>>>>,
+}
+
+impl Bus {
+    pub fn subscribe(&self) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut subs) = self.subscribers.lock() {
+            subs.push(tx);
+        }
+        rx
+    }
+
+    pub fn publish(&self, event: Event) {
+        if let Ok(mut subs) = self.subscribers.lock() {
+            // a failed send means the subscriber hung up
+            subs.retain(|tx| tx.send(event.clone()).is_ok());
+        }
+    }
+
+    // hang up on every subscriber, so their threads finish once they've
+    // caught up
+    pub fn close(&self) {
+        if let Ok(mut subs) = self.subscribers.lock() {
+            subs.clear();
+        }
+    }
+}
+
+// a consumer on its own thread, until the bus is closed
+pub fn spawn(bus: &Bus, name: &str, mut consume: impl FnMut(Event) + Send + 'static) -> Option<JoinHandle<()>> {
+    let events = bus.subscribe();
+    let spawned = thread::Builder::new().name(name.into()).spawn(move || events.into_iter().for_each(&mut consume));
+    if let Err(e) = &spawned {
+        warn!("Could not start the {} thread: {}", name, e);
+    }
+    spawned.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribers_get_everything_until_closed() {
+        let bus = Bus::default();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let consumer = {
+            let seen = seen.clone();
+            spawn(&bus, "test", move |event| {
+                if let Event::Warning(text) = event {
+                    seen.lock().unwrap().push(text);
+                }
+            })
+        };
+        let gone = bus.subscribe();
+        drop(gone);
+        bus.publish(Event::Warning("one".into()));
+        bus.publish(Event::Notify("ignored".into()));
+        bus.publish(Event::Warning("two".into()));
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+        bus.close();
+        consumer.unwrap().join().unwrap();
+        assert_eq!(*seen.lock().unwrap(), ["one", "two"]);
+    }
+}
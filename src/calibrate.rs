@@ -0,0 +1,143 @@
+// `shhh calibrate`: measure the room the way `run` does at startup and print
+// the thresholds it gives, or say why the run can't be trusted (see
+// `meter::check_calibration`). With --wizard it takes the user through it:
+// ten seconds of quiet with the level shown as it comes, the thresholds, and
+// on confirmation saves them to the config as fixed `soft_db`/`cut_db`, so
+// later runs skip calibration and use these.
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::audio;
+use crate::config;
+use crate::meter::{self, LevelSource, Meter};
+use crate::policy::{self, DIGITAL_SILENCE_DB};
+
+// how long the wizard listens for
+const WIZARD_SECS: u64 = 10;
+// width of the live level bar, from digital silence to full scale
+const BAR: usize = 40;
+
+#[derive(clap::Args, Debug)]
+pub struct CalibrateArgs {
+    /// Walk through it: stay quiet for 10 s while the level is shown, then save the thresholds to the config
+    #[arg(long)]
+    pub wizard: bool,
+    /// Config file to read, and for --wizard to save to (default: as for `run`)
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Microphone to calibrate, by (part of) its name in `shhh devices` (default: as for `run`)
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+    /// Calibrate on a WAV file instead of the microphone
+    #[arg(long, value_name = "WAV")]
+    pub input_file: Option<PathBuf>,
+}
+
+pub fn run(args: &CalibrateArgs) -> Result<(), anyhow::Error> {
+    let config = config::load(args.config.as_deref())?;
+    let input = match &args.input_file {
+        Some(path) => audio::open_file(path, 100.0)?,
+        None => audio::open_input(args.device.as_deref().or(config.input_device.as_deref()))?,
+    };
+    let mut meter = Meter::new(input).with_window(config.window_ms, config.hop_ms).with_lowpass(config.lowpass_hz);
+    let rules = policy::Rules::from_config(&config);
+    if !args.wizard {
+        println!("Measuring ambient noise, stay quiet...");
+        let ambient_db = meter.calibrate(&mut || {})?;
+        let (min_db, max_db) = rules.thresholds(ambient_db);
+        println!("Ambient {:.1} dBFS -> min {:.1}, max {:.1}", ambient_db, min_db, max_db);
+        return Ok(());
+    }
+
+    let count = (WIZARD_SECS * 1000 / meter.hop().as_millis().max(1) as u64).max(1) as usize;
+    let ambient_db = loop {
+        println!("Stay quiet for {} seconds: no talking, no music, just the room as it usually is.", WIZARD_SECS);
+        let mut left = count;
+        let run = meter::calibrate_with(&mut meter, count, |level| {
+            match level {
+                Some(db) => {
+                    left -= 1;
+                    let secs = (left as u64 * WIZARD_SECS) as f32 / count as f32;
+                    print!("\r{:>5.1} s  {:>6.1} dBFS  {:<width$}", secs, db, bar(db), width = BAR);
+                }
+                None => print!("\r{:>5} s  {:>6} dBFS  {:<width$}", "", "-", "(no audio)", width = BAR),
+            }
+            let _ = std::io::stdout().flush();
+        });
+        println!();
+        match run {
+            Ok(ambient_db) => break ambient_db,
+            Err(e) if !meter.exhausted() && ask(&format!("That didn't work: {}. Try again? [y/N] ", e))? => {}
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    // from the offsets, whatever fixed thresholds the config has now
+    let (min_db, max_db) = (ambient_db + rules.soft_offset_db, ambient_db + rules.cut_offset_db);
+    println!("Ambient {:.1} dBFS -> min {:.1}, max {:.1}", ambient_db, min_db, max_db);
+    let path = args.config.clone().unwrap_or_else(config::default_path);
+    if !ask(&format!("Save them to {} as fixed thresholds (no calibrating at startup)? [y/N] ", path.display()))? {
+        println!("Not saved.");
+        return Ok(());
+    }
+    // fixed thresholds are in dB SPL when the config converts
+    let offset = config.spl_offset_db.unwrap_or(0.0);
+    save(&path, min_db + offset, max_db + offset)?;
+    println!("Saved to {}.", path.display());
+    Ok(())
+}
+
+// the level from digital silence up to full scale
+fn bar(db: f32) -> String {
+    let filled = ((db - DIGITAL_SILENCE_DB) / -DIGITAL_SILENCE_DB * BAR as f32).clamp(0.0, BAR as f32) as usize;
+    "#".repeat(filled)
+}
+
+// a yes/no question; anything but yes is no
+fn ask(prompt: &str) -> Result<bool, anyhow::Error> {
+    print!("{}", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// set soft_db/cut_db in the config, leaving the rest of it (comments too) as
+// it was; the file is created if there isn't one
+fn save(path: &Path, soft: f32, cut: f32) -> Result<(), anyhow::Error> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => anyhow::bail!("could not read {}: {}", path.display(), e),
+    };
+    let text =
+        with_thresholds(&text, soft, cut).map_err(|e| anyhow::anyhow!("invalid config {}: {}", path.display(), e))?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| anyhow::anyhow!("could not create {}: {}", dir.display(), e))?;
+    }
+    std::fs::write(path, text).map_err(|e| anyhow::anyhow!("could not write {}: {}", path.display(), e))
+}
+
+fn with_thresholds(text: &str, soft: f32, cut: f32) -> Result<String, toml_edit::TomlError> {
+    let mut doc: toml_edit::DocumentMut = text.parse()?;
+    // a tenth of a dB, without f32's noise in the file
+    let tenth = |db: f32| (db as f64 * 10.0).round() / 10.0;
+    doc["soft_db"] = toml_edit::value(tenth(soft));
+    doc["cut_db"] = toml_edit::value(tenth(cut));
+    Ok(doc.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_keeps_the_rest_of_the_config() {
+        let text = "# the study\nmode = \"disconnect\"\nsoft_db = -40\n\n[tamper]\nafter_secs = 5\n";
+        let saved = with_thresholds(text, -47.34, -17.26).unwrap();
+        assert!(saved.starts_with("# the study\nmode = \"disconnect\"\nsoft_db = -47.3\n"), "{}", saved);
+        let config: config::Config = toml::from_str(&saved).unwrap();
+        assert_eq!((config.soft_db, config.cut_db), (Some(-47.3), Some(-17.3)));
+        assert_eq!(config.tamper.after_secs, 5);
+    }
+}
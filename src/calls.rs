@@ -0,0 +1,223 @@
+// Video calls: a loud meeting shouldn't cut the connection the meeting runs
+// on. With `[calls]` in the config, a background thread looks every few
+// seconds for one of the listed apps using the microphone (PulseAudio or
+// PipeWire clients on Linux, the privacy settings' "in use" record on
+// Windows) or, if asked to and on Windows, in the foreground. While it finds
+// one, the call profile takes over (see profile.rs).
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::config::CallConfig;
+use crate::i18n::tr;
+
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+
+// the configured app that is in a call right now, if any
+#[derive(Clone, Default)]
+pub struct Watcher {
+    current: Arc<Mutex<Option<String>>>,
+}
+
+impl Watcher {
+    pub fn start(config: &CallConfig) -> Watcher {
+        let watcher = Watcher::default();
+        let (apps, with_foreground) = (config.apps.clone(), config.foreground);
+        let publish = watcher.clone();
+        thread::spawn(move || loop {
+            let mut users = mic_users();
+            if with_foreground {
+                users.extend(foreground());
+            }
+            let found = users.iter().find_map(|user| matching(user, &apps)).cloned();
+            match (&found, publish.current()) {
+                (Some(app), None) => info!("{}", tr!("call-detected", app = app.as_str())),
+                (None, Some(app)) => info!("{}", tr!("call-over", app = app)),
+                _ => {}
+            }
+            publish.set(found);
+            thread::sleep(CHECK_EVERY);
+        });
+        watcher
+    }
+
+    pub fn current(&self) -> Option<String> {
+        self.current.lock().ok().and_then(|c| c.clone())
+    }
+
+    pub fn set(&self, app: Option<String>) {
+        if let Ok(mut c) = self.current.lock() {
+            *c = app;
+        }
+    }
+}
+
+// the configured app this program (name or path) is, if any; case-insensitive
+// on the file name, so "zoom" finds "/opt/zoom/zoom" and "Zoom.exe"
+pub fn matching<'a>(program: &str, apps: &'a [String]) -> Option<&'a String> {
+    let name = program.rsplit(['/', '\\']).next().unwrap_or(program).to_lowercase();
+    apps.iter().find(|app| name.contains(&app.to_lowercase()))
+}
+
+#[cfg(not(windows))]
+fn mic_users() -> Vec<String> {
+    match std::process::Command::new("pactl").args(["list", "source-outputs"]).output() {
+        Ok(out) if out.status.success() => source_outputs(&String::from_utf8_lossy(&out.stdout)),
+        _ => Vec::new(),
+    }
+}
+
+// the binaries and app names of `pactl list source-outputs` (one per stream
+// recording from a microphone)
+#[cfg(not(windows))]
+fn source_outputs(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().split_once(" = ")?;
+            matches!(key, "application.process.binary" | "application.name").then(|| value.trim_matches('"').to_string())
+        })
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn foreground() -> Option<String> {
+    None
+}
+
+#[cfg(windows)]
+use win::{foreground, mic_users};
+
+#[cfg(windows)]
+mod win {
+    use std::ptr::{null, null_mut};
+
+    use windows_sys::Win32::Foundation::{CloseHandle, ERROR_SUCCESS};
+    use windows_sys::Win32::System::Registry::{
+        RegCloseKey, RegEnumKeyExW, RegOpenKeyExW, RegQueryValueExW, HKEY, HKEY_USERS, KEY_READ,
+    };
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowThreadProcessId};
+
+    const MICROPHONE: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\CapabilityAccessManager\\ConsentStore\\microphone";
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+
+    // an open registry key, closed on drop
+    struct Key(HKEY);
+
+    impl Key {
+        fn open(parent: HKEY, path: &str) -> Option<Key> {
+            let mut key = null_mut();
+            let err = unsafe { RegOpenKeyExW(parent, wide(path).as_ptr(), 0, KEY_READ, &mut key) };
+            (err == ERROR_SUCCESS).then_some(Key(key))
+        }
+
+        fn subkeys(&self) -> Vec<String> {
+            let mut names = Vec::new();
+            for i in 0.. {
+                let mut buf = [0u16; 512];
+                let mut len = buf.len() as u32;
+                let err = unsafe {
+                    RegEnumKeyExW(self.0, i, buf.as_mut_ptr(), &mut len, null(), null_mut(), null_mut(), null_mut())
+                };
+                if err != ERROR_SUCCESS {
+                    break;
+                }
+                names.push(String::from_utf16_lossy(&buf[..len as usize]));
+            }
+            names
+        }
+
+        fn qword(&self, name: &str) -> Option<u64> {
+            let mut value = 0u64;
+            let mut len = 8u32;
+            let err = unsafe {
+                RegQueryValueExW(self.0, wide(name).as_ptr(), null(), null_mut(), &mut value as *mut u64 as *mut u8, &mut len)
+            };
+            (err == ERROR_SUCCESS).then_some(value)
+        }
+
+        // a consent-store entry that has started using the mic and not stopped
+        fn in_use(&self) -> bool {
+            self.qword("LastUsedTimeStart").unwrap_or(0) != 0 && self.qword("LastUsedTimeStop") == Some(0)
+        }
+    }
+
+    impl Drop for Key {
+        fn drop(&mut self) {
+            unsafe { RegCloseKey(self.0) };
+        }
+    }
+
+    // Store apps by package name, desktop apps by path ('#' for '\'); every
+    // user's hive, since the service doesn't run as the one in the call
+    pub fn mic_users() -> Vec<String> {
+        let mut users = Vec::new();
+        let Some(hku) = Key::open(HKEY_USERS, "") else { return users };
+        for sid in hku.subkeys() {
+            let Some(mic) = Key::open(hku.0, &format!("{}\\{}", sid, MICROPHONE)) else { continue };
+            for app in mic.subkeys() {
+                let Some(key) = Key::open(mic.0, &app) else { continue };
+                if app == "NonPackaged" {
+                    for program in key.subkeys() {
+                        if Key::open(key.0, &program).is_some_and(|k| k.in_use()) {
+                            users.push(program.replace('#', "\\"));
+                        }
+                    }
+                } else if key.in_use() {
+                    users.push(app);
+                }
+            }
+        }
+        users
+    }
+
+    // the program behind the foreground window (nothing from a service,
+    // which has no desktop)
+    pub fn foreground() -> Option<String> {
+        unsafe {
+            let window = GetForegroundWindow();
+            if window.is_null() {
+                return None;
+            }
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(window, &mut pid);
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return None;
+            }
+            let mut buf = [0u16; 1024];
+            let mut len = buf.len() as u32;
+            let ok = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buf.as_mut_ptr(), &mut len);
+            CloseHandle(process);
+            (ok != 0).then(|| String::from_utf16_lossy(&buf[..len as usize]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apps_match_by_file_name() {
+        let apps = vec!["zoom".to_string(), "Teams".to_string()];
+        assert_eq!(matching("/opt/zoom/zoom", &apps), Some(&apps[0]));
+        assert_eq!(matching("C:\\Program Files\\Microsoft\\MSTeams.exe", &apps), Some(&apps[1]));
+        assert_eq!(matching("C:\\zoom\\notepad.exe", &apps), None);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn source_outputs_name_their_programs() {
+        let text = "Source Output #42\n\tDriver: PipeWire\n\tProperties:\n\t\tapplication.name = \"ZOOM VoiceEngine\"\n\
+                    \t\tapplication.process.binary = \"zoom\"\n\t\tmedia.name = \"capture\"\n";
+        assert_eq!(source_outputs(text), ["ZOOM VoiceEngine", "zoom"]);
+    }
+}
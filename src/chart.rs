@@ -0,0 +1,183 @@
+// `shhh chart`: the running monitor's recorded levels as a PNG, with the
+// thresholds that were in force and the cuts shaded, for showing someone when
+// and how loud it got. The monitor keeps the last ~24h in memory (history.rs)
+// and hands it over through the control channel.
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, TimeZone};
+use plotters::prelude::*;
+
+use crate::history::{self, Entry};
+use crate::ipc::{self, Request, Response};
+
+// windows further apart than this are drawn as separate lines (the monitor
+// wasn't running in between)
+const GAP_MS: u64 = 5_000;
+
+#[derive(clap::Args, Debug)]
+pub struct ChartArgs {
+    /// How far back to go, e.g. 90m, 24h or 2d (the monitor keeps about 24h)
+    #[arg(long, value_name = "SPAN", default_value = "24h", value_parser = parse_span)]
+    pub since: Duration,
+    /// PNG file to write
+    #[arg(long, value_name = "FILE", default_value = "noise.png")]
+    pub out: PathBuf,
+    /// Image size in pixels
+    #[arg(long, value_name = "WxH", default_value = "1600x600", value_parser = parse_size)]
+    pub size: (u32, u32),
+}
+
+// "30s", "90m", "24h", "2d"
+fn parse_span(s: &str) -> Result<Duration, String> {
+    let unit = match s.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 60 * 60,
+        Some('d') => 24 * 60 * 60,
+        _ => return Err("expected a number and a unit: s, m, h or d (e.g. 24h)".into()),
+    };
+    let n: u64 = s[..s.len() - 1].parse().map_err(|_| format!("'{}' isn't a number", &s[..s.len() - 1]))?;
+    Ok(Duration::from_secs(n * unit))
+}
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s.split_once('x').ok_or("expected WIDTHxHEIGHT, e.g. 1600x600")?;
+    match (w.parse(), h.parse()) {
+        (Ok(w @ 200..), Ok(h @ 150..)) => Ok((w, h)),
+        _ => Err("expected WIDTHxHEIGHT of at least 200x150".into()),
+    }
+}
+
+pub fn run(args: &ChartArgs) -> Result<(), anyhow::Error> {
+    let since_ms = history::now_ms().saturating_sub(args.since.as_millis() as u64);
+    let entries = match ipc::send(Request::History { since_ms })? {
+        Response::History(entries) => entries,
+        Response::Error(e) => anyhow::bail!(e),
+        _ => anyhow::bail!("unexpected answer from the monitor"),
+    };
+    let entries: Vec<Entry> = entries.into_iter().filter(|e| e.db.is_finite()).collect();
+    if entries.is_empty() {
+        anyhow::bail!("nothing recorded in that span yet");
+    }
+    draw(&entries, args)?;
+    println!("Wrote {} ({} windows, {} cuts).", args.out.display(), entries.len(), cuts(&entries).len());
+    Ok(())
+}
+
+fn local(time_ms: u64) -> DateTime<Local> {
+    Local.timestamp_millis_opt(time_ms as i64).single().unwrap_or_default()
+}
+
+// runs of windows recorded back to back
+fn segments(entries: &[Entry]) -> Vec<&[Entry]> {
+    entries.chunk_by(|a, b| b.time_ms.saturating_sub(a.time_ms) <= GAP_MS).collect()
+}
+
+// (start, end) of every cut, in ms; a cut lasts until the window after it
+fn cuts(entries: &[Entry]) -> Vec<(u64, u64)> {
+    segments(entries)
+        .into_iter()
+        .flat_map(|segment| {
+            let mut spans = Vec::new();
+            let mut start = None;
+            for entry in segment {
+                match (entry.state == "CUT", start) {
+                    (true, None) => start = Some(entry.time_ms),
+                    (false, Some(s)) => {
+                        spans.push((s, entry.time_ms));
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            spans.extend(start.map(|s| (s, segment[segment.len() - 1].time_ms)));
+            spans
+        })
+        .collect()
+}
+
+// a legend label, a colour and what to plot
+type Line = (&'static str, RGBColor, fn(&Entry) -> f32);
+
+fn draw(entries: &[Entry], args: &ChartArgs) -> Result<(), anyhow::Error> {
+    let (first, last) = (local(entries[0].time_ms), local(entries[entries.len() - 1].time_ms));
+    let levels = entries.iter().flat_map(|e| [e.db, e.min_db, e.max_db]);
+    let (low, high) = levels.fold((f32::MAX, f32::MIN), |(lo, hi), db| (lo.min(db), hi.max(db)));
+    let (low, high) = ((low / 10.0).floor() * 10.0, (high / 10.0).ceil() * 10.0 + 5.0);
+    let format = match last - first {
+        span if span > chrono::Duration::hours(20) => "%a %H:%M",
+        span if span > chrono::Duration::hours(1) => "%H:%M",
+        _ => "%H:%M:%S",
+    };
+
+    let root = BitMapBackend::new(&args.out, args.size).into_drawing_area();
+    root.fill(&WHITE)?;
+    let title = format!("Noise, {} to {}", first.format("%Y-%m-%d %H:%M"), last.format("%Y-%m-%d %H:%M"));
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 22))
+        .margin(12)
+        .x_label_area_size(36)
+        .y_label_area_size(56)
+        .build_cartesian_2d(first..last, low..high)?;
+    chart
+        .configure_mesh()
+        .x_labels(12)
+        .x_label_formatter(&|t| t.format(format).to_string())
+        .y_desc("dBFS")
+        .light_line_style(WHITE.mix(0.0))
+        .draw()?;
+
+    let shade = RED.mix(0.15);
+    chart
+        .draw_series(cuts(entries).into_iter().map(|(start, end)| {
+            Rectangle::new([(local(start), high), (local(end), low)], shade.filled())
+        }))?
+        .label("cut")
+        .legend(move |(x, y)| Rectangle::new([(x, y - 5), (x + 16, y + 5)], shade.filled()));
+    let lines: [Line; 3] = [
+        ("level", BLUE, |e| e.db),
+        ("warning threshold", RGBColor(230, 140, 0), |e| e.min_db),
+        ("cut threshold", RED, |e| e.max_db),
+    ];
+    for (label, color, value) in lines {
+        for (i, segment) in segments(entries).into_iter().enumerate() {
+            let points = segment.iter().map(|e| (local(e.time_ms), value(e)));
+            let series = chart.draw_series(LineSeries::new(points, color.stroke_width(2)))?;
+            if i == 0 {
+                series.label(label).legend(move |(x, y)| PathElement::new([(x, y), (x + 16, y)], color.stroke_width(2)));
+            }
+        }
+    }
+    chart.configure_series_labels().background_style(WHITE.mix(0.85)).border_style(BLACK).draw()?;
+    root.present()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(time_ms: u64, state: &str) -> Entry {
+        Entry { time_ms, db: -40.0, pct: 0, state: state.into(), min_db: -50.0, max_db: -20.0 }
+    }
+
+    #[test]
+    fn spans_and_cuts() {
+        assert_eq!(parse_span("90m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_span("2d"), Ok(Duration::from_secs(2 * 24 * 60 * 60)));
+        assert!(parse_span("24").is_err() && parse_span("xh").is_err());
+
+        let entries = [
+            entry(0, "OK 80%"),
+            entry(500, "CUT"),
+            entry(1000, "CUT"),
+            entry(1500, "OK 90%"),
+            entry(2000, "CUT"),
+            // the monitor was stopped mid-cut and started again later
+            entry(60_000, "OK 100%"),
+        ];
+        assert_eq!(segments(&entries).len(), 2);
+        assert_eq!(cuts(&entries), [(500, 1500), (2000, 2000)]);
+    }
+}
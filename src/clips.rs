@@ -0,0 +1,207 @@
+// Evidence for "I wasn't even loud": with `[clips]` in the config, the meter
+// keeps the last few seconds of raw audio around, and each cut saves them plus
+// a few seconds after as a timestamped WAV. Old clips are pruned by count and
+// age after every save.
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, warn};
+
+use crate::config::ClipConfig;
+
+const PREFIX: &str = "shhh-";
+
+pub struct Recorder {
+    dir: PathBuf,
+    keep: usize,
+    keep_days: u64,
+    spec: hound::WavSpec,
+    // the last `before` samples, interleaved like the input
+    ring: VecDeque<f32>,
+    before: usize,
+    after: usize,
+    // a clip still collecting its "after" part, and how much is left of that
+    clip: Option<(Vec<f32>, usize)>,
+}
+
+impl Recorder {
+    pub fn new(config: &ClipConfig, sample_rate: u32, channels: u16) -> Recorder {
+        let per_sec = sample_rate as usize * channels as usize;
+        let spec = hound::WavSpec { channels, sample_rate, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let before = per_sec * config.before_secs as usize;
+        Recorder {
+            dir: config.dir.clone().unwrap_or_else(default_dir),
+            keep: config.keep,
+            keep_days: config.keep_days,
+            spec,
+            ring: VecDeque::with_capacity(before),
+            before,
+            after: per_sec * config.after_secs as usize,
+            clip: None,
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        if let Some((clip, left)) = &mut self.clip {
+            let take = (*left).min(samples.len());
+            clip.extend_from_slice(&samples[..take]);
+            *left -= take;
+            if *left == 0 {
+                self.save();
+            }
+        }
+        self.ring.extend(samples);
+        let excess = self.ring.len().saturating_sub(self.before);
+        self.ring.drain(..excess);
+    }
+
+    // start a clip from what's in the buffer; a cut while one is still being
+    // recorded is already in it
+    pub fn trigger(&mut self) {
+        if self.clip.is_some() {
+            return;
+        }
+        self.clip = Some((self.ring.iter().copied().collect(), self.after));
+        if self.after == 0 {
+            self.save();
+        }
+    }
+
+    // written on its own thread so the meter doesn't miss windows
+    fn save(&mut self) {
+        let Some((samples, _)) = self.clip.take() else {
+            return;
+        };
+        let (dir, spec, keep, keep_days) = (self.dir.clone(), self.spec, self.keep, self.keep_days);
+        thread::spawn(move || {
+            match write(&dir, spec, &samples) {
+                Ok(path) => info!("Saved clip: {}", path.display()),
+                Err(e) => warn!("Could not save clip: {}", e),
+            }
+            if let Err(e) = prune(&dir, keep, keep_days) {
+                warn!("Could not prune clips in {}: {}", dir.display(), e);
+            }
+        });
+    }
+}
+
+// the service's data directory (next to the state file), or for a regular
+// user their XDG data directory
+pub fn default_dir() -> PathBuf {
+    #[cfg(windows)]
+    let dir = PathBuf::from(std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into())).join("shhh");
+    #[cfg(unix)]
+    let dir = if unsafe { libc::geteuid() } == 0 {
+        PathBuf::from("/var/lib/shhh")
+    } else if let Some(data) = std::env::var_os("XDG_DATA_HOME") {
+        PathBuf::from(data).join("shhh")
+    } else {
+        PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".local/share/shhh")
+    };
+    dir.join("clips")
+}
+
+fn write(dir: &Path, spec: hound::WavSpec, samples: &[f32]) -> Result<PathBuf, anyhow::Error> {
+    std::fs::create_dir_all(dir)?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let mut path = dir.join(format!("{}{}.wav", PREFIX, stamp));
+    // two cuts within a second
+    for n in 2.. {
+        if !path.exists() {
+            break;
+        }
+        path = dir.join(format!("{}{}-{}.wav", PREFIX, stamp, n));
+    }
+    // under another name until it's complete, so nothing (pruning, whoever
+    // picks clips up) sees half a file
+    let part = path.with_extension("wav.part");
+    let mut writer = hound::WavWriter::create(&part, spec)?;
+    for &s in samples {
+        writer.write_sample((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    std::fs::rename(&part, &path)?;
+    Ok(path)
+}
+
+// drop clips past `keep_days` (0: no age limit), then the oldest beyond `keep`
+fn prune(dir: &Path, keep: usize, keep_days: u64) -> Result<(), anyhow::Error> {
+    let mut clips = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(PREFIX) && name.ends_with(".wav") {
+            clips.push((entry.metadata()?.modified()?, entry.path()));
+        }
+    }
+    clips.sort();
+    let max_age = Duration::from_secs(keep_days * 24 * 60 * 60);
+    let now = SystemTime::now();
+    let excess = clips.len().saturating_sub(keep);
+    for (i, (modified, path)) in clips.iter().enumerate() {
+        let old = keep_days > 0 && now.duration_since(*modified).unwrap_or_default() > max_age;
+        if i < excess || old {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("shhh-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn wavs(dir: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir).map(|d| d.map(|e| e.unwrap().path()).collect()).unwrap_or_default();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn a_clip_holds_the_audio_before_and_after() {
+        let dir = scratch("clip");
+        let config = ClipConfig { dir: Some(dir.clone()), before_secs: 2, after_secs: 1, ..Default::default() };
+        // 10 Hz mono keeps the numbers small
+        let mut recorder = Recorder::new(&config, 10, 1);
+        recorder.push(&[0.0; 50]);
+        recorder.trigger();
+        recorder.push(&[0.5; 30]);
+        // the clip only shows up as .wav once it's complete
+        let clips = || -> Vec<PathBuf> {
+            wavs(&dir).into_iter().filter(|p| p.extension().is_some_and(|e| e == "wav")).collect()
+        };
+        let t0 = Instant::now();
+        while clips().is_empty() && t0.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let clips = clips();
+        assert_eq!(clips.len(), 1);
+        let samples: Vec<i16> = hound::WavReader::open(&clips[0]).unwrap().into_samples().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), 30);
+        assert!(samples[..20].iter().all(|&s| s == 0) && samples[20..].iter().all(|&s| s > 0));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest() {
+        let dir = scratch("prune");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["shhh-1.wav", "shhh-2.wav", "shhh-3.wav", "notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        prune(&dir, 2, 0).unwrap();
+        let names: Vec<_> = wavs(&dir).iter().map(|p| p.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["notes.txt", "shhh-2.wav", "shhh-3.wav"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
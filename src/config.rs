@@ -0,0 +1,872 @@
+// Optional TOML config file. Everything has a default, so a missing file
+// behaves like an empty one; command-line options still take precedence.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::audio::SAMPLE_WINDOW_MS;
+use crate::error::Error;
+use crate::monitor::{CUT_OFFSET_DB, SOFT_OFFSET_DB};
+use crate::policy::HYSTERESIS_DB;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // what enforcing means
+    pub mode: Mode,
+    // adapters to cut together; empty means the detected default-route one
+    pub interfaces: Vec<String>,
+    // microphone by name (see `shhh devices`); unset means the default one
+    pub input_device: Option<String>,
+    // length of the window each level is measured over, and how far apart
+    // levels are (less than the window to overlap them); unset means 500ms
+    // windows one after the other
+    pub window_ms: Option<u64>,
+    pub hop_ms: Option<u64>,
+    // measure only what's below this frequency, to keep keyboard and mouse
+    // clicks out of the level
+    pub lowpass_hz: Option<f32>,
+    // judge a percentile of the recent levels (L10 and the like) instead of
+    // each window's own
+    pub percentile: Option<PercentileConfig>,
+    // short bangs as opposed to noise that keeps up
+    pub impulses: ImpulseConfig,
+    // hosts-file blocklist for `mode = "block-domains"`
+    pub blocked_domains: Vec<String>,
+    // thresholds over ambient, and how far below the cut threshold the level
+    // has to fall before a cut is lifted; unset means the built-in defaults
+    pub soft_offset_db: Option<f32>,
+    pub cut_offset_db: Option<f32>,
+    pub dead_zone_db: Option<f32>,
+    // fixed thresholds that replace the offsets and skip calibration; dBFS,
+    // or dB SPL when `spl_offset_db` says how to convert
+    pub soft_db: Option<f32>,
+    pub cut_db: Option<f32>,
+    // dB SPL minus dBFS for this microphone, from a sound level meter
+    pub spl_offset_db: Option<f32>,
+    // the shortest a cut lasts, however quickly it goes quiet again
+    pub penalty_secs: Option<u64>,
+    // how long it has to stay under the soft threshold, without a break,
+    // before a cut is lifted; unset lifts it at the first quiet window
+    pub restore_quiet_secs: Option<u64>,
+    // `[profiles.<name>]`: settings that take over during their hours
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    // how `shhh server` combines several sensors
+    pub room_policy: RoomPolicy,
+    pub rooms: BTreeMap<String, RoomConfig>,
+    pub tamper: TamperConfig,
+    // a microphone turned up so far that loud windows hit full scale
+    pub clipping: ClippingConfig,
+    // automatic gain control moving the room's level away from calibration
+    pub agc: AgcConfig,
+    pub reward: RewardConfig,
+    // `[countdown]`: a warning before a loud room is cut
+    pub countdown: CountdownConfig,
+    // sleep or shutdown after repeated cuts; off unless asked for
+    pub power: PowerConfig,
+    // how bad `mode = "degrade"` makes the connection at a cut
+    pub degrade: DegradeConfig,
+    // drive OBS as well as (or, with `mode = "none"`, instead of) the network
+    pub obs: Option<ObsConfig>,
+    // lock the screen at a cut, on top of whatever else happens
+    pub lock_screen: bool,
+    // `[duck]`: turn the speakers down as it gets louder
+    pub duck: Option<DuckConfig>,
+    // `[dim]`: turn the screen down as it gets louder, and off at a cut
+    pub dim: Option<DimConfig>,
+    // `[mute]`: silence some apps' sound at a cut
+    pub mute: Option<MuteConfig>,
+    // `[email]`: cuts and the like by mail, and a daily digest
+    pub email: Option<EmailConfig>,
+    // every window pushed to InfluxDB
+    pub influx: Option<InfluxConfig>,
+    // `[clips]`: save the audio around each cut
+    pub clips: Option<ClipConfig>,
+    // `[speech]`: words that count as a violation however quietly they're said
+    pub speech: Option<SpeechConfig>,
+    // `[voice]`: only one person being loud counts
+    pub voice: Option<VoiceConfig>,
+    // `[calls]`: go easy while a video call has the microphone
+    pub calls: Option<CallConfig>,
+    // `[script]`: custom policy logic
+    pub script: Option<ScriptConfig>,
+    // where drop-in `.wasm` plugins are loaded from (see plugin.rs), and
+    // `[plugins.<name>]` for ones that need settings or live elsewhere
+    pub plugin_dir: Option<PathBuf>,
+    pub plugins: BTreeMap<String, PluginConfig>,
+    // `[log]`: keep a log file
+    pub log: Option<LogConfig>,
+    // for messages, e.g. "de"; unset means the system's (see i18n.rs)
+    pub language: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    // unset means /var/log/shhh, or `logs` next to the config on Windows
+    pub dir: Option<PathBuf>,
+    pub rotate: Rotation,
+    // also rotate once the file gets this big; 0 means no limit
+    pub max_size_mb: u64,
+    // rotated files to keep besides the current one
+    pub keep: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig { dir: None, rotate: Rotation::Daily, max_size_mb: 10, keep: 7 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rotation {
+    #[default]
+    Daily,
+    Hourly,
+    // only by size
+    Never,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PluginConfig {
+    // unset means `<name>.wasm` in the plugin directory
+    pub path: Option<PathBuf>,
+    // handed to the plugin's `shhh_init` as JSON
+    pub settings: toml::Table,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DegradeConfig {
+    // added latency and jitter (Linux)
+    pub delay_ms: u64,
+    pub jitter_ms: u64,
+    // dropped packets (Linux)
+    pub loss_pct: f32,
+    // outgoing bandwidth (Windows, from 10 Mbit/s)
+    pub rate_kbps: u64,
+}
+
+impl Default for DegradeConfig {
+    fn default() -> Self {
+        DegradeConfig { delay_ms: 400, jitter_ms: 100, loss_pct: 5.0, rate_kbps: 256 }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptConfig {
+    // a Rhai script defining `on_window(w)`
+    pub path: PathBuf,
+    // loudness only cuts when the script says so (tampering still does)
+    pub replace_rules: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CallConfig {
+    // program names (or parts of them) that count as a call when they use the
+    // microphone (or, with `foreground`, have the foreground window)
+    pub apps: Vec<String>,
+    pub foreground: bool,
+    // the profile in force during a call; without a `[profiles.<name>]` of
+    // that name, a built-in one that only warns
+    pub profile: String,
+}
+
+impl Default for CallConfig {
+    fn default() -> Self {
+        // not Discord and the like: voice chat while gaming is what shhh is for
+        let apps = ["zoom", "teams", "webex"];
+        CallConfig { apps: apps.iter().map(|a| a.to_string()).collect(), foreground: false, profile: "call".into() }
+    }
+}
+
+// offline speech recognition (needs a build with `--features speech`)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpeechConfig {
+    // an unpacked Vosk model directory
+    pub model: PathBuf,
+    // words or short phrases, matched as whole words
+    pub keywords: Vec<String>,
+    // the shortest a keyword cut lasts
+    pub cut_secs: u64,
+    // a phrase that lifts any cut and pauses for `safe_pause_mins`
+    pub safe_word: Option<String>,
+    pub safe_pause_mins: u64,
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        SpeechConfig { model: PathBuf::new(), keywords: Vec::new(), cut_secs: 60, safe_word: None, safe_pause_mins: 15 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VoiceConfig {
+    // the voiceprint `shhh enroll` saved
+    pub print: PathBuf,
+    // how unlike the enrolled voice a window can sound and still be theirs,
+    // in standard deviations of it
+    pub max_distance: f32,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        VoiceConfig { print: PathBuf::new(), max_distance: 1.5 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClipConfig {
+    // where the WAV files go; unset means a `clips` directory next to the state
+    pub dir: Option<PathBuf>,
+    // audio kept from before the cut, and recorded after it
+    pub before_secs: u64,
+    pub after_secs: u64,
+    // the oldest clips are deleted beyond this many, or once older than
+    // `keep_days` (0: no age limit)
+    pub keep: usize,
+    pub keep_days: u64,
+}
+
+impl Default for ClipConfig {
+    fn default() -> Self {
+        ClipConfig { dir: None, before_secs: 10, after_secs: 5, keep: 50, keep_days: 30 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DuckConfig {
+    // the volume cap at a cut; in between, it goes down in a straight line
+    // from 100% at the soft threshold
+    pub min_volume_pct: u8,
+}
+
+impl Default for DuckConfig {
+    fn default() -> Self {
+        DuckConfig { min_volume_pct: 10 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DimConfig {
+    // the brightness cap just short of a cut, falling as for `[duck]`
+    pub min_brightness_pct: u8,
+    // turn the screen off altogether at a cut
+    pub blank: bool,
+}
+
+impl Default for DimConfig {
+    fn default() -> Self {
+        DimConfig { min_brightness_pct: 10, blank: true }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MuteConfig {
+    // program names (or parts of them), as for `[calls]`
+    pub apps: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObsConfig {
+    // obs-websocket address
+    pub url: String,
+    pub password: Option<String>,
+    // scene item to show while too loud, in `scene` (default: the current one)
+    pub source: Option<String>,
+    pub scene: Option<String>,
+    // scene to switch to while too loud; the previous one comes back after
+    pub cut_scene: Option<String>,
+}
+
+impl Default for ObsConfig {
+    fn default() -> Self {
+        ObsConfig { url: "ws://127.0.0.1:4455".into(), password: None, source: None, scene: None, cut_scene: None }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    pub server: String,
+    // unset means the usual one for `security`: 465, 587 or 25
+    pub port: Option<u16>,
+    pub security: Security,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    // templates; `{host}`, `{summary}` and `{events}` are filled in
+    pub subject: String,
+    pub body: String,
+    // the least time between two mails; what happens meanwhile is batched
+    pub batch_mins: u64,
+    // "HH:MM" local time for a summary of the day; unset means none
+    pub digest_at: Option<String>,
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        EmailConfig {
+            server: String::new(),
+            port: None,
+            security: Security::Starttls,
+            username: None,
+            password: None,
+            from: String::new(),
+            to: Vec::new(),
+            subject: "shhh on {host}: {summary}".into(),
+            body: "{events}\n".into(),
+            batch_mins: 15,
+            digest_at: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    // TLS from the start (port 465)
+    Tls,
+    // upgraded after connecting (port 587)
+    Starttls,
+    // nothing, for a relay on the local network
+    None,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfluxConfig {
+    // the write endpoint, e.g. http://host:8086/api/v2/write?org=home&bucket=shhh
+    // or http://host:8086/write?db=shhh
+    pub url: String,
+    // InfluxDB 2 API token
+    pub token: Option<String>,
+    pub measurement: String,
+    // added to every point, e.g. `room = "kids"`
+    pub tags: BTreeMap<String, String>,
+    pub batch_secs: u64,
+}
+
+impl Default for InfluxConfig {
+    fn default() -> Self {
+        let measurement = "shhh".into();
+        InfluxConfig { url: String::new(), token: None, measurement, tags: BTreeMap::new(), batch_secs: 10 }
+    }
+}
+
+// positive reinforcement for staying quiet
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RewardConfig {
+    // unbroken quiet time that earns a reward; 0 turns rewards off
+    pub every_mins: u64,
+    // desktop notification in the session shhh runs in
+    pub notify: bool,
+    // POSTed a JSON `{event, streak_mins}` for each reward
+    pub webhook: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CountdownConfig {
+    // how long it has to stay over the soft threshold before the cut lands;
+    // 0 cuts right away
+    pub secs: u64,
+    // desktop notifications as it counts down
+    pub notify: bool,
+}
+
+impl Default for CountdownConfig {
+    fn default() -> Self {
+        CountdownConfig { secs: 0, notify: true }
+    }
+}
+
+// a microphone that goes quiet in a suspicious way
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TamperConfig {
+    pub action: TamperAction,
+    // how long it has to look tampered with before we say so
+    pub after_secs: u64,
+}
+
+impl Default for TamperConfig {
+    fn default() -> Self {
+        TamperConfig { action: TamperAction::Warn, after_secs: 60 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TamperAction {
+    Off,
+    // report it only
+    Warn,
+    // report it and cut until the microphone is back
+    Cut,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerConfig {
+    pub action: PowerAction,
+    // this many cuts within `within_mins` is a strike-out
+    pub strikes: usize,
+    pub within_mins: u64,
+    // the countdown before it happens
+    pub warning_secs: u64,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        PowerConfig { action: PowerAction::Off, strikes: 3, within_mins: 60, warning_secs: 60 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerAction {
+    #[default]
+    Off,
+    Sleep,
+    Shutdown,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClippingConfig {
+    pub action: ClippingAction,
+    // share of a window's samples at full scale that counts as clipping
+    pub max_pct: f32,
+}
+
+impl Default for ClippingConfig {
+    fn default() -> Self {
+        ClippingConfig { action: ClippingAction::Warn, max_pct: 1.0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClippingAction {
+    Off,
+    // report it only
+    Warn,
+    // report it and don't act on clipped windows
+    Skip,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PercentileConfig {
+    // the level exceeded this share of the time: 10 is L10, 90 is L90
+    pub exceeded_pct: f32,
+    pub over_secs: u64,
+}
+
+impl Default for PercentileConfig {
+    fn default() -> Self {
+        PercentileConfig { exceeded_pct: 10.0, over_secs: 10 }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImpulseConfig {
+    pub action: ImpulseAction,
+    // more impulses than this within `within_secs` are noise that keeps up
+    pub max_ignored: usize,
+    pub within_secs: u64,
+}
+
+impl Default for ImpulseConfig {
+    fn default() -> Self {
+        ImpulseConfig { action: ImpulseAction::Cut, max_ignored: 2, within_secs: 30 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImpulseAction {
+    // judged like any other loud window
+    Cut,
+    // isolated ones don't cut
+    Ignore,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgcConfig {
+    pub action: AgcAction,
+    // how far the room's level can settle from the calibrated ambient before
+    // it's put down to the microphone's gain
+    pub max_drift_db: f32,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        AgcConfig { action: AgcAction::Warn, max_drift_db: 6.0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgcAction {
+    Off,
+    // report it only
+    Warn,
+    // report it and move the thresholds along with the drift
+    Compensate,
+}
+
+// anything left out comes from the top level (and the command line)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileConfig {
+    // "HH:MM-HH:MM" local time, wrapping past midnight if the end is earlier;
+    // a profile without hours is only used when picked by hand
+    pub hours: Vec<String>,
+    pub action: Option<LoudAction>,
+    pub soft_offset_db: Option<f32>,
+    pub cut_offset_db: Option<f32>,
+    pub dead_zone_db: Option<f32>,
+    pub penalty_secs: Option<u64>,
+    pub restore_quiet_secs: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    // take the network interfaces down
+    #[default]
+    Disconnect,
+    // blackhole `blocked_domains` through the hosts file
+    BlockDomains,
+    // leave the network alone (for `[obs]` on its own)
+    None,
+    // slow the interfaces down instead of cutting them, see `[degrade]`
+    Degrade,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoomPolicy {
+    // every room gets the default thresholds; the loudest one decides
+    #[default]
+    Any,
+    // each room uses its own `[rooms.<name>]` thresholds and action
+    PerRoom,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomConfig {
+    pub soft_offset_db: f32,
+    pub cut_offset_db: f32,
+    pub action: LoudAction,
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        RoomConfig { soft_offset_db: SOFT_OFFSET_DB, cut_offset_db: CUT_OFFSET_DB, action: LoudAction::Cut }
+    }
+}
+
+// what being too loud does, for a room or a profile
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoudAction {
+    // cut the network
+    Cut,
+    // only report it
+    Warn,
+}
+
+pub fn default_path() -> PathBuf {
+    #[cfg(windows)]
+    let dir = PathBuf::from(std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into())).join("shhh");
+    #[cfg(not(windows))]
+    let dir = PathBuf::from("/etc/shhh");
+    dir.join("shhh.toml")
+}
+
+// an explicit path has to exist; the default one is optional
+pub fn load(path: Option<&Path>) -> Result<Config, anyhow::Error> {
+    let default = default_path();
+    let path = match path {
+        Some(p) => p,
+        None if default.exists() => &default,
+        None => return Ok(Config::default()),
+    };
+    // the service runs it as SYSTEM, so a default config the monitored user
+    // could have written isn't the administrator's; without the service
+    // installed it's only what an elevated console reads
+    #[cfg(windows)]
+    if path == default && crate::service::installed() {
+        for p in [path, path.parent().unwrap_or(path)] {
+            crate::acl::check(p).map_err(|e| Error::Config(e.to_string()))?;
+        }
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("could not read config {}: {}", path.display(), e)))?;
+    let config: Config = toml::from_str(&text).map_err(|e| {
+        let problem = e.to_string();
+        Error::Config(format!("invalid config {}: {}{}", path.display(), problem.trim_end(), suggestion(e.message())))
+    })?;
+    if config.mode == Mode::BlockDomains && config.blocked_domains.is_empty() {
+        return Err(Error::Config("mode = \"block-domains\" needs a non-empty blocked_domains list".into()).into());
+    }
+    let dead_zone = config.dead_zone_db.unwrap_or(HYSTERESIS_DB);
+    match (config.soft_db, config.cut_db) {
+        (Some(soft), Some(cut)) => check_thresholds(soft, cut, dead_zone, true)?,
+        (None, None) => check_thresholds(
+            config.soft_offset_db.unwrap_or(SOFT_OFFSET_DB),
+            config.cut_offset_db.unwrap_or(CUT_OFFSET_DB),
+            dead_zone,
+            false,
+        )?,
+        _ => {
+            return Err(Error::Config(
+                "soft_db and cut_db have to be set together; add the missing one, or remove both to use offsets".into(),
+            )
+            .into())
+        }
+    }
+    match &config.obs {
+        Some(obs) if obs.source.is_none() && obs.cut_scene.is_none() => {
+            return Err(Error::Config("[obs] needs a `source` to show, a `cut_scene` to switch to, or both".into()).into());
+        }
+        _ => {}
+    }
+    if config.duck.as_ref().is_some_and(|duck| duck.min_volume_pct > 100) {
+        return Err(Error::Config("duck.min_volume_pct is a percentage of the full volume, from 0 to 100".into()).into());
+    }
+    if config.dim.as_ref().is_some_and(|dim| dim.min_brightness_pct > 100) {
+        return Err(Error::Config("dim.min_brightness_pct is a percentage of the full brightness, from 0 to 100".into()).into());
+    }
+    if config.mute.as_ref().is_some_and(|m| m.apps.is_empty() || m.apps.iter().any(|a| a.trim().is_empty())) {
+        return Err(Error::Config("[mute] needs `apps` to mute, without empty names".into()).into());
+    }
+    if let Some(email) = &config.email {
+        if email.server.is_empty() || email.from.is_empty() || email.to.is_empty() {
+            return Err(Error::Config("[email] needs a `server`, a `from` address and some `to` addresses".into()).into());
+        }
+        if email.digest_at.as_deref().is_some_and(|at| crate::profile::minute(at).is_none()) {
+            return Err(Error::Config("email.digest_at must be a local time like \"21:00\"".into()).into());
+        }
+        // AUTH PLAIN is the password in base64, readable by anyone on the way
+        if email.security == Security::None && email.username.is_some() {
+            return Err(Error::Config(
+                "[email] won't send a username and password without encryption; use security = \"starttls\" or \"tls\""
+                    .into(),
+            )
+            .into());
+        }
+    }
+    if let Some(influx) = &config.influx {
+        if !influx.url.starts_with("http://") && !influx.url.starts_with("https://") {
+            return Err(Error::Config(format!("influx.url must be an http(s) URL, not \"{}\"", influx.url)).into());
+        }
+        if influx.batch_secs == 0 || influx.measurement.is_empty() {
+            return Err(Error::Config("influx needs a `measurement` and batch_secs of at least 1".into()).into());
+        }
+    }
+    if let Some(clips) = &config.clips {
+        if clips.before_secs + clips.after_secs == 0 || clips.keep == 0 {
+            return Err(Error::Config("[clips] needs some audio (before_secs/after_secs) and keep of at least 1".into()).into());
+        }
+    }
+    if let Some(speech) = &config.speech {
+        if speech.model.as_os_str().is_empty() || (speech.keywords.is_empty() && speech.safe_word.is_none()) {
+            return Err(Error::Config("[speech] needs a `model` directory and some `keywords` or a `safe_word`".into()).into());
+        }
+        if speech.safe_word.is_some() && speech.safe_pause_mins == 0 {
+            return Err(Error::Config("speech.safe_pause_mins must be at least 1".into()).into());
+        }
+    }
+    if let Some(voice) = &config.voice {
+        if voice.print.as_os_str().is_empty() {
+            return Err(Error::Config("[voice] needs the `print` that `shhh enroll` saved".into()).into());
+        }
+        if voice.max_distance <= 0.0 {
+            return Err(Error::Config("voice.max_distance must be greater than 0".into()).into());
+        }
+    }
+    if config.script.as_ref().is_some_and(|s| s.path.as_os_str().is_empty()) {
+        return Err(Error::Config("[script] needs a `path`".into()).into());
+    }
+    if config.calls.as_ref().is_some_and(|c| c.apps.iter().any(|a| a.trim().is_empty())) {
+        return Err(Error::Config("calls.apps can't contain empty names".into()).into());
+    }
+    if let Some(url) = &config.reward.webhook {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(Error::Config(format!("reward.webhook must be an http(s) URL, not \"{}\"", url)).into());
+        }
+    }
+    if !(config.clipping.max_pct > 0.0 && config.clipping.max_pct <= 100.0) {
+        return Err(Error::Config(format!(
+            "clipping.max_pct is a percentage of a window's samples, above 0 and at most 100, not {}",
+            config.clipping.max_pct
+        ))
+        .into());
+    }
+    let window_ms = config.window_ms.unwrap_or(SAMPLE_WINDOW_MS);
+    if !(50..=5000).contains(&window_ms) {
+        return Err(Error::Config(format!(
+            "window_ms must be between 50 and 5000, not {}; a shorter window turns a single click into a cut",
+            window_ms
+        ))
+        .into());
+    }
+    if config.hop_ms.is_some_and(|hop| hop < 10 || hop > window_ms) {
+        return Err(Error::Config(format!(
+            "hop_ms must be between 10 and window_ms ({}); leave it out for windows one after the other",
+            window_ms
+        ))
+        .into());
+    }
+    if config.lowpass_hz.is_some_and(|hz| !(200.0..=20_000.0).contains(&hz)) {
+        return Err(Error::Config(format!(
+            "lowpass_hz must be between 200 and 20000, not {}; 2000 to 4000 keeps voices and cuts most clicks",
+            config.lowpass_hz.unwrap_or_default()
+        ))
+        .into());
+    }
+    if let Some(percentile) = &config.percentile {
+        if !(percentile.exceeded_pct > 0.0 && percentile.exceeded_pct < 100.0) || percentile.over_secs == 0 {
+            return Err(Error::Config(format!(
+                "percentile.exceeded_pct must be above 0 and below 100 (10 for L10), not {}, and over_secs at least 1",
+                percentile.exceeded_pct
+            ))
+            .into());
+        }
+    }
+    if config.impulses.within_secs == 0 {
+        return Err(Error::Config("impulses.within_secs must be at least 1".into()).into());
+    }
+    if config.agc.max_drift_db < 1.0 {
+        return Err(Error::Config(format!(
+            "agc.max_drift_db must be at least 1, not {}; a room's level wanders by a few dB on its own",
+            config.agc.max_drift_db
+        ))
+        .into());
+    }
+    if config.power.action != PowerAction::Off && (config.power.strikes == 0 || config.power.within_mins == 0) {
+        return Err(Error::Config("power.strikes and power.within_mins must be at least 1".into()).into());
+    }
+    if config.tamper.after_secs == 0 {
+        return Err(Error::Config("tamper.after_secs must be at least 1".into()).into());
+    }
+    for (name, room) in &config.rooms {
+        if room.cut_offset_db <= room.soft_offset_db {
+            return Err(Error::Config(format!(
+                "rooms.{}: cut_offset_db ({}) must be greater than soft_offset_db ({}); raise cut_offset_db or lower \
+                 soft_offset_db",
+                name, room.cut_offset_db, room.soft_offset_db
+            ))
+            .into());
+        }
+    }
+    Ok(config)
+}
+
+// the restore level (cut minus dead zone) has to stay above the soft one, or
+// a cut would never lift before the level is back to "quiet"
+// (as offsets over ambient or as absolute levels, the rule is the same; the
+// message names whichever settings were used)
+pub fn check_thresholds(soft_db: f32, cut_db: f32, dead_zone_db: f32, absolute: bool) -> Result<(), Error> {
+    let (soft, cut) = match absolute {
+        true => ("soft_db/--soft-db", "cut_db/--cut-db"),
+        false => ("soft_offset_db/--soft-offset", "cut_offset_db/--cut-offset"),
+    };
+    if cut_db <= soft_db {
+        return Err(Error::Config(format!(
+            "{} ({} dB) must be greater than {} ({} dB); raise the first or lower the second",
+            cut, cut_db, soft, soft_db
+        )));
+    }
+    if !(0.0..cut_db - soft_db).contains(&dead_zone_db) {
+        return Err(Error::Config(format!(
+            "dead_zone_db/--dead-zone is {} dB but must be at least 0 and less than the {} dB between {} and {}; \
+             lower it or move the thresholds apart",
+            dead_zone_db,
+            cut_db - soft_db,
+            soft,
+            cut
+        )));
+    }
+    Ok(())
+}
+
+// "did you mean `soft_offset_db`?" (on a line of its own) for a misspelt key
+// or value, going by serde's "unknown field `x`, expected one of `a`, `b`"
+fn suggestion(message: &str) -> String {
+    let Some(rest) = message.strip_prefix("unknown field ").or_else(|| message.strip_prefix("unknown variant ")) else {
+        return String::new();
+    };
+    let mut quoted = rest.split('`').skip(1).step_by(2);
+    let Some(unknown) = quoted.next() else {
+        return String::new();
+    };
+    closest(unknown, quoted).map(|c| format!("\ndid you mean `{}`?", c)).unwrap_or_default()
+}
+
+// the candidate a typo most likely meant, if any is close enough
+pub fn closest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let word = word.to_lowercase();
+    let max = (word.chars().count() / 3).max(2);
+    candidates
+        .into_iter()
+        .map(|c| (edit_distance(&word, &c.to_lowercase()), c))
+        .filter(|&(d, _)| d <= max)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, c)| c)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let next = (diagonal + (ca != cb) as usize).min(row[j] + 1).min(row[j + 1] + 1);
+            diagonal = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mistakes_come_with_a_fix() {
+        let e = toml::from_str::<Config>("soft_ofset_db = 12.0").unwrap_err();
+        assert_eq!(suggestion(e.message()), "\ndid you mean `soft_offset_db`?");
+        let e = toml::from_str::<Config>("mode = \"disconect\"").unwrap_err();
+        assert_eq!(suggestion(e.message()), "\ndid you mean `disconnect`?");
+        assert_eq!(suggestion(toml::from_str::<Config>("xyz = 1").unwrap_err().message()), "");
+        assert_eq!(closest("WiFi", ["Ethernet", "Wi-Fi"]), Some("Wi-Fi"));
+
+        let e = check_thresholds(20.0, 10.0, 3.0, false).unwrap_err().to_string();
+        assert!(e.starts_with("cut_offset_db/--cut-offset (10 dB) must be greater than soft_offset_db"), "{}", e);
+        assert!(check_thresholds(-40.0, -30.0, 12.0, true).unwrap_err().to_string().contains("soft_db/--soft-db"));
+    }
+}
@@ -0,0 +1,103 @@
+// Linux background operation: classic double-fork daemonizing, sd_notify
+// readiness for `Type=notify` units, and a generated systemd unit.
+use std::fs;
+use std::os::fd::AsRawFd;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::process::Command;
+
+const UNIT_PATH: &str = "/etc/systemd/system/shhh.service";
+
+pub fn daemonize() -> Result<(), anyhow::Error> {
+    // first fork lets the parent return to the shell, setsid drops the
+    // controlling terminal, second fork makes sure we can never reacquire one
+    unsafe {
+        match libc::fork() {
+            -1 => return Err(std::io::Error::last_os_error().into()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+        if libc::setsid() == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        match libc::fork() {
+            -1 => return Err(std::io::Error::last_os_error().into()),
+            0 => {}
+            _ => libc::_exit(0),
+        }
+    }
+    std::env::set_current_dir("/")?;
+
+    let null = fs::OpenOptions::new().read(true).write(true).open("/dev/null")?;
+    for fd in 0..3 {
+        if unsafe { libc::dup2(null.as_raw_fd(), fd) } == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+// best-effort sd_notify(3); a no-op when not started by systemd
+pub fn notify(state: &str) {
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else { return };
+    let path = path.to_string_lossy();
+    let addr = match path.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        None => SocketAddr::from_pathname(path.as_ref()),
+    };
+    if let (Ok(addr), Ok(sock)) = (addr, UnixDatagram::unbound()) {
+        let _ = sock.send_to_addr(state.as_bytes(), &addr);
+    }
+}
+
+fn unit_file(run_args: &[String]) -> Result<String, anyhow::Error> {
+    let exe = std::env::current_exe()?;
+    let mut exec = format!("{} run", exe.display());
+    for arg in run_args {
+        exec.push(' ');
+        exec.push_str(arg);
+    }
+    Ok(format!(
+        "[Unit]
+Description=shhh noise monitor
+After=network.target sound.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={}
+{}# SIGTERM makes shhh bring the interface back up before exiting
+KillSignal=SIGTERM
+Restart=on-failure
+RestartSec=5
+
+[Install]
+WantedBy=multi-user.target
+",
+        exec,
+        // a protected monitor ignores SIGTERM, so ask it properly
+        if run_args.iter().any(|a| a == "--protect") {
+            format!("ExecStop={} ctl stop\n", exe.display())
+        } else {
+            String::new()
+        }
+    ))
+}
+
+pub fn install_service(print: bool, run_args: &[String]) -> Result<(), anyhow::Error> {
+    let unit = unit_file(run_args)?;
+    if print {
+        print!("{}", unit);
+        return Ok(());
+    }
+    fs::write(UNIT_PATH, unit)?;
+    println!("Wrote {}", UNIT_PATH);
+    for args in [&["daemon-reload"][..], &["enable", "--now", "shhh.service"]] {
+        let status = Command::new("systemctl").args(args).status()?;
+        if !status.success() {
+            anyhow::bail!("systemctl {} failed ({})", args.join(" "), status);
+        }
+    }
+    println!("Enabled and started shhh.service.");
+    Ok(())
+}
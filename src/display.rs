@@ -0,0 +1,198 @@
+// The screen's brightness, for `[dim]`, and blanking it at a cut. On Linux a
+// laptop panel's backlight through sysfs, or else an external monitor over
+// DDC/CI through `ddcutil`; both need root (or a udev rule, or the i2c
+// group). On Windows every monitor over DDC/CI, and blanking through the
+// monitor power broadcast, which only reaches the desktop shhh runs on.
+pub use platform::{blank, get, set};
+
+// "VCP 10 C 50 100" -> (50, 100), from `ddcutil getvcp 10 --brief`
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_ddcutil(output: &str) -> Option<(u32, u32)> {
+    let fields: Vec<&str> = output.split_whitespace().collect();
+    match fields[..] {
+        ["VCP", _, "C", current, max, ..] => Some((current.parse().ok()?, max.parse().ok()?)),
+        _ => None,
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    // DDC/CI feature codes: brightness, and the monitor's power mode (1 on,
+    // 4 off)
+    const BRIGHTNESS: &str = "10";
+    const POWER_MODE: &str = "D6";
+
+    fn backlight() -> Option<PathBuf> {
+        std::fs::read_dir("/sys/class/backlight").ok()?.flatten().map(|entry| entry.path()).next()
+    }
+
+    fn read(path: PathBuf) -> Result<u32, anyhow::Error> {
+        let text = std::fs::read_to_string(&path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        Ok(text.trim().parse()?)
+    }
+
+    fn write(path: PathBuf, value: u32) -> Result<(), anyhow::Error> {
+        std::fs::write(&path, value.to_string()).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    fn ddcutil(args: &[&str]) -> Result<String, anyhow::Error> {
+        let output = Command::new("ddcutil")
+            .args(args)
+            .output()
+            .map_err(|e| anyhow::anyhow!("no backlight, and could not run ddcutil for DDC/CI: {}", e))?;
+        if !output.status.success() {
+            anyhow::bail!("ddcutil {}: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn ddc_brightness() -> Result<(u32, u32), anyhow::Error> {
+        let output = ddcutil(&["getvcp", BRIGHTNESS, "--brief"])?;
+        super::parse_ddcutil(&output).ok_or_else(|| anyhow::anyhow!("unexpected ddcutil output: {}", output.trim()))
+    }
+
+    pub fn get() -> Result<u8, anyhow::Error> {
+        let (current, max) = match backlight() {
+            Some(dir) => (read(dir.join("brightness"))?, read(dir.join("max_brightness"))?),
+            None => ddc_brightness()?,
+        };
+        Ok((current * 100 / max.max(1)).min(100) as u8)
+    }
+
+    pub fn set(pct: u8) -> Result<(), anyhow::Error> {
+        let pct = pct.min(100) as u32;
+        match backlight() {
+            Some(dir) => write(dir.join("brightness"), read(dir.join("max_brightness"))? * pct / 100),
+            None => {
+                let (_, max) = ddc_brightness()?;
+                ddcutil(&["setvcp", BRIGHTNESS, &(max * pct / 100).to_string()]).map(|_| ())
+            }
+        }
+    }
+
+    pub fn blank(on: bool) -> Result<(), anyhow::Error> {
+        match backlight() {
+            // FB_BLANK_POWERDOWN / FB_BLANK_UNBLANK
+            Some(dir) => write(dir.join("bl_power"), if on { 4 } else { 0 }),
+            None => ddcutil(&["setvcp", POWER_MODE, if on { "4" } else { "1" }]).map(|_| ()),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ptr::{null, null_mut};
+
+    use windows_sys::core::BOOL;
+    use windows_sys::Win32::Devices::Display::{
+        DestroyPhysicalMonitors, GetMonitorBrightness, GetNumberOfPhysicalMonitorsFromHMONITOR,
+        GetPhysicalMonitorsFromHMONITOR, SetMonitorBrightness, PHYSICAL_MONITOR,
+    };
+    use windows_sys::Win32::Foundation::{LPARAM, RECT};
+    use windows_sys::Win32::Graphics::Gdi::{EnumDisplayMonitors, HDC, HMONITOR};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SendMessageTimeoutW, HWND_BROADCAST, SC_MONITORPOWER, SMTO_ABORTIFHUNG, WM_SYSCOMMAND,
+    };
+
+    // the physical monitors behind every display, released on drop
+    struct Monitors(Vec<PHYSICAL_MONITOR>);
+
+    impl Monitors {
+        fn all() -> Monitors {
+            unsafe extern "system" fn found(monitor: HMONITOR, _: HDC, _: *mut RECT, list: LPARAM) -> BOOL {
+                (*(list as *mut Vec<HMONITOR>)).push(monitor);
+                1
+            }
+            let mut displays: Vec<HMONITOR> = Vec::new();
+            let mut monitors = Vec::new();
+            unsafe {
+                EnumDisplayMonitors(null_mut(), null(), Some(found), &mut displays as *mut _ as LPARAM);
+                for display in displays {
+                    let mut count = 0;
+                    if GetNumberOfPhysicalMonitorsFromHMONITOR(display, &mut count) == 0 || count == 0 {
+                        continue;
+                    }
+                    let mut physical = vec![PHYSICAL_MONITOR::default(); count as usize];
+                    if GetPhysicalMonitorsFromHMONITOR(display, count, physical.as_mut_ptr()) != 0 {
+                        monitors.extend(physical);
+                    }
+                }
+            }
+            Monitors(monitors)
+        }
+
+        // (minimum, current, maximum) of each monitor that speaks DDC/CI
+        fn brightness(&self) -> Vec<(&PHYSICAL_MONITOR, u32, u32, u32)> {
+            self.0
+                .iter()
+                .filter_map(|monitor| {
+                    let (mut min, mut current, mut max) = (0, 0, 0);
+                    let ok = unsafe { GetMonitorBrightness(monitor.hPhysicalMonitor, &mut min, &mut current, &mut max) };
+                    (ok != 0 && max > min).then_some((monitor, min, current, max))
+                })
+                .collect()
+        }
+    }
+
+    impl Drop for Monitors {
+        fn drop(&mut self) {
+            unsafe { DestroyPhysicalMonitors(self.0.len() as u32, self.0.as_ptr()) };
+        }
+    }
+
+    // the first monitor's, taken to stand for all of them
+    pub fn get() -> Result<u8, anyhow::Error> {
+        let monitors = Monitors::all();
+        let Some(&(_, min, current, max)) = monitors.brightness().first() else {
+            anyhow::bail!("no monitor with a brightness setting over DDC/CI");
+        };
+        Ok(((current.saturating_sub(min)) * 100 / (max - min)).min(100) as u8)
+    }
+
+    pub fn set(pct: u8) -> Result<(), anyhow::Error> {
+        let monitors = Monitors::all();
+        let brightness = monitors.brightness();
+        if brightness.is_empty() {
+            anyhow::bail!("no monitor with a brightness setting over DDC/CI");
+        }
+        for (monitor, min, _, max) in brightness {
+            unsafe { SetMonitorBrightness(monitor.hPhysicalMonitor, min + (max - min) * pct.min(100) as u32 / 100) };
+        }
+        Ok(())
+    }
+
+    // 2 turns the monitors off, -1 back on
+    pub fn blank(on: bool) -> Result<(), anyhow::Error> {
+        let state: isize = if on { 2 } else { -1 };
+        let sent = unsafe {
+            SendMessageTimeoutW(
+                HWND_BROADCAST,
+                WM_SYSCOMMAND,
+                SC_MONITORPOWER as usize,
+                state,
+                SMTO_ABORTIFHUNG,
+                1000,
+                null_mut(),
+            )
+        };
+        if sent == 0 {
+            let what = if on { "off" } else { "on" };
+            anyhow::bail!("could not turn the monitors {}: {}", what, std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ddcutil_brightness_is_read() {
+        assert_eq!(parse_ddcutil("VCP 10 C 50 100\n"), Some((50, 100)));
+        assert_eq!(parse_ddcutil("VCP 10 ERR\n"), None);
+    }
+}
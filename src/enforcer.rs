@@ -0,0 +1,423 @@
+// Carries out the policy's decisions on a worker thread. Backend calls spawn
+// `ip`/`netsh`, rewrite the hosts file or wait on a driver, which can take
+// hundreds of milliseconds; doing that on the metering thread drops windows.
+// The run loop only queues "enforce"/"restore" and picks up the outcome on a
+// later window.
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tracing::{error, info, warn};
+
+use crate::action::{self, Action};
+use crate::bus::{Bus, Event};
+use crate::i18n::tr;
+use crate::policy::Decision;
+use crate::shaping;
+use crate::state::{self, State};
+
+// backend calls are process spawns or driver round-trips; flapping faster
+// than this only costs CPU and leaves adapters half-reset
+const MIN_ENFORCE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Apply(bool),
+    // a degrading action's level short of a cut
+    Shape(u8),
+}
+
+// what the worker did with one command
+struct Outcome {
+    command: Command,
+    result: Result<(), String>,
+}
+
+// The configured action and whether it is currently in force. The backend is
+// only called when `wanted` differs from what was last applied (or that call
+// failed), never while a call is still running, and never more than once per
+// MIN_ENFORCE_INTERVAL; a cut and restore within one interval cancel out
+// without touching the backend. An action that shapes traffic also follows
+// the level between cuts, in shaping::STEPS steps.
+pub struct Enforcer {
+    action: Arc<dyn Action>,
+    active: bool,
+    wanted: bool,
+    // shaping level last applied and wanted, for actions that shape
+    shaped: u8,
+    wanted_level: u8,
+    // the command the worker is busy with
+    pending: Option<Command>,
+    last_call: Option<Instant>,
+    // last enforcement failure, cleared on the next success
+    error: Option<String>,
+    commands: Option<mpsc::Sender<Command>>,
+    outcomes: mpsc::Receiver<Outcome>,
+    worker: Option<JoinHandle<()>>,
+    // where outcomes are reported
+    bus: Option<Bus>,
+}
+
+impl Enforcer {
+    pub fn new(action: Arc<dyn Action>) -> Enforcer {
+        let (commands, rx) = mpsc::channel();
+        let (tx, outcomes) = mpsc::channel();
+        let worker = {
+            let action = action.clone();
+            thread::Builder::new().name("enforcer".into()).spawn(move || work(action.as_ref(), rx, tx)).ok()
+        };
+        if worker.is_none() {
+            warn!("Could not start the enforcement thread; enforcing inline.");
+        }
+        Enforcer {
+            action,
+            active: false,
+            wanted: false,
+            shaped: 0,
+            wanted_level: 0,
+            pending: None,
+            last_call: None,
+            error: None,
+            commands: worker.as_ref().map(|_| commands),
+            outcomes,
+            worker,
+            bus: None,
+        }
+    }
+
+    pub fn with_bus(mut self, bus: Bus) -> Enforcer {
+        self.bus = Some(bus);
+        self
+    }
+
+    pub fn describe(&self) -> String {
+        self.action.describe()
+    }
+
+    // in force as far as the backend has confirmed
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.clone()
+    }
+
+    pub fn carry_out(&mut self, decisions: &[Decision]) {
+        for decision in decisions {
+            match decision {
+                Decision::Enforce => self.wanted = true,
+                Decision::Restore(_) => self.wanted = false,
+                _ => {}
+            }
+        }
+    }
+
+    // how far past the soft threshold the level is (see Policy::shaping)
+    pub fn shape(&mut self, level: u8) {
+        if self.action.shapes() {
+            self.wanted_level = shaping::quantize(level);
+        }
+    }
+
+    // Collect what the worker finished and queue `wanted` if it needs applying
+    // and the interval allows. Called every window, so a deferred or failed
+    // call goes out as soon as it can; never blocks on the backend.
+    pub fn flush(&mut self) {
+        loop {
+            match self.outcomes.try_recv() {
+                Ok(outcome) => self.record(outcome),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    if self.pending.take().is_some() || self.commands.take().is_some() {
+                        warn!("The enforcement thread stopped; enforcing inline.");
+                    }
+                    break;
+                }
+            }
+        }
+        if self.pending.is_some() {
+            return;
+        }
+        let command = if self.wanted != self.active || self.error.is_some() {
+            Command::Apply(self.wanted)
+        } else if !self.active && self.wanted_level != self.shaped {
+            Command::Shape(self.wanted_level)
+        } else {
+            return;
+        };
+        if self.last_call.is_some_and(|t| t.elapsed() < MIN_ENFORCE_INTERVAL) {
+            return;
+        }
+        self.last_call = Some(Instant::now());
+        if let Some(commands) = &self.commands {
+            if commands.send(command).is_ok() {
+                self.pending = Some(command);
+                return;
+            }
+            self.commands = None;
+        }
+        let result = apply(self.action.as_ref(), command);
+        self.record(Outcome { command, result });
+    }
+
+    // Failures are reported and kept for `status` rather than propagated, so a
+    // transient backend error doesn't take the whole monitor down; the state
+    // only flips once the backend actually succeeded.
+    fn record(&mut self, outcome: Outcome) {
+        self.pending = None;
+        let error = outcome.result.clone().err();
+        let enforce = match outcome.command {
+            Command::Apply(enforce) => enforce,
+            // a failed level isn't retried until the level changes
+            Command::Shape(level) => {
+                self.shaped = level;
+                if let Some(e) = &error {
+                    error!("Action error: {}", e);
+                }
+                if let Some(bus) = &self.bus {
+                    bus.publish(Event::Shaped { level, error });
+                }
+                return;
+            }
+        };
+        if let Some(bus) = &self.bus {
+            bus.publish(Event::Enforced { enforce, error });
+        }
+        match outcome.result {
+            Ok(()) => {
+                self.active = enforce;
+                self.shaped = if enforce { 100 } else { 0 };
+                self.error = None;
+            }
+            Err(e) => {
+                // a partly failed enforce (one adapter of several) may still
+                // have taken effect, so make sure it gets undone later
+                if enforce {
+                    self.active = true;
+                }
+                error!("Action error: {}", e);
+                self.error = Some(e);
+            }
+        }
+    }
+
+    // Wait for the worker to finish what it's doing and stop it, then lift
+    // the action if it's in force. For the stop path, where blocking is fine.
+    pub fn finish(&mut self) -> Result<(), anyhow::Error> {
+        self.commands = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        while let Ok(outcome) = self.outcomes.try_recv() {
+            self.record(outcome);
+        }
+        // a shaping level short of a cut is undone too (the volume, `degrade`)
+        if self.active || self.shaped > 0 {
+            info!("{}", tr!("stopping"));
+            action::restore(self.action.as_ref())?;
+            self.active = false;
+        }
+        Ok(())
+    }
+}
+
+// covers the paths that never reach the end of run_loop (an early `?`, a
+// panic unwinding through it)
+impl Drop for Enforcer {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Could not restore: {}", e);
+        }
+    }
+}
+
+fn work(action: &dyn Action, commands: mpsc::Receiver<Command>, outcomes: mpsc::Sender<Outcome>) {
+    for command in commands {
+        let result = apply(action, command);
+        if outcomes.send(Outcome { command, result }).is_err() {
+            break;
+        }
+    }
+}
+
+fn apply(action: &dyn Action, command: Command) -> Result<(), String> {
+    let result = match command {
+        Command::Apply(true) | Command::Shape(1..) => {
+            // written before enforcing, so a crash halfway through is still covered
+            let state = action.state();
+            if state != State::default() {
+                if let Err(e) = state::save(&state) {
+                    warn!("Could not save state: {}", e);
+                }
+            }
+            match command {
+                Command::Shape(level) => action.shape(level),
+                _ => action.apply(true),
+            }
+        }
+        Command::Apply(false) => action::restore(action),
+        Command::Shape(0) => action.shape(0).and_then(|()| action::forget(action)),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Disconnect;
+    use crate::iface::{Mock, NetController};
+    use crate::policy::{Policy, Rules};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    // a backend that takes a while and can be told to fail
+    #[derive(Default)]
+    struct Slow {
+        calls: AtomicUsize,
+        fail: AtomicBool,
+    }
+
+    impl Action for Slow {
+        fn describe(&self) -> String {
+            "slow".into()
+        }
+
+        fn apply(&self, _enforce: bool) -> Result<(), anyhow::Error> {
+            thread::sleep(Duration::from_millis(200));
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.fail.load(Ordering::SeqCst) {
+                anyhow::bail!("backend failed");
+            }
+            Ok(())
+        }
+
+        fn state(&self) -> State {
+            State::default()
+        }
+    }
+
+    fn settle(enforcer: &mut Enforcer) {
+        let t0 = Instant::now();
+        while enforcer.pending.is_some() && t0.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+            enforcer.flush();
+        }
+    }
+
+    #[test]
+    fn flush_does_not_wait_for_the_backend() {
+        let action = Arc::new(Slow::default());
+        let mut enforcer = Enforcer::new(action.clone());
+        enforcer.carry_out(&[Decision::Enforce]);
+        let t0 = Instant::now();
+        enforcer.flush();
+        assert!(t0.elapsed() < Duration::from_millis(100));
+        assert!(!enforcer.active());
+        settle(&mut enforcer);
+        assert!(enforcer.active());
+        assert_eq!(action.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn failures_are_reported_back() {
+        let action = Arc::new(Slow::default());
+        action.fail.store(true, Ordering::SeqCst);
+        let mut enforcer = Enforcer::new(action.clone());
+        enforcer.carry_out(&[Decision::Enforce]);
+        enforcer.flush();
+        settle(&mut enforcer);
+        assert_eq!(enforcer.error().as_deref(), Some("backend failed"));
+        // a failed enforce still counts as possibly in force
+        assert!(enforcer.active());
+    }
+
+    // records the levels it was shaped to
+    #[derive(Default)]
+    struct Shaper {
+        levels: Mutex<Vec<u8>>,
+    }
+
+    impl Action for Shaper {
+        fn describe(&self) -> String {
+            "shaper".into()
+        }
+
+        fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+            self.shape(if enforce { 100 } else { 0 })
+        }
+
+        fn shapes(&self) -> bool {
+            true
+        }
+
+        fn shape(&self, level: u8) -> Result<(), anyhow::Error> {
+            self.levels.lock().unwrap().push(level);
+            Ok(())
+        }
+
+        fn state(&self) -> State {
+            State::default()
+        }
+    }
+
+    #[test]
+    fn shaping_follows_the_level_in_steps() {
+        let action = Arc::new(Shaper::default());
+        let mut enforcer = Enforcer::new(action.clone());
+        enforcer.shape(60);
+        enforcer.flush();
+        settle(&mut enforcer);
+        // the same step again is left alone
+        enforcer.shape(70);
+        thread::sleep(MIN_ENFORCE_INTERVAL);
+        enforcer.flush();
+        assert!(enforcer.pending.is_none());
+        // a cut takes over, and the full level during it is nothing new
+        enforcer.carry_out(&[Decision::Enforce]);
+        enforcer.shape(100);
+        enforcer.flush();
+        settle(&mut enforcer);
+        assert!(enforcer.active());
+        assert_eq!(*action.levels.lock().unwrap(), [50, 100]);
+    }
+
+    #[test]
+    fn finish_restores_after_the_pending_call() {
+        let action = Arc::new(Slow::default());
+        let mut enforcer = Enforcer::new(action.clone());
+        enforcer.carry_out(&[Decision::Enforce]);
+        enforcer.flush();
+        enforcer.finish().unwrap();
+        assert!(!enforcer.active());
+        assert_eq!(action.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn a_loud_spell_takes_the_adapters_down_and_back_up() {
+        let _state = state::TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let net = Arc::new(Mock::default());
+        let action = Arc::new(Disconnect { ifaces: vec!["wlan0".into(), "eth0".into()], net: net.clone() });
+        let t0 = Instant::now();
+        let mut policy = Policy::new(-60.0, Rules::default(), t0);
+        let mut enforcer = Enforcer::new(action);
+        for (i, db) in [-60.0, -60.0, 0.0, 0.0].into_iter().enumerate() {
+            enforcer.carry_out(&policy.step(db, t0 + Duration::from_millis(500 * i as u64)));
+            enforcer.flush();
+        }
+        settle(&mut enforcer);
+        assert!(enforcer.active());
+        assert_eq!((net.status("wlan0").unwrap(), net.status("eth0").unwrap()), (false, false));
+        thread::sleep(MIN_ENFORCE_INTERVAL);
+        enforcer.carry_out(&policy.step(-60.0, t0 + Duration::from_secs(3)));
+        enforcer.flush();
+        settle(&mut enforcer);
+        assert!(!enforcer.active());
+        let calls = net.calls.lock().unwrap().clone();
+        let expected = [("wlan0", false), ("eth0", false), ("wlan0", true), ("eth0", true)];
+        assert_eq!(calls, expected.map(|(name, enabled)| (name.to_string(), enabled)));
+        // the state file written for the cut is gone again
+        assert!(!state::path().exists());
+    }
+}
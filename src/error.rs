@@ -0,0 +1,66 @@
+// Failures that deserve a specific message and exit code, so scripts (and the
+// person at the keyboard) can tell "no microphone" from "bad config" without
+// reading a backtrace. Anything else stays a plain anyhow error and exits 1.
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    NoInputDevice,
+    // the device exists but won't give us a usable stream
+    InputDevice(String),
+    Config(String),
+    // no interface given and none could be detected
+    NoInterface(String),
+    // the backend refused to turn an interface on or off
+    Interface(String),
+    // `shhh ctl`/`status` with no monitor to talk to
+    NotRunning(String),
+    // enforcing needs administrator/root and we don't have it
+    NotElevated,
+}
+
+impl Error {
+    // 2 is clap's usage error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Config(_) => 3,
+            Error::NoInputDevice | Error::InputDevice(_) => 4,
+            Error::NoInterface(_) | Error::Interface(_) => 5,
+            Error::NotRunning(_) => 6,
+            Error::NotElevated => 7,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoInputDevice => {
+                write!(f, "no input device found — is a microphone connected? Run `shhh devices` to list inputs")
+            }
+            Error::InputDevice(e) => {
+                write!(f, "the microphone can't be used ({}); check it in `shhh devices` or try another one", e)
+            }
+            #[cfg(windows)]
+            Error::NotElevated => write!(
+                f,
+                "shhh needs administrator rights to control network adapters; run it from an elevated prompt or \
+                 pass --elevate"
+            ),
+            #[cfg(not(windows))]
+            Error::NotElevated => {
+                write!(f, "shhh needs root (or CAP_NET_ADMIN) to control network interfaces; use sudo or pass --elevate")
+            }
+            Error::Config(e) | Error::NoInterface(e) | Error::Interface(e) => write!(f, "{}", e),
+            Error::NotRunning(e) => {
+                write!(f, "could not reach a running shhh instance ({}); is `shhh run` or the service running?", e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.downcast_ref::<Error>().map_or(1, Error::exit_code)
+}
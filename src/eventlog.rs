@@ -0,0 +1,144 @@
+// The Windows Event Log: cuts, lifts, failed enforcement, tampering and the
+// service starting and stopping go to the Application log under a `shhh`
+// source, where Event Viewer and log collectors (Windows Event Forwarding, a
+// SIEM agent) pick them up with everything else. The shhh.log file has the
+// full story; this is the part an administrator wants next to the system's.
+//
+// The source is registered with EventCreate.exe's message table, which has
+// every ID from 1 to 1000 as a plain "%1", so the entries read as written
+// without a message DLL of our own.
+use std::ptr::null;
+
+use tracing::debug;
+use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+use windows_sys::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+    EVENTLOG_WARNING_TYPE, REPORT_EVENT_TYPE,
+};
+use windows_sys::Win32::System::Registry::{
+    RegCloseKey, RegCreateKeyExW, RegDeleteKeyW, RegSetValueExW, HKEY, HKEY_LOCAL_MACHINE, KEY_WRITE, REG_DWORD,
+    REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE,
+};
+
+use crate::bus::Event;
+use crate::i18n::tr;
+use crate::policy::Decision;
+
+const SOURCE: &str = "shhh";
+const KEY: &str = "SYSTEM\\CurrentControlSet\\Services\\EventLog\\Application\\shhh";
+
+// event IDs, for filtering in Event Viewer
+pub const STARTED: u32 = 1;
+pub const STOPPED: u32 = 2;
+const CUT: u32 = 10;
+const LIFTED: u32 = 11;
+const ENFORCE_FAILED: u32 = 12;
+const TAMPERING: u32 = 20;
+const TAMPERING_CLEARED: u32 = 21;
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Info,
+    Warning,
+    Error,
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+// Register the source (needs administrator rights, which the monitor has
+// anyway). Idempotent; without it entries still arrive, just with Event
+// Viewer complaining that it can't find their description.
+pub fn register() -> Result<(), anyhow::Error> {
+    let mut key: HKEY = std::ptr::null_mut();
+    let err = unsafe {
+        RegCreateKeyExW(
+            HKEY_LOCAL_MACHINE,
+            wide(KEY).as_ptr(),
+            0,
+            null(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_WRITE,
+            null(),
+            &mut key,
+            std::ptr::null_mut(),
+        )
+    };
+    if err != ERROR_SUCCESS {
+        anyhow::bail!("could not register the event source: {}", std::io::Error::from_raw_os_error(err as i32));
+    }
+    let file: Vec<u8> = wide("%SystemRoot%\\System32\\EventCreate.exe").iter().flat_map(|c| c.to_le_bytes()).collect();
+    let types = 7u32.to_le_bytes();
+    let errs = unsafe {
+        [
+            RegSetValueExW(key, wide("EventMessageFile").as_ptr(), 0, REG_EXPAND_SZ, file.as_ptr(), file.len() as u32),
+            RegSetValueExW(key, wide("TypesSupported").as_ptr(), 0, REG_DWORD, types.as_ptr(), types.len() as u32),
+        ]
+    };
+    unsafe { RegCloseKey(key) };
+    if let Some(&err) = errs.iter().find(|&&err| err != ERROR_SUCCESS) {
+        anyhow::bail!("could not register the event source: {}", std::io::Error::from_raw_os_error(err as i32));
+    }
+    Ok(())
+}
+
+pub fn deregister() {
+    unsafe { RegDeleteKeyW(HKEY_LOCAL_MACHINE, wide(KEY).as_ptr()) };
+}
+
+// one entry; failing to write it isn't worth more than a debug line
+pub fn report(kind: Kind, id: u32, message: &str) {
+    let kind: REPORT_EVENT_TYPE = match kind {
+        Kind::Info => EVENTLOG_INFORMATION_TYPE,
+        Kind::Warning => EVENTLOG_WARNING_TYPE,
+        Kind::Error => EVENTLOG_ERROR_TYPE,
+    };
+    let message = wide(message);
+    let strings = [message.as_ptr()];
+    unsafe {
+        let source = RegisterEventSourceW(null(), wide(SOURCE).as_ptr());
+        if source.is_null() {
+            debug!("Could not open the event log: {}", std::io::Error::last_os_error());
+            return;
+        }
+        if ReportEventW(source, kind, 0, id, std::ptr::null_mut(), 1, 0, strings.as_ptr(), null()) == 0 {
+            debug!("Could not write to the event log: {}", std::io::Error::last_os_error());
+        }
+        DeregisterEventSource(source);
+    }
+}
+
+// the monitor's events that make an entry, with the level and thresholds of
+// the window that cut
+pub fn consumer() -> impl FnMut(Event) + Send {
+    if let Err(e) = register() {
+        debug!("{}", e);
+    }
+    let mut last = None;
+    move |event| match event {
+        Event::Window(entry) => last = Some((entry.db, entry.max_db)),
+        Event::Enforced { enforce: true, error: None } => {
+            let level = last.map(|(db, max)| tr!("eventlog-level", db = format!("{:.1}", db), max = format!("{:.1}", max)));
+            let message = [tr!("cut")].into_iter().chain(level).collect::<Vec<_>>().join(" ");
+            report(Kind::Warning, CUT, &message)
+        }
+        Event::Enforced { enforce: false, error: None } => report(Kind::Info, LIFTED, &tr!("lifted")),
+        Event::Enforced { enforce, error: Some(e) } => {
+            let message = format!("Could not {}: {}", if enforce { "cut" } else { "restore" }, e);
+            report(Kind::Error, ENFORCE_FAILED, &message)
+        }
+        Event::Decisions(decisions) => {
+            for decision in decisions {
+                match decision {
+                    Decision::Tamper(tamper) => {
+                        report(Kind::Warning, TAMPERING, &tr!("tampering", what = tamper.to_string()))
+                    }
+                    Decision::TamperCleared => report(Kind::Info, TAMPERING_CLEARED, &tr!("tampering-cleared")),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
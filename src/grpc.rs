@@ -0,0 +1,222 @@
+// Optional gRPC API (the `grpc` feature, `run --grpc ADDR`) for controllers
+// that would rather have a generated client than the HTTP API: the status
+// snapshot, the `shhh ctl` requests and a stream of every metered window.
+// proto/shhh.proto is the contract; build.rs compiles it.
+//
+// Like the HTTP API it needs the token, here as `authorization: Bearer
+// <token>` metadata. tonic wants tokio, which nothing else in shhh does, so
+// the server gets a small runtime on a thread of its own, and each level
+// stream a thread that carries bus events over to it.
+use std::net::{SocketAddr, TcpListener};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::api::constant_time_eq;
+use crate::bus::Event;
+use crate::history::Entry;
+use crate::ipc;
+use crate::monitor::Handle;
+
+mod proto {
+    tonic::include_proto!("shhh.v1");
+}
+
+use proto::monitor_server::{Monitor, MonitorServer};
+use proto::{
+    ControlReply, ControlRequest, CutRequest, GetStatusRequest, Level, SetProfileRequest, StatusReply,
+    StreamLevelsRequest,
+};
+
+pub fn serve(addr: SocketAddr, token: String, handle: Handle) -> Result<(), anyhow::Error> {
+    let listener = TcpListener::bind(addr).map_err(|e| anyhow::anyhow!("could not bind {}: {}", addr, e))?;
+    spawn(listener, token, handle)?;
+    info!("gRPC API listening on {}", addr);
+    Ok(())
+}
+
+fn spawn(listener: TcpListener, token: String, handle: Handle) -> Result<(), anyhow::Error> {
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).thread_name("grpc").enable_all().build()?;
+    thread::Builder::new().name("grpc".into()).spawn(move || {
+        runtime.block_on(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => return warn!("gRPC API unavailable: {}", e),
+            };
+            let authorize = move |request: Request<()>| authorize(request, &token);
+            let server = tonic::transport::Server::builder()
+                .add_service(MonitorServer::with_interceptor(Service { handle }, authorize))
+                .serve_with_incoming(TcpIncoming::from(listener));
+            if let Err(e) = server.await {
+                warn!("gRPC API stopped: {}", e);
+            }
+        })
+    })?;
+    Ok(())
+}
+
+fn authorize(request: Request<()>, token: &str) -> Result<Request<()>, Status> {
+    let given = request.metadata().get("authorization").and_then(|v| v.to_str().ok()?.strip_prefix("Bearer "));
+    if !given.is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes())) {
+        return Err(Status::unauthenticated("missing or invalid token"));
+    }
+    Ok(request)
+}
+
+struct Service {
+    handle: Handle,
+}
+
+impl Service {
+    fn control(&self, request: ipc::Request) -> Result<Response<ControlReply>, Status> {
+        self.handle.control.send(request).map_err(|_| Status::unavailable("monitor is shutting down"))?;
+        Ok(Response::new(ControlReply {}))
+    }
+}
+
+#[tonic::async_trait]
+impl Monitor for Service {
+    async fn get_status(&self, _: Request<GetStatusRequest>) -> Result<Response<StatusReply>, Status> {
+        let status = self.handle.status.lock().map_err(|_| Status::internal("status unavailable"))?.clone();
+        Ok(Response::new(status.into()))
+    }
+
+    type StreamLevelsStream = ReceiverStream<Result<Level, Status>>;
+
+    async fn stream_levels(&self, _: Request<StreamLevelsRequest>) -> Result<Response<Self::StreamLevelsStream>, Status> {
+        let events = self.handle.bus.subscribe();
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        thread::Builder::new()
+            .name("grpc-levels".into())
+            .spawn(move || forward(events, tx))
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn pause(&self, _: Request<ControlRequest>) -> Result<Response<ControlReply>, Status> {
+        self.control(ipc::Request::Pause)
+    }
+
+    async fn resume(&self, _: Request<ControlRequest>) -> Result<Response<ControlReply>, Status> {
+        self.control(ipc::Request::Resume)
+    }
+
+    async fn restore(&self, _: Request<ControlRequest>) -> Result<Response<ControlReply>, Status> {
+        self.control(ipc::Request::Restore)
+    }
+
+    async fn cut(&self, request: Request<CutRequest>) -> Result<Response<ControlReply>, Status> {
+        self.control(ipc::Request::Cut { secs: request.into_inner().secs })
+    }
+
+    async fn recalibrate(&self, _: Request<ControlRequest>) -> Result<Response<ControlReply>, Status> {
+        self.control(ipc::Request::Recalibrate)
+    }
+
+    async fn set_profile(&self, request: Request<SetProfileRequest>) -> Result<Response<ControlReply>, Status> {
+        let name = request.into_inner().name;
+        if !ipc::known_profile(&self.handle.status, &name) {
+            return Err(Status::not_found(format!("no profile named '{}'", name)));
+        }
+        self.control(ipc::Request::Profile { name })
+    }
+
+    async fn stop(&self, _: Request<ControlRequest>) -> Result<Response<ControlReply>, Status> {
+        self.control(ipc::Request::Stop)
+    }
+}
+
+// windows from the bus to one client, until it hangs up or the bus closes
+fn forward(events: mpsc::Receiver<Event>, levels: tokio::sync::mpsc::Sender<Result<Level, Status>>) {
+    loop {
+        match events.recv_timeout(Duration::from_secs(10)) {
+            Ok(Event::Window(entry)) => {
+                if levels.blocking_send(Ok(entry.into())).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            // nothing metered for a while (calibrating, no audio): still there?
+            Err(mpsc::RecvTimeoutError::Timeout) if !levels.is_closed() => {}
+            Err(_) => break,
+        }
+    }
+}
+
+impl From<ipc::Status> for StatusReply {
+    fn from(s: ipc::Status) -> StatusReply {
+        StatusReply {
+            state: s.state,
+            db: s.db,
+            ambient_db: s.ambient_db,
+            min_db: s.min_db,
+            max_db: s.max_db,
+            paused: s.paused,
+            profile: s.profile,
+            profiles: s.profiles,
+            quiet_secs: s.quiet_secs,
+            uptime_secs: s.uptime_secs,
+            penalty_left_secs: s.penalty_left_secs,
+            cut_in_secs: s.cut_in_secs,
+            restore_in_secs: s.restore_in_secs,
+            tamper: s.tamper,
+            action: s.action,
+            enforcing: s.enforcing,
+            action_error: s.action_error,
+        }
+    }
+}
+
+impl From<Entry> for Level {
+    fn from(e: Entry) -> Level {
+        Level { time_ms: e.time_ms, db: e.db, pct: e.pct, state: e.state, min_db: e.min_db, max_db: e.max_db }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::monitor_client::MonitorClient;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn a_client_gets_status_levels_and_control() {
+        let (control, requests) = mpsc::channel();
+        let status = ipc::Status { state: "OK 100%".into(), ..ipc::Status::default() };
+        let handle =
+            Handle { control, status: Arc::new(Mutex::new(status)), history: Default::default(), bus: Default::default() };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        spawn(listener, "secret".into(), handle.clone()).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let channel = tonic::transport::Endpoint::from_shared(url).unwrap().connect().await.unwrap();
+            let refused = MonitorClient::new(channel.clone()).get_status(GetStatusRequest {}).await.unwrap_err();
+            assert_eq!(refused.code(), tonic::Code::Unauthenticated);
+
+            let mut client = MonitorClient::with_interceptor(channel, |mut request: Request<()>| {
+                request.metadata_mut().insert("authorization", "Bearer secret".parse().unwrap());
+                Ok(request)
+            });
+            let reply = client.get_status(GetStatusRequest {}).await.unwrap().into_inner();
+            assert_eq!(reply.state, "OK 100%");
+
+            let mut levels = client.stream_levels(StreamLevelsRequest {}).await.unwrap().into_inner();
+            let entry = Entry { time_ms: 1, db: -30.0, pct: 80, state: "OK 80%".into(), min_db: -45.0, max_db: -15.0 };
+            handle.bus.publish(Event::Window(entry));
+            assert_eq!(levels.message().await.unwrap().unwrap().pct, 80);
+
+            client.cut(CutRequest { secs: 60 }).await.unwrap();
+            let unknown = client.set_profile(SetProfileRequest { name: "nope".into() }).await.unwrap_err();
+            assert_eq!(unknown.code(), tonic::Code::NotFound);
+        });
+        assert_eq!(requests.try_iter().collect::<Vec<_>>(), [ipc::Request::Cut { secs: 60 }]);
+    }
+}
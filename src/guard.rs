@@ -0,0 +1,82 @@
+// Restoring the network however a console run ends. Returning from the run
+// loop, with or without an error, and a panic unwinding through it restore
+// through the Enforcer's Drop. What never gets back there is covered here:
+//
+// - Ctrl+C, SIGTERM and SIGHUP (ctrlc's `termination` feature), which exit
+//   right after restoring
+// - a panic on any thread, restored from the panic hook before it unwinds
+//   (a panicking consumer lifts a cut rather than leave it up for good)
+// - on Windows, closing the console window, logging off and shutting down,
+//   which only give the process until its handler returns
+//
+// `taskkill /f` and SIGKILL can't be caught; that's the watchdog's job.
+use std::sync::{Arc, Mutex};
+
+use tracing::{error, info, warn};
+
+use crate::action::{self, Action};
+use crate::i18n::tr;
+
+// what to restore, and whether a stop signal is ignored (--protect)
+static GUARDED: Mutex<Option<(Arc<dyn Action>, bool)>> = Mutex::new(None);
+
+pub fn install(action: Arc<dyn Action>, protect: bool) {
+    if let Ok(mut guarded) = GUARDED.lock() {
+        *guarded = Some((action, protect));
+    }
+    ctrlc::set_handler(|| {
+        if stop("exiting") {
+            std::process::exit(0);
+        }
+    })
+    .ok();
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default(info);
+        restore("panicked");
+    }));
+    #[cfg(windows)]
+    console_events();
+}
+
+// a stop signal: restore, unless protected; whether to go ahead and exit
+fn stop(message: &str) -> bool {
+    if GUARDED.lock().ok().and_then(|g| g.as_ref().map(|(_, protect)| *protect)).unwrap_or(false) {
+        warn!("{}", tr!("protected"));
+        return false;
+    }
+    restore(message);
+    true
+}
+
+fn restore(message: &str) {
+    // a panic while it was held leaves it poisoned, but the action is fine
+    let guarded = GUARDED.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let Some((action, _)) = guarded else { return };
+    info!("{}", tr!(message));
+    if let Err(e) = action::restore(action.as_ref()) {
+        error!("Could not restore: {}", e);
+    }
+}
+
+// ctrlc handles these too, but on a thread of its own, and Windows ends the
+// process as soon as the console handler returns; this one restores before
+// it does. Registered after ctrlc's, so it's asked first and passes Ctrl+C
+// and Ctrl+Break on.
+#[cfg(windows)]
+fn console_events() {
+    use windows_sys::core::BOOL;
+    use windows_sys::Win32::System::Console::{
+        SetConsoleCtrlHandler, CTRL_CLOSE_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+    };
+
+    unsafe extern "system" fn handler(event: u32) -> BOOL {
+        match event {
+            CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT => stop("exiting") as BOOL,
+            _ => 0,
+        }
+    }
+    if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+        warn!("Could not watch for the console closing: {}", std::io::Error::last_os_error());
+    }
+}
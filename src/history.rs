@@ -0,0 +1,60 @@
+// Rolling in-memory record of recent windows, served by `GET /history` and
+// the `history` control request (which `shhh chart` draws) and kept by a
+// consumer of the monitor's event bus. WebSocket clients get the same entries
+// live from the bus.
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+// by age, since overlapping windows come more often than one per 500ms
+const KEEP_MS: u64 = 24 * 60 * 60 * 1000;
+const MAX_VIOLATIONS: usize = 1000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub time_ms: u64,
+    pub db: f32,
+    pub pct: i32,
+    pub state: String,
+    // the thresholds in force, which profiles and recalibration move
+    pub min_db: f32,
+    pub max_db: f32,
+}
+
+#[derive(Default)]
+pub struct History {
+    entries: VecDeque<Entry>,
+    // the first window of every cut, kept longer than the raw windows
+    violations: VecDeque<Entry>,
+}
+
+impl History {
+    pub fn push(&mut self, entry: Entry) {
+        let was_cut = self.entries.back().is_some_and(|e| e.state == "CUT");
+        if entry.state == "CUT" && !was_cut {
+            if self.violations.len() == MAX_VIOLATIONS {
+                self.violations.pop_front();
+            }
+            self.violations.push_back(entry.clone());
+        }
+        while self.entries.front().is_some_and(|e| e.time_ms + KEEP_MS < entry.time_ms) {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    // entries newer than `since_ms`, capped to the most recent `limit`
+    pub fn since(&self, since_ms: u64, limit: usize) -> Vec<Entry> {
+        let newer: Vec<&Entry> = self.entries.iter().filter(|e| e.time_ms > since_ms).collect();
+        newer[newer.len().saturating_sub(limit)..].iter().map(|&e| e.clone()).collect()
+    }
+
+    pub fn violations_since(&self, since_ms: u64) -> Vec<Entry> {
+        self.violations.iter().filter(|e| e.time_ms > since_ms).cloned().collect()
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
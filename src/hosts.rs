@@ -0,0 +1,69 @@
+// Hosts-file blocklist backend. shhh owns a marked block at the end of the
+// hosts file and never touches anything outside it.
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::process::Command;
+
+const BEGIN: &str = "# BEGIN shhh blocklist (managed by shhh, do not edit)";
+const END: &str = "# END shhh blocklist";
+
+pub fn hosts_path() -> PathBuf {
+    #[cfg(windows)]
+    {
+        let root = std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into());
+        PathBuf::from(root).join("System32\\drivers\\etc\\hosts")
+    }
+    #[cfg(not(windows))]
+    PathBuf::from("/etc/hosts")
+}
+
+// Replace shhh's block with one for `domains` (empty removes it). The file is
+// only written when something changes, so this is cheap to call every window.
+pub fn set_blocked(domains: &[String]) -> Result<(), anyhow::Error> {
+    let path = hosts_path();
+    let current =
+        std::fs::read_to_string(&path).map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+    let updated = with_block(&current, domains);
+    if updated == current {
+        return Ok(());
+    }
+    std::fs::write(&path, updated).map_err(|e| anyhow::anyhow!("could not write {}: {}", path.display(), e))?;
+    // the DNS client service caches hosts lookups
+    #[cfg(windows)]
+    let _ = Command::new("ipconfig").arg("/flushdns").output();
+    Ok(())
+}
+
+fn with_block(hosts: &str, domains: &[String]) -> String {
+    let mut out = String::new();
+    let mut inside = false;
+    for line in hosts.lines() {
+        match line.trim() {
+            BEGIN => inside = true,
+            END => inside = false,
+            _ if !inside => {
+                out.push_str(line);
+                out.push('\n');
+            }
+            _ => {}
+        }
+    }
+    if domains.is_empty() {
+        return out;
+    }
+    out.push_str(BEGIN);
+    out.push('\n');
+    for domain in domains {
+        // hosts entries don't match subdomains, so cover the usual www. alias
+        let mut names = vec![domain.clone()];
+        if !domain.starts_with("www.") {
+            names.push(format!("www.{}", domain));
+        }
+        for name in names {
+            out.push_str(&format!("0.0.0.0 {}\n:: {}\n", name, name));
+        }
+    }
+    out.push_str(END);
+    out.push('\n');
+    out
+}
@@ -0,0 +1,183 @@
+// Translations of what the household reads: the monitor's log of cuts,
+// warnings and rewards, reward notifications and `shhh status`. Messages are
+// Fluent (https://projectfluent.org) files in `locales/`; English and German
+// are built in, and a `<language>.ftl` in the `locales` directory next to the
+// config adds a language or overrides built-in messages one by one, so a
+// translation can be written and tried out without a rebuild. Whatever a
+// translation lacks comes out in English. Diagnostics and errors stay in
+// English, where they can be searched for.
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+use crate::config;
+
+const BUILT_IN: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+];
+
+static TRANSLATIONS: OnceLock<Translations> = OnceLock::new();
+
+// a message in the chosen language: `tr!("reward", mins = 5)`
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::i18n::message($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::message($id, Some(&args))
+    }};
+}
+pub(crate) use tr;
+
+struct Translations {
+    // None for English
+    language: Option<Bundle>,
+    english: Bundle,
+}
+
+impl Translations {
+    fn load(language: Option<&str>, dir: &Path) -> Translations {
+        let english = bundle("en", built_in("en"), None);
+        let requested = language.map(str::to_string).or_else(system_language).unwrap_or_default();
+        // "de_AT.UTF-8" -> "de-AT", then "de"
+        let full = requested.split('.').next().unwrap_or_default().replace('_', "-");
+        let primary = full.split('-').next().unwrap_or_default().to_string();
+        // C and POSIX are the absence of a locale
+        if matches!(primary.as_str(), "" | "en" | "C" | "POSIX") {
+            return Translations { language: None, english };
+        }
+        let language = [full, primary]
+            .into_iter()
+            .find_map(|tag| {
+                let community = dir.join(format!("{}.ftl", tag));
+                let community = community.exists().then(|| read(&community)).flatten();
+                let built_in = built_in(&tag);
+                (built_in.is_some() || community.is_some()).then(|| bundle(&tag, built_in, community))
+            });
+        if language.is_none() {
+            warn!("No translation for {}; using English.", requested);
+        }
+        Translations { language, english }
+    }
+
+    fn message(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        self.language
+            .iter()
+            .chain([&self.english])
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(id)?.value()?;
+                Some(bundle.format_pattern(pattern, args, &mut Vec::new()).into_owned())
+            })
+            .unwrap_or_else(|| id.to_string())
+    }
+}
+
+// Picks the language: `language` from the config, else the system's. Only the
+// first call counts; a message asked for earlier uses the system language.
+pub fn init(language: Option<&str>) {
+    let _ = TRANSLATIONS.set(Translations::load(language, &locales_dir()));
+}
+
+pub fn message(id: &str, args: Option<&FluentArgs>) -> String {
+    TRANSLATIONS.get_or_init(|| Translations::load(None, &locales_dir())).message(id, args)
+}
+
+// community translations: `locales` next to the default config file
+pub fn locales_dir() -> PathBuf {
+    config::default_path().with_file_name("locales")
+}
+
+fn built_in(tag: &str) -> Option<FluentResource> {
+    let (_, source) = BUILT_IN.iter().find(|(t, _)| *t == tag)?;
+    Some(FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, _)| resource))
+}
+
+// a community file with mistakes still counts for the messages it got right
+fn read(path: &Path) -> Option<FluentResource> {
+    let source = std::fs::read_to_string(path).map_err(|e| warn!("Could not read {}: {}", path.display(), e)).ok()?;
+    Some(FluentResource::try_new(source).unwrap_or_else(|(resource, errors)| {
+        warn!("{}: {} message(s) could not be read", path.display(), errors.len());
+        resource
+    }))
+}
+
+type Bundle = FluentBundle<FluentResource>;
+
+fn bundle(tag: &str, built_in: Option<FluentResource>, community: Option<FluentResource>) -> Bundle {
+    let id: LanguageIdentifier = tag.parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // no invisible direction marks around values in a terminal
+    bundle.set_use_isolating(false);
+    if let Some(resource) = built_in {
+        let _ = bundle.add_resource(resource);
+    }
+    if let Some(resource) = community {
+        bundle.add_resource_overriding(resource);
+    }
+    bundle
+}
+
+#[cfg(not(windows))]
+fn system_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter().find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
+#[cfg(windows)]
+fn system_language() -> Option<String> {
+    use windows_sys::Win32::Globalization::GetUserDefaultLocaleName;
+
+    let mut buf = [0u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(buf.as_mut_ptr(), buf.len() as i32) };
+    // the length includes the terminating NUL
+    (len > 1).then(|| String::from_utf16_lossy(&buf[..len as usize - 1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(source: &str) -> Vec<&str> {
+        let ids = source.lines().filter_map(|line| line.split_once(" = ")).map(|(id, _)| id);
+        ids.filter(|id| !id.starts_with(' ')).collect()
+    }
+
+    #[test]
+    fn built_in_translations_match_english() {
+        let english = ids(BUILT_IN[0].1);
+        for (tag, source) in BUILT_IN {
+            assert!(FluentResource::try_new(source.to_string()).is_ok(), "{} doesn't parse", tag);
+            for id in ids(source) {
+                assert!(english.contains(&id), "{} has {}, which English doesn't", tag, id);
+            }
+        }
+    }
+
+    #[test]
+    fn languages_fall_back_to_english() {
+        let dir = std::env::temp_dir().join(format!("shhh-locales-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("nl.ftl"), "paused = Gepauzeerd.\n").unwrap();
+        let dutch = Translations::load(Some("nl_NL.UTF-8"), &dir);
+        assert_eq!(dutch.message("paused", None), "Gepauzeerd.");
+        assert_eq!(dutch.message("resumed", None), "Resumed by control request.");
+        std::fs::write(dir.join("de.ftl"), "cut = Offline.\n").unwrap();
+        let german = Translations::load(Some("de-AT"), &dir);
+        assert_eq!(german.message("cut", None), "Offline.");
+        assert_eq!(german.message("lifted", None), "Verbindung wieder da.");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut args = FluentArgs::new();
+        args.set("mins", 1);
+        let english = Translations::load(Some("en_US.UTF-8"), Path::new("/nonexistent"));
+        assert_eq!(english.message("reward", Some(&args)), "Quiet for a minute straight. Nice!");
+        args.set("mins", 20);
+        assert_eq!(english.message("reward", Some(&args)), "Quiet for 20 min straight. Nice!");
+    }
+}
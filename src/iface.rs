@@ -0,0 +1,399 @@
+// Network interface backend: the one place that actually turns the adapter
+// on and off, shared by the monitor and the `enable`/`disable` commands.
+// NetController is the seam: System drives the platform's adapters, and a new
+// OS backend is another implementation of it; tests use Mock, which only
+// pretends.
+#[cfg(test)]
+use std::collections::BTreeMap;
+#[cfg(not(windows))]
+use std::process::Command;
+#[cfg(test)]
+use std::sync::Mutex;
+
+use tracing::debug;
+#[cfg(test)]
+use tracing::info;
+
+use crate::config;
+use crate::error::Error;
+
+#[cfg(windows)]
+use windows as platform;
+#[cfg(not(windows))]
+use linux as platform;
+
+pub trait NetController: Send + Sync {
+    fn enable(&self, name: &str) -> Result<(), anyhow::Error>;
+    fn disable(&self, name: &str) -> Result<(), anyhow::Error>;
+    // whether the adapter is enabled (switched on, connected or not)
+    fn status(&self, name: &str) -> Result<bool, anyhow::Error>;
+}
+
+// this machine's adapters: `ip link` on Linux, SetupAPI on Windows
+pub struct System;
+
+impl NetController for System {
+    fn enable(&self, name: &str) -> Result<(), anyhow::Error> {
+        platform::set_enabled(name, true)
+    }
+
+    fn disable(&self, name: &str) -> Result<(), anyhow::Error> {
+        platform::set_enabled(name, false)
+    }
+
+    fn status(&self, name: &str) -> Result<bool, anyhow::Error> {
+        platform::enabled(name)
+    }
+}
+
+// Every adapter is attempted even if an earlier one fails, so a broken VPN
+// adapter can't keep Wi-Fi from being cut (or restored). One that's already
+// the way it should be is left alone.
+pub fn set_iface(net: &dyn NetController, names: &[String], enabled: bool) -> Result<(), anyhow::Error> {
+    let set = |name: &String| {
+        if net.status(name).is_ok_and(|status| status == enabled) {
+            debug!("{} is already {}", name, if enabled { "enabled" } else { "disabled" });
+            return Ok(());
+        }
+        if enabled { net.enable(name) } else { net.disable(name) }
+    };
+    let errors: Vec<String> = names.iter().filter_map(|name| set(name).err()).map(|e| e.to_string()).collect();
+    if !errors.is_empty() {
+        return Err(Error::Interface(errors.join("; ")).into());
+    }
+    Ok(())
+}
+
+// adapters that only exist in memory, all enabled to begin with; it logs and
+// remembers what it was asked to do
+#[cfg(test)]
+#[derive(Default)]
+pub struct Mock {
+    disabled: Mutex<BTreeMap<String, bool>>,
+    pub calls: Mutex<Vec<(String, bool)>>,
+}
+
+#[cfg(test)]
+impl Mock {
+    fn set(&self, name: &str, enabled: bool) -> Result<(), anyhow::Error> {
+        info!("[mock] {} {}", if enabled { "enable" } else { "disable" }, name);
+        self.calls.lock().unwrap().push((name.to_string(), enabled));
+        self.disabled.lock().unwrap().insert(name.to_string(), !enabled);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl NetController for Mock {
+    fn enable(&self, name: &str) -> Result<(), anyhow::Error> {
+        self.set(name, true)
+    }
+
+    fn disable(&self, name: &str) -> Result<(), anyhow::Error> {
+        self.set(name, false)
+    }
+
+    fn status(&self, name: &str) -> Result<bool, anyhow::Error> {
+        Ok(!self.disabled.lock().unwrap().get(name).copied().unwrap_or(false))
+    }
+}
+
+// The interfaces to control: the given names if any, otherwise whichever
+// adapter currently carries the default route. Detection only works while
+// that adapter is up, so resolve once at startup and keep the names.
+pub fn resolve(names: &[String]) -> Result<Vec<String>, anyhow::Error> {
+    if let Some(unknown) = names.iter().find(|n| !platform::exists(n)) {
+        let available = platform::list().unwrap_or_default();
+        let hint = config::closest(unknown, available.iter().map(String::as_str))
+            .map(|c| format!("; did you mean \"{}\"?", c))
+            .unwrap_or_default();
+        return Err(Error::NoInterface(format!(
+            "no network interface named \"{}\"{} (set by --interface or `interfaces` in the config); available: {}",
+            unknown,
+            hint,
+            available.iter().map(|n| format!("\"{}\"", n)).collect::<Vec<_>>().join(", ")
+        ))
+        .into());
+    }
+    if !names.is_empty() {
+        return Ok(names.to_vec());
+    }
+    let reason = match platform::default_route() {
+        Ok(Some(name)) => return Ok(vec![name]),
+        Ok(None) => "no default route".to_string(),
+        Err(e) => e.to_string(),
+    };
+    let available = platform::list().unwrap_or_default();
+    let message = if available.is_empty() {
+        format!("could not detect the active network interface ({}); pass --interface <name>", reason)
+    } else {
+        format!(
+            "could not detect the active network interface ({}); pass --interface with one of: {}",
+            reason,
+            available.iter().map(|n| format!("\"{}\"", n)).collect::<Vec<_>>().join(", ")
+        )
+    };
+    Err(Error::NoInterface(message).into())
+}
+
+#[cfg(not(windows))]
+mod linux {
+    use super::Command;
+
+    pub fn set_enabled(name: &str, enabled: bool) -> Result<(), anyhow::Error> {
+        let state = if enabled { "up" } else { "down" };
+        let output = Command::new("ip").args(["link", "set", "dev", name, state]).output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "ip link set {} {} failed: {}",
+                name,
+                state,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    // IFF_UP in the interface flags, which `ip link set ... down` clears
+    pub fn enabled(name: &str) -> Result<bool, anyhow::Error> {
+        let flags = std::fs::read_to_string(std::path::Path::new("/sys/class/net").join(name).join("flags"))?;
+        let flags = u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16)?;
+        Ok(flags & libc::IFF_UP as u32 != 0)
+    }
+
+    // lowest-metric 0.0.0.0/0 entry in /proc/net/route
+    pub fn default_route() -> Result<Option<String>, anyhow::Error> {
+        let table = std::fs::read_to_string("/proc/net/route")?;
+        let best = table
+            .lines()
+            .skip(1)
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .filter(|f| f.len() > 7 && f[1] == "00000000" && f[7] == "00000000")
+            .min_by_key(|f| f[6].parse::<u32>().unwrap_or(u32::MAX))
+            .map(|f| f[0].to_string());
+        Ok(best)
+    }
+
+    pub fn exists(name: &str) -> bool {
+        !name.contains('/') && std::path::Path::new("/sys/class/net").join(name).exists()
+    }
+
+    pub fn list() -> Result<Vec<String>, anyhow::Error> {
+        let mut names: Vec<String> = std::fs::read_dir("/sys/class/net")?
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .filter(|n| n != "lo")
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+}
+
+// Enabling/disabling a network adapter is a device state change, the same
+// thing Device Manager (and devcon) do through SetupAPI. The interface alias
+// ("Wi-Fi") is resolved to the adapter's GUID with IP Helper, which also works
+// while the adapter is disabled, and matched against each network device's
+// NetCfgInstanceId.
+#[cfg(windows)]
+mod windows {
+    use std::io;
+    use std::mem::size_of;
+    use std::ptr::{null, null_mut};
+
+    use windows_sys::core::GUID;
+    use windows_sys::Win32::Devices::DeviceAndDriverInstallation::{
+        CM_Get_DevNode_Status, SetupDiCallClassInstaller, SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW,
+        SetupDiOpenDevRegKey, SetupDiSetClassInstallParamsW, DICS_DISABLE, DICS_ENABLE, DICS_FLAG_GLOBAL,
+        DIF_PROPERTYCHANGE, DIGCF_PRESENT, DIREG_DRV, GUID_DEVCLASS_NET, HDEVINFO, SP_CLASSINSTALL_HEADER,
+        SP_DEVINFO_DATA, SP_PROPCHANGE_PARAMS, CM_PROB_DISABLED, CR_SUCCESS, DN_HAS_PROBLEM,
+    };
+    use windows_sys::Win32::Foundation::{ERROR_SUCCESS, INVALID_HANDLE_VALUE, NO_ERROR};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        ConvertInterfaceAliasToLuid, ConvertInterfaceIndexToLuid, ConvertInterfaceLuidToAlias,
+        ConvertInterfaceLuidToGuid, FreeMibTable, GetBestInterfaceEx, GetIfTable2, IF_TYPE_SOFTWARE_LOOPBACK,
+        MIB_IF_TABLE2,
+    };
+    use windows_sys::Win32::NetworkManagement::Ndis::NET_LUID_LH;
+    use windows_sys::Win32::Networking::WinSock::{AF_INET, IN_ADDR, IN_ADDR_0, SOCKADDR, SOCKADDR_IN};
+    use windows_sys::Win32::System::Registry::{RegCloseKey, RegQueryValueExW, KEY_READ};
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn win32(code: u32, what: &str) -> anyhow::Error {
+        anyhow::anyhow!("{}: {}", what, io::Error::from_raw_os_error(code as i32))
+    }
+
+    fn adapter_guid(alias: &str) -> Result<String, anyhow::Error> {
+        let mut luid = NET_LUID_LH { Value: 0 };
+        let mut guid: GUID = unsafe { std::mem::zeroed() };
+        unsafe {
+            let err = ConvertInterfaceAliasToLuid(wide(alias).as_ptr(), &mut luid);
+            if err != ERROR_SUCCESS {
+                return Err(win32(err, &format!("no network interface named '{}'", alias)));
+            }
+            let err = ConvertInterfaceLuidToGuid(&luid, &mut guid);
+            if err != ERROR_SUCCESS {
+                return Err(win32(err, "could not resolve interface GUID"));
+            }
+        }
+        Ok(format!(
+            "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+            guid.data1, guid.data2, guid.data3,
+            guid.data4[0], guid.data4[1], guid.data4[2], guid.data4[3],
+            guid.data4[4], guid.data4[5], guid.data4[6], guid.data4[7]
+        ))
+    }
+
+    // the NetCfgInstanceId value under the device's driver key
+    fn net_cfg_instance_id(set: HDEVINFO, dev: &SP_DEVINFO_DATA) -> Option<String> {
+        unsafe {
+            let key = SetupDiOpenDevRegKey(set, dev, DICS_FLAG_GLOBAL, 0, DIREG_DRV, KEY_READ);
+            if key.is_null() || key == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let mut buf = [0u16; 64];
+            let mut len = (buf.len() * 2) as u32;
+            let err = RegQueryValueExW(
+                key,
+                wide("NetCfgInstanceId").as_ptr(),
+                null(),
+                null_mut(),
+                buf.as_mut_ptr() as *mut u8,
+                &mut len,
+            );
+            RegCloseKey(key);
+            if err != ERROR_SUCCESS {
+                return None;
+            }
+            let chars = &buf[..(len as usize / 2)];
+            Some(String::from_utf16_lossy(chars).trim_end_matches('\0').to_string())
+        }
+    }
+
+    // closes the device info list on every exit path
+    struct DevInfoList(HDEVINFO);
+
+    impl Drop for DevInfoList {
+        fn drop(&mut self) {
+            unsafe { SetupDiDestroyDeviceInfoList(self.0) };
+        }
+    }
+
+    fn from_wide(buf: &[u16]) -> String {
+        let end = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        String::from_utf16_lossy(&buf[..end])
+    }
+
+    // the interface Windows would route 8.8.8.8 through (nothing is sent)
+    pub fn default_route() -> Result<Option<String>, anyhow::Error> {
+        let dest = SOCKADDR_IN {
+            sin_family: AF_INET,
+            sin_port: 0,
+            sin_addr: IN_ADDR { S_un: IN_ADDR_0 { S_addr: u32::from_ne_bytes([8, 8, 8, 8]) } },
+            sin_zero: [0; 8],
+        };
+        let mut index = 0u32;
+        let mut luid = NET_LUID_LH { Value: 0 };
+        let mut alias = [0u16; 257];
+        unsafe {
+            if GetBestInterfaceEx(&dest as *const SOCKADDR_IN as *const SOCKADDR, &mut index) != NO_ERROR {
+                return Ok(None);
+            }
+            let err = ConvertInterfaceIndexToLuid(index, &mut luid);
+            if err != ERROR_SUCCESS {
+                return Err(win32(err, "could not resolve interface index"));
+            }
+            let err = ConvertInterfaceLuidToAlias(&luid, alias.as_mut_ptr(), alias.len());
+            if err != ERROR_SUCCESS {
+                return Err(win32(err, "could not resolve interface name"));
+            }
+        }
+        Ok(Some(from_wide(&alias)))
+    }
+
+    pub fn exists(alias: &str) -> bool {
+        adapter_guid(alias).is_ok()
+    }
+
+    // hardware adapters, including disabled ones
+    pub fn list() -> Result<Vec<String>, anyhow::Error> {
+        let mut table: *mut MIB_IF_TABLE2 = null_mut();
+        let err = unsafe { GetIfTable2(&mut table) };
+        if err != ERROR_SUCCESS {
+            return Err(win32(err, "could not list interfaces"));
+        }
+        let mut names = Vec::new();
+        unsafe {
+            let rows = std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize);
+            for row in rows {
+                let hardware = row.InterfaceAndOperStatusFlags._bitfield & 1 != 0;
+                let name = from_wide(&row.Alias);
+                if hardware && row.Type != IF_TYPE_SOFTWARE_LOOPBACK && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            FreeMibTable(table as *const _);
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    // the adapter's entry in the network device list
+    fn device(alias: &str) -> Result<(DevInfoList, SP_DEVINFO_DATA), anyhow::Error> {
+        let guid = adapter_guid(alias)?;
+        let set = unsafe { SetupDiGetClassDevsW(&GUID_DEVCLASS_NET, null(), null_mut(), DIGCF_PRESENT) };
+        if set == INVALID_HANDLE_VALUE as HDEVINFO {
+            return Err(io::Error::last_os_error().into());
+        }
+        let set = DevInfoList(set);
+
+        let mut dev = SP_DEVINFO_DATA { cbSize: size_of::<SP_DEVINFO_DATA>() as u32, ..Default::default() };
+        let mut index = 0;
+        loop {
+            if unsafe { SetupDiEnumDeviceInfo(set.0, index, &mut dev) } == 0 {
+                anyhow::bail!("no network device found for interface '{}' ({})", alias, guid);
+            }
+            if net_cfg_instance_id(set.0, &dev).is_some_and(|id| id.eq_ignore_ascii_case(&guid)) {
+                return Ok((set, dev));
+            }
+            index += 1;
+        }
+    }
+
+    // a disabled device is one with the "disabled" problem code
+    pub fn enabled(alias: &str) -> Result<bool, anyhow::Error> {
+        let (_set, dev) = device(alias)?;
+        let (mut status, mut problem) = (0, 0);
+        let err = unsafe { CM_Get_DevNode_Status(&mut status, &mut problem, dev.DevInst, 0) };
+        if err != CR_SUCCESS {
+            anyhow::bail!("could not read the state of '{}' (CONFIGRET {})", alias, err);
+        }
+        Ok(status & DN_HAS_PROBLEM == 0 || problem != CM_PROB_DISABLED)
+    }
+
+    pub fn set_enabled(alias: &str, enabled: bool) -> Result<(), anyhow::Error> {
+        let (set, dev) = device(alias)?;
+
+        let params = SP_PROPCHANGE_PARAMS {
+            ClassInstallHeader: SP_CLASSINSTALL_HEADER {
+                cbSize: size_of::<SP_CLASSINSTALL_HEADER>() as u32,
+                InstallFunction: DIF_PROPERTYCHANGE,
+            },
+            StateChange: if enabled { DICS_ENABLE } else { DICS_DISABLE },
+            Scope: DICS_FLAG_GLOBAL,
+            HwProfile: 0,
+        };
+        unsafe {
+            if SetupDiSetClassInstallParamsW(set.0, &dev, &params.ClassInstallHeader, size_of::<SP_PROPCHANGE_PARAMS>() as u32) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            // fails with access denied unless we're elevated
+            if SetupDiCallClassInstaller(DIF_PROPERTYCHANGE, set.0, &dev) == 0 {
+                let e = io::Error::last_os_error();
+                anyhow::bail!("could not {} '{}': {}", if enabled { "enable" } else { "disable" }, alias, e);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+// Pushes every window to InfluxDB (or anything else that takes the line
+// protocol: Telegraf, VictoriaMetrics, QuestDB) for an existing Grafana
+// setup. A consumer of the monitor's event bus batches windows and POSTs
+// them every `batch_secs`; a batch that can't be delivered is kept and sent
+// with the next one, up to MAX_BUFFERED lines, so a restarting database
+// loses nothing and a missing one doesn't grow without bound.
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::bus::Event;
+use crate::config::InfluxConfig;
+use crate::history::Entry;
+
+// ~1h of windows
+const MAX_BUFFERED: usize = 2 * 60 * 60;
+
+pub struct Exporter {
+    config: InfluxConfig,
+    url: String,
+    lines: Vec<String>,
+    last_flush: Instant,
+    failing: bool,
+}
+
+impl Exporter {
+    pub fn new(config: InfluxConfig) -> Exporter {
+        // timestamps are in ms; InfluxDB assumes ns unless told
+        let url = match config.url.contains("precision=") {
+            true => config.url.clone(),
+            false if config.url.contains('?') => format!("{}&precision=ms", config.url),
+            false => format!("{}?precision=ms", config.url),
+        };
+        Exporter { config, url, lines: Vec::new(), last_flush: Instant::now(), failing: false }
+    }
+
+    pub fn consume(&mut self, event: Event) {
+        if let Event::Window(entry) = event {
+            if self.lines.len() == MAX_BUFFERED {
+                self.lines.remove(0);
+            }
+            self.lines.push(line(&self.config, &entry));
+        }
+        if self.last_flush.elapsed() >= Duration::from_secs(self.config.batch_secs) {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.lines.is_empty() {
+            return;
+        }
+        let mut req = ureq::post(&self.url)
+            .timeout(Duration::from_secs(10))
+            .set("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = &self.config.token {
+            req = req.set("Authorization", &format!("Token {}", token));
+        }
+        match req.send_string(&self.lines.join("\n")) {
+            Ok(_) => {
+                if self.failing {
+                    info!("InfluxDB export working again.");
+                }
+                self.failing = false;
+                self.lines.clear();
+            }
+            // a batch the server rejects would be rejected again
+            Err(ureq::Error::Status(code, response)) if (400..500).contains(&code) && code != 429 => {
+                let body = response.into_string().unwrap_or_default();
+                warn!("InfluxDB rejected {} windows ({}): {}", self.lines.len(), code, body);
+                self.lines.clear();
+            }
+            Err(e) => {
+                // only the first failure of a run of them
+                if !self.failing {
+                    warn!("InfluxDB export failed, will keep retrying: {}", e);
+                }
+                self.failing = true;
+            }
+        }
+    }
+}
+
+// whatever is left when the bus closes
+impl Drop for Exporter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+// `shhh,room=kids db=-41.2,pct=80i,min_db=-50,max_db=-20,cut=false,state="OK 80%" 1700000000000`
+fn line(config: &InfluxConfig, entry: &Entry) -> String {
+    let mut line = escape(&config.measurement, ", ");
+    for (key, value) in &config.tags {
+        let _ = write!(line, ",{}={}", escape(key, ",= "), escape(value, ",= "));
+    }
+    let _ = write!(
+        line,
+        " db={},pct={}i,min_db={},max_db={},cut={},state=\"{}\" {}",
+        entry.db,
+        entry.pct,
+        entry.min_db,
+        entry.max_db,
+        entry.state == "CUT",
+        escape(&entry.state, "\""),
+        entry.time_ms
+    );
+    line
+}
+
+// a backslash before each of `special` (and before backslashes)
+fn escape(s: &str, special: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '\\' || special.contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_become_line_protocol() {
+        let config = InfluxConfig { tags: [("room".into(), "kids, up".into())].into(), ..InfluxConfig::default() };
+        let state = "OK 80%".into();
+        let entry = Entry { time_ms: 1_700_000_000_000, db: -41.5, pct: 80, state, min_db: -50.0, max_db: -20.0 };
+        assert_eq!(
+            line(&config, &entry),
+            r#"shhh,room=kids\,\ up db=-41.5,pct=80i,min_db=-50,max_db=-20,cut=false,state="OK 80%" 1700000000000"#
+        );
+        assert_eq!(escape(r#"a b,c"d\"#, ", "), r#"a\ b\,c"d\\"#);
+        let url = |url: &str| Exporter::new(InfluxConfig { url: url.into(), ..InfluxConfig::default() }).url.clone();
+        assert_eq!(url("http://db:8086/api/v2/write?bucket=h"), "http://db:8086/api/v2/write?bucket=h&precision=ms");
+        assert_eq!(url("http://db:8086/write?db=home&precision=ms"), "http://db:8086/write?db=home&precision=ms");
+    }
+}
@@ -0,0 +1,253 @@
+// Local control channel: a Unix socket (Linux) or named pipe (Windows) that
+// lets a second `shhh` process query or steer the running monitor.
+//
+// The protocol is one request per line ("status", "pause", "profile night",
+// ...) answered by one line of JSON, so it is easy to poke at with socat as
+// well.
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::Mutex;
+use std::thread;
+
+use interprocess::local_socket::{prelude::*, ListenerOptions, Name, Stream};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config;
+use crate::error::Error;
+use crate::history::Entry;
+use crate::i18n::{self, tr};
+use crate::monitor::Handle;
+use crate::profile;
+
+#[derive(Clone, Debug, PartialEq, Eq, clap::Subcommand)]
+pub enum Request {
+    /// Print the current level, thresholds and enforcement state
+    Status {
+        /// Print it as JSON, for scripts
+        #[arg(long)]
+        json: bool,
+    },
+    /// Stop enforcing (the interface is restored) until resumed
+    Pause,
+    /// Resume enforcing after a pause
+    Resume,
+    /// Re-enable the interface right now
+    Restore,
+    /// Cut right now, however quiet it is, for at least SECS seconds
+    Cut { secs: u64 },
+    /// Re-measure ambient noise and recompute the thresholds
+    Recalibrate,
+    /// Restore and shut the monitor down (the authorized way to stop `--protect`)
+    Stop,
+    /// Switch to a profile from the config, or `auto` to follow their hours again
+    Profile { name: String },
+    /// Print the recorded windows newer than a Unix time in ms, one JSON object per line
+    #[command(hide = true)]
+    History { since_ms: u64 },
+}
+
+impl Request {
+    fn parse(line: &str) -> Option<Request> {
+        let mut words = line.split_whitespace();
+        let request = match words.next()?.to_ascii_lowercase().as_str() {
+            "status" => Request::Status { json: false },
+            "pause" => Request::Pause,
+            "resume" => Request::Resume,
+            "restore" => Request::Restore,
+            "cut" => Request::Cut { secs: words.next()?.parse().ok()? },
+            "recalibrate" => Request::Recalibrate,
+            "stop" => Request::Stop,
+            "profile" => Request::Profile { name: words.next()?.to_string() },
+            "history" => Request::History { since_ms: words.next()?.parse().ok()? },
+            _ => return None,
+        };
+        words.next().is_none().then_some(request)
+    }
+
+    fn to_line(&self) -> String {
+        match self {
+            Request::Status { .. } => "status".into(),
+            Request::Pause => "pause".into(),
+            Request::Resume => "resume".into(),
+            Request::Restore => "restore".into(),
+            Request::Cut { secs } => format!("cut {}", secs),
+            Request::Recalibrate => "recalibrate".into(),
+            Request::Stop => "stop".into(),
+            Request::Profile { name } => format!("profile {}", name),
+            Request::History { since_ms } => format!("history {}", since_ms),
+        }
+    }
+}
+
+// snapshot of the monitor, kept up to date by run_loop
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub state: String,
+    pub db: f32,
+    pub ambient_db: f32,
+    pub min_db: f32,
+    pub max_db: f32,
+    pub paused: bool,
+    // the profile in force, if any, and every one that could be
+    pub profile: Option<String>,
+    pub profiles: Vec<String>,
+    // unbroken quiet time so far
+    pub quiet_secs: u64,
+    // how long the monitor has been running
+    pub uptime_secs: u64,
+    // during a cut with a penalty (or a keyword's) still to serve, what's left
+    pub penalty_left_secs: Option<u64>,
+    // counting down to a cut: the seconds left
+    pub cut_in_secs: Option<u64>,
+    // during a cut that waits for a quiet streak: the seconds of it still needed
+    pub restore_in_secs: Option<u64>,
+    // suspected microphone tampering, while it lasts
+    pub tamper: Option<String>,
+    // what enforcing does, e.g. "disconnect Wi-Fi"
+    pub action: String,
+    pub enforcing: bool,
+    // last enforcement failure, cleared on the next success
+    pub action_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Response {
+    Status(Box<Status>),
+    History(Vec<Entry>),
+    Ok,
+    Error(String),
+}
+
+#[cfg(windows)]
+fn socket_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericNamespaced;
+    // \\.\pipe\shhh — the default pipe DACL only gives write access to
+    // SYSTEM, administrators and the owner, so the monitored user can't send
+    "shhh".to_ns_name::<GenericNamespaced>()
+}
+
+#[cfg(unix)]
+fn socket_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericFilePath;
+    // root (the systemd unit) and regular users get separate sockets
+    let path = if unsafe { libc::geteuid() } == 0 {
+        "/run/shhh.sock".to_string()
+    } else {
+        std::env::temp_dir().join(format!("shhh-{}.sock", unsafe { libc::geteuid() })).display().to_string()
+    };
+    path.to_fs_name::<GenericFilePath>()
+}
+
+// accept connections forever on a background thread; control requests are
+// forwarded to the monitor loop, status is answered from the shared snapshot
+pub fn serve(handle: Handle) -> io::Result<()> {
+    let opts = ListenerOptions::new().name(socket_name()?).try_overwrite(true);
+    #[cfg(unix)]
+    let opts = {
+        use interprocess::os::unix::local_socket::ListenerOptionsExt;
+        opts.mode(0o600)
+    };
+    let listener = opts.create_sync()?;
+
+    thread::spawn(move || {
+        for conn in listener.incoming().filter_map(Result::ok) {
+            if let Err(e) = answer(conn, &handle) {
+                warn!("Control connection error: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn answer(conn: Stream, handle: &Handle) -> io::Result<()> {
+    let mut conn = BufReader::new(conn);
+    let mut line = String::new();
+    conn.read_line(&mut line)?;
+
+    let response = match Request::parse(line.trim()) {
+        Some(Request::Status { .. }) => match handle.status.lock() {
+            Ok(s) => Response::Status(Box::new(s.clone())),
+            Err(_) => Response::Error("status unavailable".into()),
+        },
+        Some(Request::History { since_ms }) => match handle.history.lock() {
+            Ok(h) => Response::History(h.since(since_ms, usize::MAX)),
+            Err(_) => Response::Error("history unavailable".into()),
+        },
+        Some(Request::Profile { name }) if !known_profile(&handle.status, &name) => {
+            Response::Error(format!("no profile named '{}'", name))
+        }
+        Some(req) => match handle.control.send(req) {
+            Ok(()) => Response::Ok,
+            Err(_) => Response::Error("monitor is shutting down".into()),
+        },
+        None => Response::Error(format!("unknown request '{}'", line.trim())),
+    };
+
+    let mut out = serde_json::to_string(&response).map_err(io::Error::other)?;
+    out.push('\n');
+    conn.get_mut().write_all(out.as_bytes())
+}
+
+pub fn known_profile(status: &Mutex<Status>, name: &str) -> bool {
+    name == profile::AUTO || status.lock().is_ok_and(|s| s.profiles.iter().any(|p| p == name))
+}
+
+pub fn send(request: Request) -> Result<Response, anyhow::Error> {
+    let conn = Stream::connect(socket_name()?)
+        .map_err(|e| Error::NotRunning(e.to_string()))?;
+    let mut conn = BufReader::new(conn);
+    conn.get_mut().write_all(format!("{}\n", request.to_line()).as_bytes())?;
+
+    let mut line = String::new();
+    conn.read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+// `shhh ctl <request>`
+pub fn client(request: Request) -> Result<(), anyhow::Error> {
+    let json = matches!(request, Request::Status { json: true });
+    match send(request)? {
+        Response::Status(s) if json => println!("{}", serde_json::to_string_pretty(&s)?),
+        Response::Status(s) => {
+            i18n::init(config::load(None).ok().and_then(|c| c.language).as_deref());
+            let state = if s.paused { format!("{} {}", s.state, tr!("status-paused")) } else { s.state };
+            let db = |db: f32| format!("{:.1}", db);
+            let thresholds = tr!("status-ambient", ambient = db(s.ambient_db), min = db(s.min_db), max = db(s.max_db));
+            let action = format!("{} ({})", s.action, tr!(if s.enforcing { "status-in-force" } else { "status-idle" }));
+            let uptime = tr!("status-uptime-value", hours = s.uptime_secs / 3600, mins = s.uptime_secs / 60 % 60);
+            let rows = [
+                ("status-state", Some(state)),
+                ("status-level", Some(format!("{} dBFS", db(s.db)))),
+                ("status-thresholds", Some(thresholds)),
+                ("status-profile", s.profile),
+                ("status-uptime", Some(uptime)),
+                ("status-penalty", s.penalty_left_secs.map(|secs| tr!("status-secs", secs = secs))),
+                ("status-quiet", Some(tr!("status-quiet-mins", mins = s.quiet_secs / 60))),
+                ("status-countdown", s.cut_in_secs.map(|secs| tr!("status-secs", secs = secs))),
+                ("status-restore", s.restore_in_secs.map(|secs| tr!("status-restore-secs", secs = secs))),
+                ("status-action", Some(action)),
+                ("status-tampering", s.tamper),
+                ("status-error", s.action_error),
+            ];
+            let rows: Vec<(String, String)> =
+                rows.into_iter().filter_map(|(label, value)| Some((format!("{}:", tr!(label)), value?))).collect();
+            let width = rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+            for (label, value) in rows {
+                println!("{:width$} {}", label, value);
+            }
+        }
+        Response::History(entries) => {
+            // written rather than printed, so piping into `head` ends quietly
+            let mut out = io::stdout().lock();
+            for entry in entries {
+                if writeln!(out, "{}", serde_json::to_string(&entry)?).is_err() {
+                    break;
+                }
+            }
+        }
+        Response::Ok => println!("ok"),
+        Response::Error(e) => anyhow::bail!(e),
+    }
+    Ok(())
+}
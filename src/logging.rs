@@ -0,0 +1,202 @@
+// Logging through `tracing`: to the console as plain messages (warnings and
+// errors on stderr), and with `[log]` in the config (or by default when there
+// is no console: `--daemon`, the Windows service) to `shhh.log` as well. The
+// file is rotated daily or hourly and whenever it outgrows `max_size_mb`, and
+// only the newest `keep` rotated files are kept. `--verbosity` sets the level
+// for both; `debug` adds every window's level.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local};
+use tracing::Level;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+
+use crate::config::{LogConfig, Rotation};
+
+const FILE_NAME: &str = "shhh.log";
+
+// the log file, once the config has said where
+static FILE: Mutex<Option<Rotating>> = Mutex::new(None);
+
+// Console from the start, so even a config error is logged; the file joins
+// in with to_file.
+pub fn init(level: Level) {
+    let filter = LevelFilter::from_level(level);
+    let console = tracing_subscriber::fmt::layer()
+        .without_time()
+        .with_level(false)
+        .with_target(false)
+        .with_ansi(false)
+        .with_writer(io::stderr.with_max_level(Level::WARN).or_else(io::stdout));
+    let file = tracing_subscriber::fmt::layer().with_target(false).with_ansi(false).with_writer(FileSink);
+    let _ = tracing_subscriber::registry().with(console.with_filter(filter)).with(file.with_filter(filter)).try_init();
+}
+
+// start writing the log file; `None` means no `[log]` section, which still
+// logs to the default place when there's no console to read it on
+pub fn to_file(config: Option<&LogConfig>, headless: bool) -> Result<(), anyhow::Error> {
+    let config = match config {
+        Some(config) => config.clone(),
+        None if headless => LogConfig::default(),
+        None => return Ok(()),
+    };
+    let dir = config.dir.clone().unwrap_or_else(default_dir);
+    fs::create_dir_all(&dir).map_err(|e| anyhow::anyhow!("could not create log directory {}: {}", dir.display(), e))?;
+    let rotating = Rotating::open(dir, config)?;
+    tracing::info!("Logging to {}", rotating.path().display());
+    if let Ok(mut file) = FILE.lock() {
+        *file = Some(rotating);
+    }
+    Ok(())
+}
+
+pub fn default_dir() -> PathBuf {
+    #[cfg(windows)]
+    return crate::config::default_path().with_file_name("logs");
+    #[cfg(not(windows))]
+    PathBuf::from("/var/log/shhh")
+}
+
+struct FileSink;
+
+impl<'a> MakeWriter<'a> for FileSink {
+    type Writer = FileSink;
+
+    fn make_writer(&'a self) -> FileSink {
+        FileSink
+    }
+}
+
+impl Write for FileSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match FILE.lock().as_deref_mut() {
+            Ok(Some(file)) => file.write(buf),
+            _ => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match FILE.lock().as_deref_mut() {
+            Ok(Some(file)) => file.file.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+// shhh.log, moved aside to shhh-<when>.log when its period ends or it's full
+struct Rotating {
+    dir: PathBuf,
+    config: LogConfig,
+    file: File,
+    size: u64,
+    // the period the current file belongs to
+    period: String,
+}
+
+impl Rotating {
+    fn open(dir: PathBuf, config: LogConfig) -> Result<Rotating, anyhow::Error> {
+        let path = dir.join(FILE_NAME);
+        // a file left by an earlier run belongs to the period it was written in
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).map(DateTime::<Local>::from);
+        let period = period(config.rotate, modified.unwrap_or_else(|_| Local::now()));
+        let file = append(&path)?;
+        let size = file.metadata()?.len();
+        let mut rotating = Rotating { dir, config, file, size, period };
+        if rotating.due(0) {
+            rotating.rotate()?;
+        }
+        Ok(rotating)
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.join(FILE_NAME)
+    }
+
+    fn due(&self, incoming: usize) -> bool {
+        let max = self.config.max_size_mb * 1024 * 1024;
+        let full = max > 0 && self.size > 0 && self.size + incoming as u64 > max;
+        full || period(self.config.rotate, Local::now()) != self.period
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = self.dir.join(format!("shhh-{}.log", Local::now().format("%Y%m%d-%H%M%S")));
+        fs::rename(self.path(), &rotated)?;
+        self.file = append(&self.path())?;
+        self.size = 0;
+        self.period = period(self.config.rotate, Local::now());
+        prune(&self.dir, self.config.keep)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) {
+            // better one oversized file than a lost line
+            let _ = self.rotate();
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn period(rotation: Rotation, time: DateTime<Local>) -> String {
+    match rotation {
+        Rotation::Daily => time.format("%Y%m%d").to_string(),
+        Rotation::Hourly => time.format("%Y%m%d%H").to_string(),
+        Rotation::Never => String::new(),
+    }
+}
+
+// rotated names sort by when they were rotated
+fn prune(dir: &Path, keep: usize) -> io::Result<()> {
+    let mut rotated: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with("shhh-") && name.ends_with(".log")
+        })
+        .collect();
+    rotated.sort();
+    for path in &rotated[..rotated.len().saturating_sub(keep)] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_files_are_rotated_and_pruned() {
+        let dir = std::env::temp_dir().join(format!("shhh-log-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for old in ["shhh-20200101-000000.log", "shhh-20200102-000000.log"] {
+            fs::write(dir.join(old), "old\n").unwrap();
+        }
+        let config = LogConfig { max_size_mb: 1, keep: 2, rotate: Rotation::Never, ..LogConfig::default() };
+        let mut log = Rotating::open(dir.clone(), config).unwrap();
+        let line = vec![b'x'; 600 * 1024];
+        log.write(&line).unwrap();
+        // the second would take it past 1 MiB
+        log.write(&line).unwrap();
+        assert_eq!(fs::metadata(dir.join(FILE_NAME)).unwrap().len(), line.len() as u64);
+        let mut names: Vec<String> =
+            fs::read_dir(&dir).unwrap().flatten().map(|e| e.file_name().to_string_lossy().into_owned()).collect();
+        names.sort();
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[0], "shhh-20200102-000000.log");
+        assert_eq!(names[2], FILE_NAME);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
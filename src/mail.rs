@@ -0,0 +1,308 @@
+// Email alerts for households without a chat or push service: with `[email]`
+// in the config, a consumer of the monitor's event bus mails cuts, lifts,
+// tampering and strike-outs, and optionally a daily digest. The first event
+// after a quiet spell goes out right away and the rest are batched, at most
+// one mail every `batch_mins`, so a flappy evening is a few mails rather
+// than forty. A mail that can't be delivered is kept for the next try.
+//
+// Plain SMTP, by hand: implicit TLS (port 465) or STARTTLS (587) through
+// native-tls, AUTH PLAIN, and a UTF-8 text body.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use chrono::{Local, NaiveDate};
+use data_encoding::BASE64;
+use tracing::{info, warn};
+
+use crate::bus::Event;
+use crate::config::{EmailConfig, Security};
+use crate::i18n::tr;
+use crate::policy::Decision;
+use crate::profile;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// what the day held, for the digest
+#[derive(Default)]
+struct Day {
+    cuts: u32,
+    cut_for: Duration,
+    rewards: u32,
+    tampering: u32,
+    strike_outs: u32,
+}
+
+pub struct Mailer {
+    config: EmailConfig,
+    host: String,
+    // event lines not sent yet, and when the last mail went out
+    pending: Vec<String>,
+    last_sent: Option<Instant>,
+    day: Day,
+    cut_since: Option<Instant>,
+    digest_sent: Option<NaiveDate>,
+}
+
+impl Mailer {
+    pub fn new(config: EmailConfig) -> Mailer {
+        Mailer {
+            config,
+            host: hostname(),
+            pending: Vec::new(),
+            last_sent: None,
+            day: Day::default(),
+            cut_since: None,
+            // not today's, if it's already past the time at startup
+            digest_sent: Some(Local::now().date_naive()),
+        }
+    }
+
+    pub fn consume(&mut self, event: Event) {
+        let time = Local::now().format("%H:%M");
+        match event {
+            Event::Enforced { enforce: true, error: None } => {
+                self.day.cuts += 1;
+                self.cut_since = Some(Instant::now());
+                self.pending.push(format!("{} {}", time, tr!("cut")));
+            }
+            Event::Enforced { enforce: false, error: None } => {
+                if let Some(since) = self.cut_since.take() {
+                    self.day.cut_for += since.elapsed();
+                }
+                self.pending.push(format!("{} {}", time, tr!("lifted")));
+            }
+            Event::Decisions(decisions) => {
+                for decision in decisions {
+                    match decision {
+                        Decision::Reward(_) => self.day.rewards += 1,
+                        Decision::Tamper(tamper) => {
+                            self.day.tampering += 1;
+                            self.pending.push(format!("{} {}", time, tr!("tampering", what = tamper.to_string())));
+                        }
+                        Decision::TamperCleared => self.pending.push(format!("{} {}", time, tr!("tampering-cleared"))),
+                        Decision::StruckOut => {
+                            self.day.strike_outs += 1;
+                            self.pending.push(format!("{} {}", time, tr!("email-struck-out")));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+        let batch = Duration::from_secs(self.config.batch_mins * 60);
+        if !self.pending.is_empty() && self.last_sent.is_none_or(|at| at.elapsed() >= batch) {
+            self.send_pending();
+        }
+        self.digest();
+    }
+
+    fn send_pending(&mut self) {
+        let summary = match self.pending.len() {
+            1 => self.pending[0].clone(),
+            n => tr!("email-events", count = n),
+        };
+        // a failed mail is retried after another batch interval
+        self.last_sent = Some(Instant::now());
+        if self.mail(&summary, &self.pending.join("\n")) {
+            self.pending.clear();
+        }
+    }
+
+    fn digest(&mut self) {
+        let (Some(at), today) = (self.config.digest_at.as_deref().and_then(profile::minute), Local::now().date_naive())
+        else {
+            return;
+        };
+        if self.digest_sent == Some(today) || profile::minute_now() < at {
+            return;
+        }
+        self.digest_sent = Some(today);
+        let day = std::mem::take(&mut self.day);
+        let mut cut_for = day.cut_for;
+        if let Some(since) = &mut self.cut_since {
+            cut_for += since.elapsed();
+            *since = Instant::now();
+        }
+        let lines = [
+            tr!("email-digest-cuts", count = day.cuts, mins = cut_for.as_secs() / 60),
+            tr!("email-digest-rewards", count = day.rewards),
+            tr!("email-digest-tampering", count = day.tampering),
+            tr!("email-digest-strike-outs", count = day.strike_outs),
+        ];
+        self.mail(&tr!("email-digest"), &lines.join("\n"));
+    }
+
+    // whether it went out
+    fn mail(&self, summary: &str, events: &str) -> bool {
+        let fill = |template: &str| fill(template, &self.host, summary, events);
+        match send(&self.config, &self.host, &fill(&self.config.subject), &fill(&self.config.body)) {
+            Ok(()) => {
+                info!("Emailed {}: {}", self.config.to.join(", "), summary);
+                true
+            }
+            Err(e) => {
+                warn!("Could not send email via {}: {}", self.config.server, e);
+                false
+            }
+        }
+    }
+}
+
+// what's still batched goes out when the monitor stops
+impl Drop for Mailer {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() {
+            self.send_pending();
+        }
+    }
+}
+
+// `{host}`, `{summary}` and `{events}` in a subject or body template
+fn fill(template: &str, host: &str, summary: &str, events: &str) -> String {
+    template.replace("{host}", host).replace("{summary}", summary).replace("{events}", events)
+}
+
+fn hostname() -> String {
+    #[cfg(unix)]
+    let name = {
+        let mut buf = [0u8; 256];
+        let ok = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } == 0;
+        let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        ok.then(|| String::from_utf8_lossy(&buf[..end]).into_owned())
+    };
+    #[cfg(windows)]
+    let name = std::env::var("COMPUTERNAME").ok();
+    name.filter(|n| !n.is_empty()).unwrap_or_else(|| "localhost".into())
+}
+
+fn send(config: &EmailConfig, host: &str, subject: &str, body: &str) -> Result<(), anyhow::Error> {
+    let port = config.port.unwrap_or(match config.security {
+        Security::Tls => 465,
+        Security::Starttls => 587,
+        Security::None => 25,
+    });
+    let addr = (config.server.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{} has no address", config.server))?;
+    let tcp = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+    let tls = native_tls::TlsConnector::new()?;
+    let message = message(config, subject, body);
+    match config.security {
+        Security::Tls => session(&mut BufReader::new(tls.connect(&config.server, tcp)?), config, host, &message, true),
+        Security::Starttls => {
+            let mut plain = BufReader::new(tcp);
+            reply(&mut plain, 220)?;
+            command(&mut plain, &format!("EHLO {}", host), 250)?;
+            command(&mut plain, "STARTTLS", 220)?;
+            let mut secure = BufReader::new(tls.connect(&config.server, plain.into_inner())?);
+            session(&mut secure, config, host, &message, false)
+        }
+        Security::None => session(&mut BufReader::new(tcp), config, host, &message, true),
+    }
+}
+
+// from the greeting (or, after STARTTLS, the second EHLO) on
+fn session<S: Read + Write>(
+    stream: &mut BufReader<S>,
+    config: &EmailConfig,
+    host: &str,
+    message: &str,
+    greeting: bool,
+) -> Result<(), anyhow::Error> {
+    if greeting {
+        reply(stream, 220)?;
+    }
+    command(stream, &format!("EHLO {}", host), 250)?;
+    if let Some(username) = &config.username {
+        let credentials = format!("\0{}\0{}", username, config.password.as_deref().unwrap_or_default());
+        command(stream, &format!("AUTH PLAIN {}", BASE64.encode(credentials.as_bytes())), 235)?;
+    }
+    command(stream, &format!("MAIL FROM:<{}>", config.from), 250)?;
+    for to in &config.to {
+        command(stream, &format!("RCPT TO:<{}>", to), 250)?;
+    }
+    command(stream, "DATA", 354)?;
+    command(stream, &format!("{}\r\n.", message), 250)?;
+    let _ = command(stream, "QUIT", 221);
+    Ok(())
+}
+
+fn command<S: Read + Write>(stream: &mut BufReader<S>, line: &str, expect: u16) -> Result<(), anyhow::Error> {
+    stream.get_mut().write_all(format!("{}\r\n", line).as_bytes())?;
+    stream.get_mut().flush()?;
+    reply(stream, expect).map_err(|e| {
+        // the password is in there
+        let sent = if line.starts_with("AUTH") { "AUTH" } else { line.lines().next().unwrap_or_default() };
+        anyhow::anyhow!("{} -> {}", sent, e)
+    })
+}
+
+// a (possibly multi-line) reply, which has to have the expected code
+fn reply<S: Read>(stream: &mut BufReader<S>, expect: u16) -> Result<(), anyhow::Error> {
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 {
+            anyhow::bail!("connection closed");
+        }
+        let code: u16 = line.get(..3).and_then(|c| c.parse().ok()).ok_or_else(|| anyhow::anyhow!("bad reply"))?;
+        if line.as_bytes().get(3) == Some(&b'-') {
+            continue;
+        }
+        if code != expect {
+            anyhow::bail!("{}", line.trim_end());
+        }
+        return Ok(());
+    }
+}
+
+// headers and body, CRLF line ends and dot-stuffed, without the final "."
+fn message(config: &EmailConfig, subject: &str, body: &str) -> String {
+    let subject = if subject.is_ascii() {
+        subject.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", BASE64.encode(subject.as_bytes()))
+    };
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        config.from,
+        config.to.join(", "),
+        subject,
+        Local::now().to_rfc2822()
+    );
+    for line in body.lines() {
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.truncate(message.trim_end_matches("\r\n").len());
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_templated_and_stuffed() {
+        let config = EmailConfig {
+            from: "shhh@example.com".into(),
+            to: vec!["a@example.com".into(), "b@example.com".into()],
+            ..EmailConfig::default()
+        };
+        let subject = fill(&config.subject, "den", "2 events", "");
+        assert_eq!(subject, "shhh on den: 2 events");
+        let body = fill("{events}\n.\n", "den", "", "21:03 Cut in force.\n21:05 Lifted.");
+        let text = message(&config, "Lärm", &body);
+        assert!(text.starts_with("From: shhh@example.com\r\nTo: a@example.com, b@example.com\r\n"));
+        assert!(text.contains("Subject: =?UTF-8?B?TMOkcm0=?=\r\n"));
+        assert!(text.ends_with("\r\n\r\n21:03 Cut in force.\r\n21:05 Lifted.\r\n.."));
+    }
+}
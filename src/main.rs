@@ -1,187 +1,259 @@
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::process::Command;
-use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
-use std::time::{Duration, Instant};
+use clap::{Parser, Subcommand};
+use tracing::error;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::ffi::OsString;
+use std::process::ExitCode;
+use std::time::Duration;
 
-const IFACE_NAME: &str = "Wi-Fi"; // set exact adapter name (netsh interface show interface)
-const SAMPLE_WINDOW_MS: u64 = 500; // window duration for RMS
+#[cfg(windows)]
+mod acl;
+mod action;
+mod api;
+mod audio;
+mod bus;
+mod calibrate;
+mod calls;
+mod chart;
+mod clips;
+mod config;
+#[cfg(target_os = "linux")]
+mod daemon;
+mod display;
+mod enforcer;
+mod error;
+#[cfg(windows)]
+mod eventlog;
+mod filter;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod guard;
+mod history;
+mod hosts;
+mod i18n;
+mod iface;
+mod influx;
+mod ipc;
+mod logging;
+mod mail;
+mod measure;
+mod meter;
+mod monitor;
+mod net;
+mod notify;
+mod obs;
+#[cfg(feature = "overlay")]
+mod overlay;
+mod plugin;
+mod policy;
+mod power;
+mod privilege;
+mod profile;
+mod reward;
+mod script;
+#[cfg(windows)]
+mod service;
+mod session;
+mod shaping;
+mod simd;
+mod spectrogram;
+mod speech;
+mod state;
+mod update;
+mod voice;
+mod volume;
+mod watchdog;
 
-fn set_iface(enabled: bool) {
-    let admin = if enabled { "ENABLED" } else { "DISABLED" };
-    let cmd = format!("netsh interface set interface \"{}\" admin={}", IFACE_NAME, admin);
-    // run via cmd /C so quoting works
-    let _ = Command::new("cmd")
-        .args(&["/C", &cmd])
-        .spawn()
-        .and_then(|mut child| child.wait());
-}
+use iface::{set_iface, System};
 
-// --- calibration ---
-fn calibrate(rx: &mpsc::Receiver<f32>, samples_per_window: usize) -> f32 {
-    // collect ~3s of samples to compute ambient dB
-    let mut buf = Vec::new();
-    let target_samples = samples_per_window * 6; // 6 windows = ~3s if window=500ms
-    while buf.len() < target_samples {
-        if let Ok(s) = rx.recv_timeout(Duration::from_millis(200)) { buf.push(s); }
-    }
-    let sum_sq: f64 = buf.iter().map(|&s| (s as f64)*(s as f64)).sum();
-    let rms = ((sum_sq / buf.len() as f64).sqrt()) as f32;
-    rms_to_db(rms)
-}
+#[derive(Parser)]
+#[command(name = "shhh", version, about = "Cuts the network when the microphone gets too loud")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
 
-fn rms_to_db(rms: f32) -> f32 {
-    if rms <= 1e-12 { return -999.0; }
-    20.0 * rms.log10()
-}
+    // `shhh [OPTIONS]` is shorthand for `shhh run [OPTIONS]`
+    #[command(flatten)]
+    run: monitor::RunArgs,
 
-fn main() -> Result<(), anyhow::Error> {
-    // small helper to print and ensure interface restored on exit
-    ctrlc::set_handler(|| {
-        println!("\nExiting — re-enabling interface.");
-        set_iface(true);
-        std::process::exit(0);
-    }).ok();
+    /// Relaunch with administrator/root rights (UAC prompt or sudo) if needed
+    #[arg(long, global = true)]
+    elevate: bool,
 
-    // CPAL setup
-    let host = cpal::default_host();
-    let device = host.default_input_device().expect("No input device available");
-    let config = device.default_input_config().expect("No default input config");
-    println!("Using input device: {}", device.name()?);
-    println!("Input config: {:?}", config);
+    /// How much to log: error, warn, info, debug (adds every window's level) or trace
+    #[arg(long, global = true, value_name = "LEVEL", default_value_t = tracing::Level::INFO)]
+    verbosity: tracing::Level,
+}
 
-    // channel: callback will send f32 samples to aggregator
-    let (tx, rx) = mpsc::channel::<f32>();
-    let samples_per_window = (config.sample_rate().0 as u64 * SAMPLE_WINDOW_MS / 1000) as usize;
+#[derive(Subcommand)]
+enum Commands {
+    /// Calibrate and start monitoring (the default)
+    Run(monitor::RunArgs),
+    /// Measure ambient noise and print the resulting thresholds (--wizard to walk through it and save them)
+    Calibrate(calibrate::CalibrateArgs),
+    /// Measure the level once and exit, for scripts (exits with 8 when over --max-db)
+    Measure(measure::MeasureArgs),
+    /// Show what the running monitor is doing
+    Status {
+        /// Print it as JSON, for scripts
+        #[arg(long)]
+        json: bool,
+    },
+    /// Draw the running monitor's recent levels, thresholds and cuts as a PNG
+    Chart(chart::ChartArgs),
+    /// Re-enable the network interface (e.g. after a crash)
+    Enable {
+        /// Interface to enable; repeat for several (default: as for `run`)
+        #[arg(long, value_name = "NAME")]
+        interface: Vec<String>,
+    },
+    /// Disable the network interface
+    Disable {
+        /// Interface to disable; repeat for several (default: as for `run`)
+        #[arg(long, value_name = "NAME")]
+        interface: Vec<String>,
+    },
+    /// List audio input devices
+    Devices,
+    /// Show a scrolling spectrogram of the microphone in the terminal
+    Spectrogram(spectrogram::SpectrogramArgs),
+    /// Record the voice `[voice]` listens for and save its voiceprint
+    Enroll(voice::EnrollArgs),
+    /// Replace this binary with the latest release, after checking its signature
+    SelfUpdate(update::UpdateArgs),
+    /// Enforce on levels streamed from remote sensors instead of a local mic
+    Server(net::ServerArgs),
+    /// Stream this machine's microphone levels to a `shhh server`
+    Sensor(net::SensorArgs),
+    /// Restore the network if the monitor feeding our stdin stops (spawned by `run`)
+    #[command(hide = true)]
+    Watchdog {
+        #[arg(long, default_value_t = watchdog::DEFAULT_TIMEOUT_SECS)]
+        timeout_secs: u64,
+        // relaunch the monitor with these if it dies
+        #[arg(last = true)]
+        monitor_args: Vec<OsString>,
+    },
+    /// Show the live level as a small always-on-top bar (run in the user's session)
+    #[cfg(feature = "overlay")]
+    Overlay(overlay::OverlayArgs),
+    /// Send a control request to the running monitor
+    Ctl {
+        #[command(subcommand)]
+        request: ipc::Request,
+    },
+    /// Manage the Windows service (install, uninstall, run)
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        action: service::ServiceAction,
+    },
+    /// Install a systemd unit that runs shhh at boot
+    #[cfg(target_os = "linux")]
+    InstallService {
+        /// Print the unit to stdout instead of installing it
+        #[arg(long)]
+        print: bool,
+        /// Options passed to `shhh run` by the unit (after `--`)
+        #[arg(last = true)]
+        run_args: Vec<String>,
+    },
+}
 
-    // build input stream depending on sample format
-    let tx_arc = Arc::new(Mutex::new(tx));
-    match config.sample_format() {
-        cpal::SampleFormat::F32 => {
-            let stream = device.build_input_stream(
-                &config.into(),
-                move |data: &[f32], _| {
-                    if let Ok(tx) = tx_arc.lock() {
-                        for &s in data { let _ = tx.send(s); }
-                    }
-                },
-                move |err| eprintln!("Stream error: {}", err)
-            )?;
-            stream.play()?;
-            run_loop(rx, samples_per_window)?;
-        }
-        cpal::SampleFormat::I16 => {
-            let stream = device.build_input_stream(
-                &config.into(),
-                move |data: &[i16], _| {
-                    if let Ok(tx) = tx_arc.lock() {
-                        for &s in data { let _ = tx.send(s as f32 / 32768.0); }
-                    }
-                },
-                move |err| eprintln!("Stream error: {}", err)
-            )?;
-            stream.play()?;
-            run_loop(rx, samples_per_window)?;
-        }
-        cpal::SampleFormat::U16 => {
-            let stream = device.build_input_stream(
-                &config.into(),
-                move |data: &[u16], _| {
-                    if let Ok(tx) = tx_arc.lock() {
-                        for &s in data {
-                            // convert unsigned 0..65535 to -1.0..1.0
-                            let f = (s as f32 / 65535.0) * 2.0 - 1.0;
-                            let _ = tx.send(f);
-                        }
-                    }
-                },
-                move |err| eprintln!("Stream error: {}", err)
-            )?;
-            stream.play()?;
-            run_loop(rx, samples_per_window)?;
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // what `measure` prints is its output, so keep the log out of it
+    let verbosity = match cli.command {
+        Some(Commands::Measure(_)) => cli.verbosity.min(tracing::Level::WARN),
+        _ => cli.verbosity,
+    };
+    logging::init(verbosity);
+    #[cfg(windows)]
+    update::clean_up();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("error: {:#}", e);
+            ExitCode::from(error::exit_code(&e))
         }
     }
-
-    Ok(())
 }
 
-fn run_loop(rx: mpsc::Receiver<f32>, samples_per_window: usize) -> Result<(), anyhow::Error> {
-    let mut buffer = Vec::with_capacity(samples_per_window);
-    let mut last_state: Option<String> = None;
-    let mut last_sample_time = Instant::now();
-    let mut iface_disabled = false;
-
-    let ambient_db = calibrate(&rx, samples_per_window);
-    let min_db = ambient_db + 15.0; // soft threshold
-    let max_db = ambient_db + 45.0; // cut threshold
-    println!("Ambient {:.1} dBFS -> min {:.1}, max {:.1}", ambient_db, min_db, max_db);
-
-    loop {
-        let start = Instant::now();
-        // collect window
-        while buffer.len() < samples_per_window {
-            match rx.recv_timeout(Duration::from_millis(100)) {
-                Ok(s) => {
-                    buffer.push(s);
-                    last_sample_time = Instant::now();
-                }
-                Err(mpsc::RecvTimeoutError::Timeout) => {
-                    if start.elapsed() > Duration::from_millis(SAMPLE_WINDOW_MS + 200) {
-                        break;
-                    }
-                }
-                Err(_) => break,
+fn run(cli: Cli) -> Result<(), anyhow::Error> {
+    match cli.command.unwrap_or(Commands::Run(cli.run)) {
+        Commands::Run(args) => {
+            if action::needs_elevation(&args)? {
+                privilege::require(cli.elevate)?;
             }
+            // fork before any threads exist (ctrlc and cpal both spawn some)
+            #[cfg(target_os = "linux")]
+            if args.daemon {
+                daemon::daemonize()?;
+            }
+            let action = action::build(&args)?;
+            guard::install(action.clone(), args.protect);
+            monitor::run_monitor(Arc::new(AtomicBool::new(false)), &args, action)
         }
-
-        // ---- inactivity watchdog ----
-        if last_sample_time.elapsed() > Duration::from_secs(3) {
-            if iface_disabled {
-                set_iface(true);
-                println!("No audio for 3s — restoring interface.");
-                iface_disabled = false;
+        Commands::Server(args) => {
+            if action::needs_elevation(&args.run)? {
+                privilege::require(cli.elevate)?;
+            }
+            #[cfg(target_os = "linux")]
+            if args.run.daemon {
+                daemon::daemonize()?;
             }
-            buffer.clear();
-            thread::sleep(Duration::from_millis(100));
-            continue;
+            let action = action::build(&args.run)?;
+            guard::install(action.clone(), args.run.protect);
+            net::run_server(Arc::new(AtomicBool::new(false)), &args, action)
         }
-
-        // compute RMS
-        let rms = if buffer.is_empty() {
-            0.0
-        } else {
-            let sum_sq: f64 = buffer.iter().map(|&s| (s as f64) * (s as f64)).sum();
-            ((sum_sq / (buffer.len() as f64)).sqrt()) as f32
-        };
-        buffer.clear();
-
-        let db = rms_to_db(rms);
-        println!("Current volume: dB={:.1}", db);
-
-        let pct = if db <= min_db {
-            100
-        } else if db >= max_db {
-            0
-        } else {
-            let v = 1.0 - (db - min_db) / (max_db - min_db);
-            (100.0 * v).round() as i32
-        };
-
-        let state = if pct == 0 {
-            set_iface(false);
-            iface_disabled = true;
-            "CUT".to_string()
-        } else {
-            set_iface(true);
-            iface_disabled = false;
-            format!("OK {}%", pct)
-        };
-
-        if Some(state.clone()) != last_state {
-            println!("dB={:.1} -> {}", db, state);
-            last_state = Some(state);
+        Commands::Sensor(args) => net::run_sensor(&args),
+        Commands::Watchdog { timeout_secs, monitor_args } => {
+            watchdog::run(Duration::from_secs(timeout_secs), monitor_args)
+        }
+        Commands::Calibrate(args) => calibrate::run(&args),
+        Commands::Measure(args) => match measure::run(&args)? {
+            0 => Ok(()),
+            code => std::process::exit(code.into()),
+        },
+        Commands::Status { json } => ipc::client(ipc::Request::Status { json }),
+        Commands::Chart(args) => chart::run(&args),
+        Commands::Enable { interface } => {
+            privilege::require(cli.elevate)?;
+            state::reconcile()?;
+            let ifaces = action::interfaces(&interface, &config::load(None)?)?;
+            set_iface(&System, &ifaces, true)?;
+            println!("Enabled {}.", ifaces.join(", "));
+            Ok(())
+        }
+        Commands::Disable { interface } => {
+            privilege::require(cli.elevate)?;
+            let ifaces = action::interfaces(&interface, &config::load(None)?)?;
+            set_iface(&System, &ifaces, false)?;
+            println!("Disabled {}.", ifaces.join(", "));
+            Ok(())
+        }
+        Commands::Devices => audio::list_devices(),
+        Commands::Spectrogram(args) => spectrogram::run(&args),
+        Commands::Enroll(args) => voice::enroll(&args),
+        Commands::SelfUpdate(args) => update::run(&args, cli.elevate),
+        Commands::Ctl { request } => ipc::client(request),
+        #[cfg(feature = "overlay")]
+        Commands::Overlay(args) => overlay::run(&args),
+        #[cfg(windows)]
+        Commands::Service { action } => {
+            // the service itself runs as LocalSystem
+            if !matches!(action, service::ServiceAction::Run(_)) {
+                privilege::require(cli.elevate)?;
+            }
+            service::handle(action)
+        }
+        #[cfg(target_os = "linux")]
+        Commands::InstallService { print, run_args } => {
+            if !print {
+                privilege::require(cli.elevate)?;
+            }
+            daemon::install_service(print, &run_args)
         }
-
-        thread::sleep(Duration::from_millis(50));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+// `shhh measure`: one measurement and out, for scripts and other tools that
+// want a noise probe without the monitor. It listens for a few seconds (or
+// reads a WAV file), prints the RMS level and the peak, and exits with
+// LOUD_EXIT if the level was over a threshold, so `shhh measure --max-db -30
+// || ...` works like any other test.
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::audio::{self, rms_to_db};
+use crate::config;
+use crate::policy;
+use crate::simd;
+
+// exit code when the level is over the threshold; 1 to 7 are errors
+pub const LOUD_EXIT: u8 = 8;
+
+#[derive(clap::Args, Debug)]
+pub struct MeasureArgs {
+    /// How long to listen
+    #[arg(long, value_name = "SECS", default_value_t = 5.0)]
+    pub seconds: f32,
+    /// Exit with 8 if the level is over this many dBFS (default: the config's fixed `cut_db`, if any)
+    #[arg(long, value_name = "DB", allow_hyphen_values = true)]
+    pub max_db: Option<f32>,
+    /// Print the result as JSON
+    #[arg(long)]
+    pub json: bool,
+    /// Microphone to listen to, by (part of) its name in `shhh devices` (default: as for `run`)
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+    /// Measure a WAV file (up to --seconds of it) instead of the microphone
+    #[arg(long, value_name = "WAV")]
+    pub input_file: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Measurement {
+    // linear, 0-1 of full scale
+    rms: f32,
+    dbfs: f32,
+    peak: f32,
+}
+
+// the exit code
+pub fn run(args: &MeasureArgs) -> Result<u8, anyhow::Error> {
+    if !(args.seconds > 0.0 && args.seconds.is_finite()) {
+        anyhow::bail!("--seconds must be a positive number");
+    }
+    let config = config::load(None)?;
+    let input = match &args.input_file {
+        Some(path) => audio::open_file(path, 100.0)?,
+        None => audio::open_input(args.device.as_deref().or(config.input_device.as_deref()))?,
+    };
+    let wanted = (input.sample_rate as f32 * input.channels as f32 * args.seconds) as usize;
+    let samples: Vec<f32> = input.rx.iter().take(wanted).collect();
+    if samples.is_empty() {
+        anyhow::bail!("no audio came in");
+    }
+    let measurement = measure(&samples);
+    if args.json {
+        println!("{}", serde_json::to_string(&measurement)?);
+    } else {
+        println!("{:.1} dBFS (RMS {:.4}, peak {:.4})", measurement.dbfs, measurement.rms, measurement.peak);
+    }
+    let max_db = args.max_db.or_else(|| config.soft_db.zip(config.cut_db).map(|(soft, cut)| {
+        policy::to_dbfs(&config, soft, cut).1
+    }));
+    Ok(if max_db.is_some_and(|max| measurement.dbfs > max) { LOUD_EXIT } else { 0 })
+}
+
+fn measure(samples: &[f32]) -> Measurement {
+    let rms = simd::rms(samples);
+    Measurement { rms, dbfs: rms_to_db(rms), peak: simd::peak(samples) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_square_wave_measures_at_its_amplitude() {
+        let samples: Vec<f32> = (0..1000).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }).collect();
+        let m = measure(&samples);
+        assert_eq!((m.rms, m.peak), (0.5, 0.5));
+        assert!((m.dbfs + 6.02).abs() < 0.01);
+        assert!(serde_json::to_string(&m).unwrap().starts_with(r#"{"rms":0.5,"dbfs":-6.02"#));
+    }
+}
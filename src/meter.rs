@@ -0,0 +1,622 @@
+// Turns raw samples into one level (dBFS) per window. The monitor only ever
+// sees levels, so a local microphone and a remote sensor look the same to it.
+//
+// Windows can overlap: a 500ms window every 125ms still averages over half a
+// second, but a bang that straddles two back-to-back windows (and would be
+// halved in both) lands whole in one of them, and a level comes four times as
+// often.
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::audio::{rms_to_db, Input, SAMPLE_WINDOW_MS};
+use crate::clips::Recorder;
+use crate::config::{ClipConfig, PercentileConfig, SpeechConfig, VoiceConfig};
+use crate::filter::LowPass;
+use crate::policy::DIGITAL_SILENCE_DB;
+use crate::simd;
+use crate::speech::{Heard, Listener};
+use crate::voice::Voice;
+
+pub trait LevelSource {
+    // the next window's level, or None if no audio arrived for a whole window
+    fn next_level(&mut self) -> Option<f32>;
+
+    // true once no level will ever come again (a replayed file ran out)
+    fn exhausted(&self) -> bool {
+        false
+    }
+
+    // a cut just happened: keep the audio around it, if this source has any
+    // and clips are on
+    fn save_clip(&mut self) {}
+
+    // keywords (or the safe word) heard since the last call
+    fn heard(&mut self) -> Vec<Heard> {
+        Vec::new()
+    }
+
+    // share of the last window's samples at full scale (0-1); sources
+    // without samples (remote sensors) never clip
+    fn clipped(&self) -> f32 {
+        0.0
+    }
+
+    // whether the last window's loudness was a short impulse (a slammed
+    // door, something dropped) rather than noise that keeps up
+    fn impulsive(&self) -> bool {
+        false
+    }
+
+    // whether the last window sounded like someone other than the enrolled
+    // speaker (see voice.rs); never without `[voice]`
+    fn someone_else(&self) -> bool {
+        false
+    }
+
+    // how often a level comes
+    fn hop(&self) -> Duration {
+        Duration::from_millis(SAMPLE_WINDOW_MS)
+    }
+
+    // --- calibration ---
+    // `tick` runs after every window, for a watchdog
+    fn calibrate(&mut self, tick: &mut dyn FnMut()) -> Result<f32, Rejected> {
+        // the power of ~3s of windows is the ambient dB
+        let count = (CALIBRATION_MS / self.hop().as_millis().max(1) as u64).max(1) as usize;
+        calibrate_with(self, count, |_| tick())
+    }
+
+    // whether levels are relative to a calibration of their own (remote
+    // sensors), so the ambient is always 0 and not worth remembering
+    fn relative(&self) -> bool {
+        false
+    }
+}
+
+// how long a calibration run listens for
+pub const CALIBRATION_MS: u64 = 3000;
+// windows that spread further (in dB, one standard deviation) than a quiet
+// room's steady hum did not hear a quiet room
+const CALIBRATION_SPREAD_DB: f32 = 4.0;
+
+// why a calibration run can't be trusted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rejected {
+    // next to nothing, or nothing at all
+    Silent(f32),
+    // the level kept moving, by this many dB
+    Unsteady(f32),
+    Clipped,
+    // remote sensors: this many rooms hadn't finished theirs in time (none
+    // at all connected when 0)
+    Sensors(usize),
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejected::Silent(db) if *db <= rms_to_db(0.0) => {
+                f.write_str("no sound at all came in while calibrating (a muted or unplugged microphone)")
+            }
+            Rejected::Silent(db) => {
+                write!(f, "the room read {:.1} dBFS while calibrating, quieter than any real room (a muted mic)", db)
+            }
+            Rejected::Unsteady(spread) => write!(
+                f,
+                "the level moved by ±{:.1} dB while calibrating, so it heard more than the room (talking, music)",
+                spread
+            ),
+            Rejected::Clipped => f.write_str(
+                "the microphone clipped while calibrating, so it wasn't quiet or its input gain is far too high; \
+                 turn the gain down and try again",
+            ),
+            Rejected::Sensors(0) => f.write_str("no sensor connected while calibrating"),
+            Rejected::Sensors(rooms) => write!(f, "{} room(s) didn't finish calibrating in time", rooms),
+        }
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+// `count` windows of calibration, each one handed to `each` as it comes (the
+// wizard shows them, the monitor feeds its watchdog), and the ambient level
+// they add up to. As many windows in a row without audio (a microphone the
+// OS won't let us have, a suspended device) end it as silent.
+pub fn calibrate_with(
+    source: &mut (impl LevelSource + ?Sized),
+    count: usize,
+    mut each: impl FnMut(Option<f32>),
+) -> Result<f32, Rejected> {
+    let mut levels = Vec::new();
+    let mut clipped = false;
+    let mut missing = 0;
+    while levels.len() < count && !source.exhausted() {
+        let level = source.next_level();
+        each(level);
+        match level {
+            Some(db) => {
+                missing = 0;
+                levels.push(db);
+                clipped |= source.clipped() > 0.0;
+            }
+            None if missing + 1 == count => return Err(Rejected::Silent(rms_to_db(0.0))),
+            None => missing += 1,
+        }
+    }
+    check_calibration(&levels, clipped)
+}
+
+// The ambient level of a calibration run, unless it went wrong: digital
+// silence or a level below any microphone's noise floor (muted), levels all
+// over the place (someone talking), or full scale, which a quiet room never
+// reaches.
+pub fn check_calibration(levels: &[f32], clipped: bool) -> Result<f32, Rejected> {
+    if clipped {
+        return Err(Rejected::Clipped);
+    }
+    let ambient = power_mean(levels);
+    if ambient <= DIGITAL_SILENCE_DB {
+        return Err(Rejected::Silent(ambient));
+    }
+    let mean = levels.iter().sum::<f32>() / levels.len() as f32;
+    let spread = (levels.iter().map(|db| (db - mean).powi(2)).sum::<f32>() / levels.len() as f32).sqrt();
+    if spread > CALIBRATION_SPREAD_DB {
+        return Err(Rejected::Unsteady(spread));
+    }
+    Ok(ambient)
+}
+
+// level of the combined power of several equal-length windows
+pub fn power_mean(dbs: &[f32]) -> f32 {
+    if dbs.is_empty() {
+        return rms_to_db(0.0);
+    }
+    let mean = dbs.iter().map(|&db| 10f64.powf(db as f64 / 10.0)).sum::<f64>() / dbs.len() as f64;
+    rms_to_db(mean.sqrt() as f32)
+}
+
+// a sample this close to ±1.0 is at full scale
+const FULL_SCALE: f32 = 0.999;
+
+// The envelope is the level of every 10ms block. An impulse rises out of
+// what came before by at least IMPULSE_ATTACK_DB and is back more than
+// IMPULSE_RUN_DB below its peak within IMPULSE_MAX_BLOCKS; a shout or music
+// stays near its peak for longer. A bang at the very end of a window can't
+// be told from the start of a shout yet, and passes for an impulse until the
+// next window says otherwise.
+const ENVELOPE_BLOCK_MS: u64 = 10;
+const IMPULSE_ATTACK_DB: f32 = 20.0;
+const IMPULSE_RUN_DB: f32 = 10.0;
+const IMPULSE_MAX_BLOCKS: usize = 15;
+// blocks before the run that it has to rise out of
+const IMPULSE_LEAD_BLOCKS: usize = 5;
+
+struct Envelope {
+    // block length in samples (all channels), and the block being summed
+    block: usize,
+    sum: f64,
+    len: usize,
+    levels: VecDeque<f32>,
+    // blocks kept: a window's worth and what its impulses are judged against
+    keep: usize,
+}
+
+impl Envelope {
+    fn new(block: usize, window: usize) -> Envelope {
+        let keep = window / block + IMPULSE_LEAD_BLOCKS + IMPULSE_MAX_BLOCKS;
+        Envelope { block, sum: 0.0, len: 0, levels: VecDeque::with_capacity(keep), keep }
+    }
+
+    fn feed(&mut self, mut samples: &[f32]) {
+        while !samples.is_empty() {
+            let (part, rest) = samples.split_at((self.block - self.len).min(samples.len()));
+            self.sum += simd::sum_squares(part);
+            self.len += part.len();
+            samples = rest;
+            if self.len == self.block {
+                if self.levels.len() == self.keep {
+                    self.levels.pop_front();
+                }
+                self.levels.push_back(rms_to_db((self.sum / self.len as f64).sqrt() as f32));
+                (self.sum, self.len) = (0.0, 0);
+            }
+        }
+    }
+
+    // the loudest block of the last `window` blocks, and the run of blocks
+    // around it within IMPULSE_RUN_DB of it
+    fn impulsive(&mut self, window: usize) -> bool {
+        impulse(self.levels.make_contiguous(), window)
+    }
+}
+
+fn impulse(levels: &[f32], window: usize) -> bool {
+    let start = levels.len().saturating_sub(window);
+    let Some((peak_at, &peak)) = levels[start..].iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)) else {
+        return false;
+    };
+    let peak_at = start + peak_at;
+    let near = |db: &f32| *db >= peak - IMPULSE_RUN_DB;
+    let first = peak_at - levels[..peak_at].iter().rev().take_while(|db| near(db)).count();
+    let last = peak_at + levels[peak_at + 1..].iter().take_while(|db| near(db)).count();
+    // nothing before it to have risen out of: it may have been going on
+    if first < IMPULSE_LEAD_BLOCKS {
+        return false;
+    }
+    let before = levels[first - IMPULSE_LEAD_BLOCKS..first].iter().copied().fold(f32::MIN, f32::max);
+    last - first < IMPULSE_MAX_BLOCKS && peak - before >= IMPULSE_ATTACK_DB
+}
+
+pub struct Meter {
+    input: Input,
+    // window and hop in samples (all channels), and the hop as time
+    window: usize,
+    hop: usize,
+    hop_time: Duration,
+    buffer: Vec<f32>,
+    // the same samples through the low-pass filter, when there is one; what
+    // the level is measured on (clips, speech and clipping get the raw ones)
+    filter: Option<LowPass>,
+    filtered: Vec<f32>,
+    // samples at the end of `buffer` not yet passed on to clips and speech
+    fresh: usize,
+    ended: bool,
+    clipped: f32,
+    envelope: Envelope,
+    impulsive: bool,
+    clips: Option<Recorder>,
+    speech: Option<Listener>,
+    voice: Option<Voice>,
+    someone_else: bool,
+}
+
+impl Meter {
+    pub fn new(input: Input) -> Meter {
+        let (window, hop_time) = (input.samples_per_window, Duration::from_millis(SAMPLE_WINDOW_MS));
+        let buffer = Vec::with_capacity(window);
+        let envelope = Envelope::new(block_len(&input), window);
+        Meter {
+            input,
+            window,
+            hop: window,
+            hop_time,
+            buffer,
+            filter: None,
+            filtered: Vec::new(),
+            fresh: 0,
+            ended: false,
+            clipped: 0.0,
+            envelope,
+            impulsive: false,
+            clips: None,
+            speech: None,
+            voice: None,
+            someone_else: false,
+        }
+    }
+
+    // `window_ms` and `hop_ms` from the config
+    pub fn with_window(mut self, window_ms: Option<u64>, hop_ms: Option<u64>) -> Meter {
+        let window_ms = window_ms.unwrap_or(SAMPLE_WINDOW_MS);
+        let hop_ms = hop_ms.unwrap_or(window_ms).min(window_ms);
+        let per_ms = self.input.sample_rate as f64 * self.input.channels as f64 / 1000.0;
+        // whole frames, so every window starts on the same channel
+        let frames = |ms: u64| ((ms as f64 * per_ms) as usize / self.input.channels as usize).max(1);
+        self.window = frames(window_ms) * self.input.channels as usize;
+        self.hop = frames(hop_ms) * self.input.channels as usize;
+        self.hop_time = Duration::from_millis(hop_ms);
+        self.envelope = Envelope::new(block_len(&self.input), self.window);
+        self
+    }
+
+    // `lowpass_hz` from the config
+    pub fn with_lowpass(mut self, cutoff_hz: Option<f32>) -> Meter {
+        let Some(hz) = cutoff_hz else { return self };
+        self.filter = LowPass::new(hz, self.input.sample_rate, self.input.channels);
+        if self.filter.is_none() {
+            warn!("lowpass_hz {} is above what {} Hz audio holds; not filtering.", hz, self.input.sample_rate);
+        }
+        self
+    }
+
+    pub fn with_clips(mut self, config: &ClipConfig) -> Meter {
+        self.clips = Some(Recorder::new(config, self.input.sample_rate, self.input.channels));
+        self
+    }
+
+    pub fn with_speech(mut self, config: &SpeechConfig) -> Result<Meter, anyhow::Error> {
+        self.speech = Some(Listener::start(config, self.input.sample_rate, self.input.channels)?);
+        Ok(self)
+    }
+
+    pub fn with_voice(mut self, config: &VoiceConfig) -> Result<Meter, anyhow::Error> {
+        self.voice = Some(Voice::new(config, self.input.sample_rate, self.input.channels)?);
+        Ok(self)
+    }
+}
+
+// samples (all channels) in an envelope block, in whole frames
+fn block_len(input: &Input) -> usize {
+    let frames = (input.sample_rate as u64 * ENVELOPE_BLOCK_MS / 1000).max(1) as usize;
+    frames * input.channels as usize
+}
+
+impl LevelSource for Meter {
+    fn next_level(&mut self) -> Option<f32> {
+        // the samples still missing should take about this long to arrive
+        let missing = self.window.saturating_sub(self.buffer.len());
+        let expected = self.hop_time.mul_f64(missing as f64 / self.hop as f64);
+        let deadline = Instant::now() + expected + Duration::from_millis(200);
+        // collect window
+        while self.buffer.len() < self.window {
+            match self.input.rx.recv_timeout(Duration::from_millis(100)) {
+                Ok(s) => {
+                    self.buffer.push(s);
+                    self.fresh += 1;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if Instant::now() > deadline {
+                        break;
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    self.ended = true;
+                    break;
+                }
+            }
+        }
+        if self.fresh == 0 {
+            // what's left of the last window is stale by the time audio is back
+            self.buffer.clear();
+            self.filtered.clear();
+            return None;
+        }
+
+        if let Some(filter) = &mut self.filter {
+            filter.process(&self.buffer[self.buffer.len() - self.fresh..], &mut self.filtered);
+        }
+
+        // compute RMS
+        let measured = if self.filter.is_some() { &self.filtered } else { &self.buffer };
+        let rms = simd::rms(measured);
+        self.envelope.feed(&measured[measured.len() - self.fresh..]);
+        self.clipped = simd::count_over(&self.buffer, FULL_SCALE) as f32 / self.buffer.len() as f32;
+        let fresh = &self.buffer[self.buffer.len() - self.fresh..];
+        self.impulsive = self.envelope.impulsive(self.window / self.envelope.block);
+        self.someone_else = self.voice.as_ref().is_some_and(|voice| voice.someone_else(&self.buffer));
+        if let Some(clips) = &mut self.clips {
+            clips.push(fresh);
+        }
+        if let Some(speech) = &self.speech {
+            speech.feed(fresh);
+        }
+        self.fresh = 0;
+        // keep the overlap for the next window; a short one (audio stopped)
+        // isn't carried over
+        if self.buffer.len() == self.window {
+            self.buffer.drain(..self.hop);
+            self.filtered.drain(..self.filtered.len().min(self.hop));
+        } else {
+            self.buffer.clear();
+            self.filtered.clear();
+        }
+        Some(rms_to_db(rms))
+    }
+
+    fn exhausted(&self) -> bool {
+        self.ended && self.fresh == 0
+    }
+
+    fn save_clip(&mut self) {
+        if let Some(clips) = &mut self.clips {
+            clips.trigger();
+        }
+    }
+
+    fn heard(&mut self) -> Vec<Heard> {
+        self.speech.as_ref().map(Listener::heard).unwrap_or_default()
+    }
+
+    fn clipped(&self) -> f32 {
+        self.clipped
+    }
+
+    fn impulsive(&self) -> bool {
+        self.impulsive
+    }
+
+    fn someone_else(&self) -> bool {
+        self.someone_else
+    }
+
+    fn hop(&self) -> Duration {
+        self.hop_time
+    }
+}
+
+// Levels as a noise ordinance measures them: the level exceeded some share of
+// the last stretch of time (L10 over 10s by default) instead of each window's.
+// A single clap barely moves it; noise that keeps up does. Calibration still
+// uses the raw windows, so the thresholds are over the same ambient.
+pub struct Percentile<'a> {
+    inner: &'a mut dyn LevelSource,
+    // as a quantile of the levels: L10 is the 0.9 one
+    quantile: f32,
+    over: Duration,
+    recent: VecDeque<(Instant, f32)>,
+}
+
+impl<'a> Percentile<'a> {
+    pub fn new(inner: &'a mut dyn LevelSource, config: &PercentileConfig) -> Percentile<'a> {
+        let (quantile, over) = (1.0 - config.exceeded_pct / 100.0, Duration::from_secs(config.over_secs));
+        Percentile { inner, quantile, over, recent: VecDeque::new() }
+    }
+
+    // the statistic over the levels up to `db`; until a whole stretch has
+    // gone by, over what there is
+    fn push(&mut self, db: f32, now: Instant) -> f32 {
+        while self.recent.front().is_some_and(|&(at, _)| now.duration_since(at) >= self.over) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((now, db));
+        let mut levels: Vec<f32> = self.recent.iter().map(|&(_, db)| db).collect();
+        levels.sort_by(f32::total_cmp);
+        levels[((levels.len() - 1) as f32 * self.quantile).round() as usize]
+    }
+}
+
+impl LevelSource for Percentile<'_> {
+    fn next_level(&mut self) -> Option<f32> {
+        let db = self.inner.next_level()?;
+        Some(self.push(db, Instant::now()))
+    }
+
+    fn exhausted(&self) -> bool {
+        self.inner.exhausted()
+    }
+
+    fn save_clip(&mut self) {
+        self.inner.save_clip()
+    }
+
+    fn heard(&mut self) -> Vec<Heard> {
+        self.inner.heard()
+    }
+
+    fn clipped(&self) -> f32 {
+        self.inner.clipped()
+    }
+
+    fn impulsive(&self) -> bool {
+        self.inner.impulsive()
+    }
+
+    fn someone_else(&self) -> bool {
+        self.inner.someone_else()
+    }
+
+    fn hop(&self) -> Duration {
+        self.inner.hop()
+    }
+
+    fn calibrate(&mut self, tick: &mut dyn FnMut()) -> Result<f32, Rejected> {
+        self.inner.calibrate(tick)
+    }
+
+    fn relative(&self) -> bool {
+        self.inner.relative()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio;
+
+    // 2s of near silence at 8kHz with a 200ms bang straddling the 1s mark
+    fn bang_file() -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("shhh-meter-test-{}.wav", std::process::id()));
+        let sample_format = hound::SampleFormat::Int;
+        let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format };
+        let mut wav = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..16_000 {
+            let amplitude = if (7_200..8_800).contains(&i) { 16_000 } else { 10 };
+            wav.write_sample(if i % 2 == 0 { amplitude } else { -amplitude } as i16).unwrap();
+        }
+        wav.finalize().unwrap();
+        path
+    }
+
+    struct Unplugged;
+
+    impl LevelSource for Unplugged {
+        fn next_level(&mut self) -> Option<f32> {
+            None
+        }
+    }
+
+    fn levels(meter: &mut Meter) -> Vec<f32> {
+        std::iter::from_fn(|| (!meter.exhausted()).then(|| meter.next_level())).flatten().collect()
+    }
+
+    #[test]
+    fn overlapping_windows_catch_a_bang_at_the_boundary() {
+        let path = bang_file();
+        let mut back_to_back = Meter::new(audio::open_file(&path, 100.0).unwrap());
+        let mut overlapping = Meter::new(audio::open_file(&path, 100.0).unwrap()).with_window(Some(500), Some(125));
+        assert_eq!(overlapping.hop(), Duration::from_millis(125));
+        let (back_to_back, overlapping) = (levels(&mut back_to_back), levels(&mut overlapping));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!((back_to_back.len(), overlapping.len()), (4, 13));
+        let loudest = |levels: &[f32]| levels.iter().copied().fold(f32::MIN, f32::max);
+        // split between two windows the bang reads 3dB quieter in each
+        assert!(loudest(&overlapping) - loudest(&back_to_back) > 2.5);
+    }
+
+    #[test]
+    fn bangs_are_told_from_shouting() {
+        // 10ms blocks: room at -60, then something at -10 for `loud` blocks
+        // that fades over 30ms
+        let envelope = |loud: usize| -> Vec<f32> {
+            let mut levels = vec![-60.0; 20];
+            levels.extend(std::iter::repeat_n(-10.0, loud));
+            levels.extend([-15.0, -25.0, -40.0]);
+            levels.extend(vec![-60.0; 30]);
+            levels
+        };
+        assert!(impulse(&envelope(5), 50));
+        assert!(!impulse(&envelope(40), 50));
+        // shouting that was already going on when the envelope starts
+        assert!(!impulse(&[-10.0; 50], 50));
+        // a bang that rises out of loud music isn't an impulse on its own
+        let mut music = vec![-25.0; 20];
+        music.extend([-10.0, -10.0, -25.0]);
+        music.extend(vec![-25.0; 20]);
+        assert!(!impulse(&music, 40));
+    }
+
+    #[test]
+    fn calibration_rejects_silence_talking_and_clipping() {
+        assert_eq!(check_calibration(&[], false), Err(Rejected::Silent(-999.0)));
+        let muted = check_calibration(&[-999.0, DIGITAL_SILENCE_DB, -95.0], false);
+        assert!(matches!(muted, Err(Rejected::Silent(db)) if db <= DIGITAL_SILENCE_DB), "{:?}", muted);
+        // someone talking over a room at -60
+        let talking = [-60.0, -30.0, -58.0, -32.0, -61.0, -35.0];
+        let unsteady = check_calibration(&talking, false);
+        assert!(matches!(unsteady, Err(Rejected::Unsteady(s)) if s > CALIBRATION_SPREAD_DB), "{:?}", unsteady);
+        assert_eq!(check_calibration(&[-60.0; 6], true), Err(Rejected::Clipped));
+        // a steady room with a little variation is its power mean
+        let room = [-60.0, -61.0, -59.5, -60.5, -60.0, -61.0];
+        assert_eq!(check_calibration(&room, false), Ok(power_mean(&room)));
+    }
+
+    #[test]
+    fn calibration_gives_up_on_a_microphone_that_sends_nothing() {
+        let mut ticks = 0;
+        let calibrated = Unplugged.calibrate(&mut || ticks += 1);
+        assert_eq!(calibrated, Err(Rejected::Silent(-999.0)));
+        // one tick a window, for the watchdog, until it gave up
+        let count = (CALIBRATION_MS / Unplugged.hop().as_millis() as u64) as usize;
+        assert_eq!(ticks, count);
+    }
+
+    #[test]
+    fn l10_ignores_a_clap_but_not_noise_that_keeps_up() {
+        let mut source = Unplugged;
+        let mut l10 = Percentile::new(&mut source, &PercentileConfig::default());
+        let t0 = Instant::now();
+        let at = |ms: u64| t0 + Duration::from_millis(ms);
+        // 10s of room at -50 with one clap at -10
+        let levels: Vec<f32> = (0..20).map(|i| l10.push(if i == 12 { -10.0 } else { -50.0 }, at(i * 500))).collect();
+        assert!(levels.iter().all(|&db| db == -50.0));
+        // then shouting for 2s of every 5
+        let shouting = (20..40).map(|i| l10.push(if i % 10 < 4 { -10.0 } else { -50.0 }, at(i * 500)));
+        assert_eq!(shouting.last(), Some(-10.0));
+        assert_eq!(l10.recent.len(), 20);
+    }
+}
@@ -0,0 +1,739 @@
+// The monitor: calibrate, then meter the microphone window by window and cut
+// the interface when it gets too loud. What happens along the way goes out on
+// the event bus (bus.rs) for the log, notifications and history.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info, warn};
+
+use crate::action::Action;
+use crate::api;
+#[cfg(feature = "grpc")]
+use crate::grpc;
+use crate::audio;
+use crate::bus::{self, Bus, Event};
+use crate::calls;
+use crate::config::{self, EmailConfig, InfluxConfig};
+#[cfg(target_os = "linux")]
+use crate::daemon;
+use crate::enforcer::Enforcer;
+#[cfg(windows)]
+use crate::eventlog;
+use crate::history::{self, Entry, History};
+use crate::i18n::{self, tr};
+use crate::influx::Exporter;
+use crate::ipc;
+use crate::logging;
+use crate::meter::{LevelSource, Meter, Percentile};
+use crate::policy::{self, Decision, Policy, Reason, Rules};
+use crate::profile::{self, Profiles};
+use crate::notify;
+use crate::mail::Mailer;
+use crate::power::Power;
+use crate::reward::Rewards;
+use crate::script::{self, Command, Script};
+use crate::speech::Heard;
+use crate::state;
+use crate::watchdog::{self, Heartbeat};
+
+pub const SOFT_OFFSET_DB: f32 = 15.0; // soft threshold over ambient
+pub const CUT_OFFSET_DB: f32 = 45.0; // cut threshold over ambient
+// calibration runs at startup before giving up (someone may have been
+// talking), and the ambient level to go on with if there's nothing better
+const CALIBRATION_TRIES: usize = 3;
+const FALLBACK_AMBIENT_DB: f32 = -60.0;
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct RunArgs {
+    /// Detach from the terminal and keep running in the background
+    #[cfg(target_os = "linux")]
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Serve the HTTP API on this address (e.g. 127.0.0.1:8787)
+    #[arg(long, value_name = "ADDR")]
+    pub http: Option<SocketAddr>,
+
+    /// Serve the gRPC API on this address (e.g. 127.0.0.1:8789)
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR")]
+    pub grpc: Option<SocketAddr>,
+
+    /// Token required by the HTTP and gRPC APIs
+    #[arg(long, env = "SHHH_API_TOKEN", hide_env_values = true)]
+    pub api_token: Option<String>,
+
+    /// Network interface to control; repeat to cut several together
+    /// (default: `interfaces` from the config, else the default-route one)
+    #[arg(long, value_name = "NAME")]
+    pub interface: Vec<String>,
+
+    /// Microphone to listen to, by (part of) its name in `shhh devices` (default: `input_device`
+    /// from the config, else the system default)
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+
+    /// Replay a WAV file instead of listening to the microphone
+    #[arg(long, value_name = "WAV")]
+    pub input_file: Option<PathBuf>,
+
+    /// Replay speed for --input-file (2 = twice as fast as real time)
+    #[arg(long, value_name = "X", default_value_t = 1.0, requires = "input_file")]
+    pub input_speed: f32,
+
+    /// Make shhh hard to stop: ignore stop signals (use `shhh ctl stop`) and
+    /// restart the monitor and its watchdog if either is killed
+    #[arg(long)]
+    pub protect: bool,
+
+    /// Meter and log as usual but never actually cut anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Don't start the companion process that restores the network if shhh dies
+    #[arg(long)]
+    pub no_watchdog: bool,
+
+    // set when a service manager restarts us, so the watchdog doesn't have to
+    #[arg(skip)]
+    pub supervised: bool,
+
+    /// Soft threshold in dB over ambient, where the meter starts dropping
+    /// (default: `soft_offset_db` from the config, else 15)
+    #[arg(long, value_name = "DB")]
+    pub soft_offset: Option<f32>,
+
+    /// Cut threshold in dB over ambient (default: `cut_offset_db` from the config, else 45)
+    #[arg(long, value_name = "DB")]
+    pub cut_offset: Option<f32>,
+
+    /// Fixed soft threshold in dBFS (dB SPL with `spl_offset_db` in the config);
+    /// with --cut-db, skips calibration and ignores the offsets
+    #[arg(long, value_name = "DB", allow_hyphen_values = true, requires = "cut_db")]
+    pub soft_db: Option<f32>,
+
+    /// Fixed cut threshold, in the same unit as --soft-db
+    #[arg(long, value_name = "DB", allow_hyphen_values = true, requires = "soft_db")]
+    pub cut_db: Option<f32>,
+
+    /// Use this profile from the config regardless of its hours
+    /// (switch later with `shhh ctl profile <name>`)
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// How far below the cut threshold the level has to fall before the network
+    /// comes back (default: `dead_zone_db` from the config, else 3)
+    #[arg(long, value_name = "DB")]
+    pub dead_zone: Option<f32>,
+
+    /// Config file (default: /etc/shhh/shhh.toml or %ProgramData%\shhh\shhh.toml, if present)
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+}
+
+// what the control surfaces (IPC, HTTP) share with the running loop
+#[derive(Clone)]
+pub struct Handle {
+    pub control: mpsc::Sender<ipc::Request>,
+    pub status: Arc<Mutex<ipc::Status>>,
+    pub history: Arc<Mutex<History>>,
+    // the loop's events, e.g. for the WebSocket stream
+    pub bus: Bus,
+}
+
+impl RunArgs {
+    // nobody will see the console: detached, or the Windows service
+    pub fn headless(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        if self.daemon {
+            return true;
+        }
+        cfg!(windows) && self.supervised
+    }
+}
+
+// the config file's rules with the command-line overrides on top, and the
+// profiles that take over from them
+pub fn profiles(args: &RunArgs) -> Result<Profiles, anyhow::Error> {
+    let config = config::load(args.config.as_deref())?;
+    let mut rules = Rules::from_config(&config);
+    rules.soft_offset_db = args.soft_offset.unwrap_or(rules.soft_offset_db);
+    rules.cut_offset_db = args.cut_offset.unwrap_or(rules.cut_offset_db);
+    rules.dead_zone_db = args.dead_zone.unwrap_or(rules.dead_zone_db);
+    if let (Some(soft), Some(cut)) = (args.soft_db, args.cut_db) {
+        rules.absolute = Some(policy::to_dbfs(&config, soft, cut));
+    }
+    let (soft, cut) = rules.absolute.unwrap_or((rules.soft_offset_db, rules.cut_offset_db));
+    config::check_thresholds(soft, cut, rules.dead_zone_db, rules.absolute.is_some())?;
+    let mut profiles = Profiles::new(rules, &config)?;
+    if let Some(name) = &args.profile {
+        profiles.pin(name)?;
+    }
+    Ok(profiles)
+}
+
+// runs until `stop` is set (service stop) or the process is killed (console)
+pub fn run_monitor(stop: Arc<AtomicBool>, args: &RunArgs, action: Arc<dyn Action>) -> Result<(), anyhow::Error> {
+    let config = config::load(args.config.as_deref())?;
+    logging::to_file(config.log.as_ref(), args.headless())?;
+    i18n::init(config.language.as_deref());
+    let mut profiles = profiles(args)?;
+    if let Some(calls) = &config.calls {
+        profiles.watch_calls(calls::Watcher::start(calls));
+    }
+    let rewards = Rewards::from_config(&config.reward);
+    let mut control = start_control(args)?;
+    let input = match &args.input_file {
+        Some(path) => audio::open_file(path, args.input_speed)?,
+        None => audio::open_input(args.device.as_deref().or(config.input_device.as_deref()))?,
+    };
+    let mut meter = Meter::new(input).with_window(config.window_ms, config.hop_ms).with_lowpass(config.lowpass_hz);
+    if let Some(clips) = &config.clips {
+        meter = meter.with_clips(clips);
+    }
+    if let Some(speech) = &config.speech {
+        meter = meter.with_speech(speech)?;
+    }
+    if let Some(voice) = &config.voice {
+        meter = meter.with_voice(voice)?;
+    }
+    let mut percentile;
+    let levels: &mut dyn LevelSource = match &config.percentile {
+        Some(p) => {
+            percentile = Percentile::new(&mut meter, p);
+            &mut percentile
+        }
+        None => &mut meter,
+    };
+    run_loop(levels, action, profiles, rewards, watchdog::start(args), &stop, &mut control)
+}
+
+// the loop's end of the control surfaces: requests to carry out, and the
+// handle it publishes status and history through
+pub struct Control {
+    pub requests: mpsc::Receiver<ipc::Request>,
+    pub handle: Handle,
+    // `[script]`, which gets a say every window too
+    pub script: Option<Script>,
+    // `[influx]`, for the exporter run_loop starts
+    pub influx: Option<InfluxConfig>,
+    // `[power]`, for the strike-out countdown
+    pub power: Option<Power>,
+    // `[email]`, for the mailer run_loop starts
+    pub email: Option<EmailConfig>,
+    // desktop notifications while counting down to a cut
+    pub countdown_toasts: bool,
+}
+
+// IPC and (optionally) HTTP control surfaces for a monitor about to start
+pub fn start_control(args: &RunArgs) -> Result<Control, anyhow::Error> {
+    let (ctl_tx, ctl_rx) = mpsc::channel::<ipc::Request>();
+    let handle = Handle {
+        control: ctl_tx,
+        status: Arc::new(Mutex::new(ipc::Status::default())),
+        history: Arc::new(Mutex::new(History::default())),
+        bus: Bus::default(),
+    };
+
+    // control channel for `shhh ctl`
+    if let Err(e) = ipc::serve(handle.clone()) {
+        warn!("Control channel unavailable: {}", e);
+    }
+    if let Some(addr) = args.http {
+        let Some(token) = args.api_token.clone() else {
+            anyhow::bail!("--http needs an --api-token (or SHHH_API_TOKEN)");
+        };
+        api::serve(addr, token, handle.clone())?;
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = args.grpc {
+        let Some(token) = args.api_token.clone() else {
+            anyhow::bail!("--grpc needs an --api-token (or SHHH_API_TOKEN)");
+        };
+        grpc::serve(addr, token, handle.clone())?;
+    }
+    let config = config::load(args.config.as_deref())?;
+    let power = Power::from_config(&config.power, args.dry_run);
+    let script = config.script.map(|s| Script::load(&s.path)).transpose()?;
+    Ok(Control {
+        requests: ctl_rx,
+        handle,
+        script,
+        influx: config.influx,
+        power,
+        email: config.email,
+        countdown_toasts: config.countdown.notify,
+    })
+}
+
+pub fn run_loop(
+    levels: &mut dyn LevelSource,
+    action: Arc<dyn Action>,
+    mut profiles: Profiles,
+    rewards: Rewards,
+    mut watchdog: Option<Heartbeat>,
+    stop: &AtomicBool,
+    control: &mut Control,
+) -> Result<(), anyhow::Error> {
+    let handle = &control.handle;
+    let started = Instant::now();
+    let bus = handle.bus.clone();
+    let mut stop_requested = false;
+    let mut enforcer = Enforcer::new(action).with_bus(bus.clone());
+    let consumers = [
+        bus::spawn(&bus, "log", log()),
+        bus::spawn(&bus, "notify", notifications(rewards, control.countdown_toasts)),
+        bus::spawn(&bus, "history", {
+            let history = handle.history.clone();
+            move |event| {
+                if let (Event::Window(entry), Ok(mut h)) = (event, history.lock()) {
+                    h.push(entry);
+                }
+            }
+        }),
+        control.influx.take().and_then(|config| {
+            let mut exporter = Exporter::new(config);
+            bus::spawn(&bus, "influx", move |event| exporter.consume(event))
+        }),
+        control.power.take().and_then(|mut power| bus::spawn(&bus, "power", move |event| power.consume(event))),
+        control.email.take().and_then(|config| {
+            let mut mailer = Mailer::new(config);
+            bus::spawn(&bus, "email", move |event| mailer.consume(event))
+        }),
+        #[cfg(windows)]
+        bus::spawn(&bus, "eventlog", eventlog::consumer()),
+    ];
+
+    // a previous run may have died mid-cut
+    state::reconcile()?;
+
+    // so `ctl profile` can check names while we calibrate
+    if let Ok(mut s) = handle.status.lock() {
+        s.profiles = profiles.names();
+    }
+
+    let fixed = profiles.base.absolute.is_some();
+    let ambient = match profiles.base.nominal_ambient() {
+        Some(ambient) => ambient,
+        None => calibrate(levels, &mut watchdog),
+    };
+    let active = profiles.active(profile::minute_now());
+    let mut profile = active.map(|p| p.name.clone());
+    let mut policy = Policy::new(ambient, active.map_or(&profiles.base, |p| &p.rules).clone(), Instant::now());
+    if let Some(name) = &profile {
+        info!("{}", tr!("profile", name = name.as_str()));
+    }
+    if fixed {
+        info!("Fixed thresholds, no calibration: min {:.1}, max {:.1} dBFS", policy.min_db, policy.max_db);
+    } else {
+        info!("Ambient {:.1} dBFS -> min {:.1}, max {:.1}", policy.ambient_db, policy.min_db, policy.max_db);
+    }
+    info!("Action: {}", enforcer.describe());
+    #[cfg(target_os = "linux")]
+    daemon::notify("READY=1");
+
+    while !stop.load(Ordering::SeqCst) {
+        if let Some(w) = &mut watchdog {
+            w.beat();
+        }
+
+        // ---- profiles ----
+        let active = profiles.active(profile::minute_now());
+        if active.map(|p| &p.name) != profile.as_ref() {
+            policy.set_rules(active.map_or(&profiles.base, |p| &p.rules).clone());
+            profile = active.map(|p| p.name.clone());
+            bus.publish(Event::Profile { name: profile.clone(), min_db: policy.min_db, max_db: policy.max_db });
+        }
+
+        // ---- control requests ----
+        while let Ok(req) = control.requests.try_recv() {
+            let decisions = match req {
+                ipc::Request::Pause => {
+                    info!("{}", tr!("paused"));
+                    policy.pause()
+                }
+                ipc::Request::Resume => {
+                    info!("{}", tr!("resumed"));
+                    policy.resume();
+                    Vec::new()
+                }
+                ipc::Request::Restore => policy.restore(),
+                ipc::Request::Cut { secs } => policy.cut_for(Instant::now(), Duration::from_secs(secs)),
+                ipc::Request::Recalibrate if fixed => {
+                    info!("Thresholds are fixed; nothing to recalibrate.");
+                    Vec::new()
+                }
+                ipc::Request::Recalibrate => {
+                    info!("Recalibrating...");
+                    let mut beat = || watchdog.as_mut().map_or((), Heartbeat::beat);
+                    match levels.calibrate(&mut beat) {
+                        Ok(ambient) => {
+                            remember(levels, ambient);
+                            policy.recalibrate(ambient);
+                            info!(
+                                "Ambient {:.1} dBFS -> min {:.1}, max {:.1}",
+                                policy.ambient_db, policy.min_db, policy.max_db
+                            );
+                        }
+                        Err(e) => warn!("Recalibration rejected, keeping the old thresholds: {}.", e),
+                    }
+                    Vec::new()
+                }
+                ipc::Request::Stop => {
+                    info!("{}", tr!("stopped"));
+                    stop_requested = true;
+                    Vec::new()
+                }
+                ipc::Request::Profile { name } => {
+                    // picked up with the clock check on the next window
+                    if let Err(e) = profiles.pin(&name) {
+                        warn!("{}", e);
+                    }
+                    Vec::new()
+                }
+                ipc::Request::Status { .. } | ipc::Request::History { .. } => Vec::new(),
+            };
+            enforcer.carry_out(&decisions);
+            if !decisions.is_empty() {
+                bus.publish(Event::Decisions(decisions));
+            }
+        }
+        enforcer.flush();
+        if stop_requested {
+            break;
+        }
+
+        let level = levels.next_level();
+        if level.is_none() && levels.exhausted() {
+            info!("End of input.");
+            break;
+        }
+
+        // ---- inactivity watchdog ----
+        let Some(db) = level else {
+            let now = Instant::now();
+            let decisions = policy.silence(now);
+            enforcer.carry_out(&decisions);
+            if !decisions.is_empty() {
+                bus.publish(Event::Decisions(decisions));
+            }
+            enforcer.flush();
+            if policy.no_audio(now) {
+                thread::sleep(Duration::from_millis(100));
+            }
+            continue;
+        };
+
+        let now = Instant::now();
+        if levels.impulsive() {
+            policy.impulse();
+        }
+        if levels.someone_else() {
+            policy.someone_else();
+        }
+        let mut decisions = policy.clipping(levels.clipped(), now);
+        decisions.extend(policy.step(db, now));
+        for heard in levels.heard() {
+            match heard {
+                Heard::Keyword(phrase) => {
+                    info!("{}", tr!("heard-keyword", phrase = phrase.as_str()));
+                    decisions.extend(policy.keyword(now));
+                }
+                Heard::SafeWord => {
+                    info!("{}", tr!("heard-safe-word"));
+                    decisions.extend(policy.safe_word(now));
+                }
+            }
+        }
+        if let Some(script) = &mut control.script {
+            let state = policy.state();
+            let window = script::Window {
+                db,
+                ambient_db: policy.ambient_db,
+                soft_db: policy.min_db,
+                cut_db: policy.max_db,
+                state: &state,
+                cutting: policy.enforcing(),
+                paused: policy.paused(),
+                quiet_secs: policy.quiet_for(now).as_secs(),
+                profile: profile.as_deref(),
+            };
+            for command in script.on_window(&window) {
+                decisions.extend(match command {
+                    Command::Cut(min) => policy.cut_for(now, min),
+                    Command::Restore => policy.restore(),
+                    Command::Pause(None) => policy.pause(),
+                    Command::Pause(Some(duration)) => policy.pause_for(now, duration),
+                    Command::Resume => {
+                        policy.resume();
+                        Vec::new()
+                    }
+                    Command::Warn(message) => {
+                        bus.publish(Event::Warning(message));
+                        Vec::new()
+                    }
+                    Command::Notify(message) => {
+                        bus.publish(Event::Notify(message));
+                        Vec::new()
+                    }
+                });
+            }
+        }
+        if decisions.contains(&Decision::Enforce) {
+            levels.save_clip();
+        }
+        enforcer.carry_out(&decisions);
+        enforcer.shape(policy.shaping());
+        enforcer.flush();
+        if !decisions.is_empty() {
+            bus.publish(Event::Decisions(decisions));
+        }
+        let state = policy.state();
+
+        bus.publish(Event::Window(Entry {
+            time_ms: history::now_ms(),
+            db,
+            pct: policy.pct(),
+            state: state.clone(),
+            min_db: policy.min_db,
+            max_db: policy.max_db,
+        }));
+        if let Ok(mut s) = handle.status.lock() {
+            *s = ipc::Status {
+                state: state.clone(),
+                db,
+                ambient_db: policy.ambient_db,
+                min_db: policy.min_db,
+                max_db: policy.max_db,
+                paused: policy.paused(),
+                profile: profile.clone(),
+                profiles: profiles.names(),
+                quiet_secs: policy.quiet_for(now).as_secs(),
+                uptime_secs: started.elapsed().as_secs(),
+                penalty_left_secs: policy.penalty_left(now),
+                cut_in_secs: policy.cut_in(now),
+                restore_in_secs: policy.restore_in(now),
+                tamper: policy.tampered().map(|t| t.to_string()),
+                action: enforcer.describe(),
+                enforcing: enforcer.active(),
+                action_error: enforcer.error(),
+            };
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    #[cfg(target_os = "linux")]
+    daemon::notify("STOPPING=1");
+    enforcer.finish()?;
+    // let the consumers catch up, so the log is complete
+    bus.close();
+    for consumer in consumers.into_iter().flatten() {
+        let _ = consumer.join();
+    }
+    if let Some(w) = &mut watchdog {
+        w.bye();
+    }
+    Ok(())
+}
+
+// The ambient level from the first calibration run that passes the checks,
+// feeding the watchdog between runs. Failing every one (a muted microphone,
+// someone who keeps talking) mustn't keep the monitor from starting, or
+// muting the mic before boot would switch it off: it goes on with the last
+// good calibration, or failing that a typical room, and the tamper and
+// no-audio checks deal with a microphone that stays silent.
+fn calibrate(levels: &mut dyn LevelSource, watchdog: &mut Option<Heartbeat>) -> f32 {
+    let mut tries = 0;
+    loop {
+        tries += 1;
+        match levels.calibrate(&mut || watchdog.as_mut().map_or((), Heartbeat::beat)) {
+            Ok(ambient) => {
+                remember(levels, ambient);
+                return ambient;
+            }
+            // remote rooms carry on calibrating by themselves
+            Err(e) if levels.relative() => {
+                warn!("Calibration rejected: {}. Going on with the rooms that are ready.", e);
+                return 0.0;
+            }
+            Err(e) if tries == CALIBRATION_TRIES || levels.exhausted() => {
+                let (ambient, from) = match state::last_ambient() {
+                    Some(ambient) => (ambient, "the last good calibration"),
+                    None => (FALLBACK_AMBIENT_DB, "a typical room"),
+                };
+                warn!("Calibration rejected: {}. Going on with {} ({:.1} dBFS) for now.", e, from, ambient);
+                return ambient;
+            }
+            Err(e) => warn!("Calibration rejected, trying again: {}.", e),
+        }
+        if let Some(w) = watchdog {
+            w.beat();
+        }
+    }
+}
+
+// a good calibration, to go on with when a later start's fails
+fn remember(levels: &dyn LevelSource, ambient: f32) {
+    if !levels.relative() {
+        state::remember_ambient(ambient);
+    }
+}
+
+// the console log of the loop's events
+fn log() -> impl FnMut(Event) + Send {
+    let mut last_state: Option<String> = None;
+    move |event| match event {
+        Event::Window(entry) => {
+            debug!("Current volume: dB={:.1}", entry.db);
+            if last_state.as_ref() != Some(&entry.state) {
+                info!("dB={:.1} -> {}", entry.db, entry.state);
+                last_state = Some(entry.state);
+            }
+        }
+        Event::Decisions(decisions) => {
+            for decision in decisions {
+                match decision {
+                    Decision::Restore(Reason::NoAudio) => info!("{}", tr!("no-audio")),
+                    Decision::Restore(Reason::Paused | Reason::Forced) => info!("{}", tr!("restored")),
+                    Decision::Warn => info!("{}", tr!("warning-only")),
+                    Decision::Countdown(secs) => warn!("{}", tr!("countdown", secs = secs)),
+                    Decision::CountdownCancelled => info!("{}", tr!("countdown-cancelled")),
+                    Decision::Tamper(tamper) => warn!("{}", tr!("tampering", what = tamper.to_string())),
+                    Decision::TamperCleared => info!("{}", tr!("tampering-cleared")),
+                    Decision::Clipping => warn!("{}", tr!("clipping")),
+                    Decision::ClippingCleared => info!("{}", tr!("clipping-cleared")),
+                    Decision::GainDrift { db, compensated: false } => warn!("{}", tr!("gain-drift", drift = db)),
+                    Decision::GainDrift { db, compensated: true } => warn!("{}", tr!("gain-compensated", drift = db)),
+                    // rewards and strike-outs log themselves
+                    Decision::Reward(_) | Decision::StruckOut => {}
+                    Decision::Enforce | Decision::Restore(Reason::Quiet) => {}
+                }
+            }
+        }
+        Event::Profile { name, min_db, max_db } => {
+            let name = name.unwrap_or_else(|| tr!("profile-default"));
+            info!("{}", tr!("profile", name = name));
+            info!("{}", tr!("thresholds", min = format!("{:.1}", min_db), max = format!("{:.1}", max_db)));
+        }
+        Event::Enforced { enforce, error: None } => info!("{}", tr!(if enforce { "cut" } else { "lifted" })),
+        Event::Shaped { level: 0, error: None } => info!("{}", tr!("unshaped")),
+        Event::Shaped { level, error: None } => info!("{}", tr!("shaped", level = level)),
+        Event::Warning(message) => info!("{}", tr!("script-warning", message = message)),
+        // failures are reported by the enforcer itself, which also covers the
+        // stop path
+        Event::Enforced { error: Some(_), .. } | Event::Shaped { error: Some(_), .. } | Event::Notify(_) => {}
+    }
+}
+
+// rewards, the script's notifications, and the countdown to a cut: when it
+// starts, every five seconds, and when it's called off
+fn notifications(rewards: Rewards, countdown: bool) -> impl FnMut(Event) + Send {
+    let mut counting = false;
+    move |event| match event {
+        Event::Decisions(decisions) => {
+            rewards.carry_out(&decisions);
+            for decision in decisions.iter().filter(|_| countdown) {
+                match *decision {
+                    Decision::Countdown(secs) if !counting || secs % 5 == 0 => {
+                        notify::desktop("shhh", &tr!("countdown", secs = secs))
+                    }
+                    Decision::CountdownCancelled => notify::desktop("shhh", &tr!("countdown-cancelled")),
+                    _ => {}
+                }
+            }
+            counting = match decisions.last() {
+                Some(Decision::Countdown(_)) => true,
+                Some(_) => false,
+                None => counting,
+            };
+        }
+        Event::Notify(message) => notify::desktop("shhh", &message),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::Disconnect;
+    use crate::config::Config;
+    use crate::iface::Mock;
+    use crate::policy::TamperRule;
+    use std::collections::VecDeque;
+
+    // plays back a list of windows, `None` for one without audio (which
+    // takes a while, as a meter waiting for samples does)
+    struct Scripted(VecDeque<Option<f32>>);
+
+    impl LevelSource for Scripted {
+        fn next_level(&mut self) -> Option<f32> {
+            let level = self.0.pop_front().flatten();
+            if level.is_none() {
+                thread::sleep(Duration::from_millis(25));
+            }
+            level
+        }
+
+        fn exhausted(&self) -> bool {
+            self.0.is_empty()
+        }
+    }
+
+    // the whole loop over `windows`, with the adapter behind a mock; what
+    // the adapter was told to do
+    fn run(windows: Vec<Option<f32>>, rules: Rules) -> Vec<(String, bool)> {
+        let _state = state::TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let net = Arc::new(Mock::default());
+        let action = Arc::new(Disconnect { ifaces: vec!["wlan0".into()], net: net.clone() });
+        let profiles = Profiles::new(rules, &Config::default()).unwrap();
+        let rewards = Rewards::from_config(&Default::default());
+        let (tx, requests) = mpsc::channel();
+        let handle =
+            Handle { control: tx, status: Default::default(), history: Default::default(), bus: Bus::default() };
+        let mut control = Control {
+            requests,
+            handle,
+            script: None,
+            influx: None,
+            power: None,
+            email: None,
+            countdown_toasts: false,
+        };
+        let mut levels = Scripted(windows.into());
+        run_loop(&mut levels, action, profiles, rewards, None, &AtomicBool::new(false), &mut control).unwrap();
+        // nothing left for the next start to undo
+        assert!(!state::path().exists());
+        let calls = net.calls.lock().unwrap().clone();
+        calls
+    }
+
+    fn windows(spells: &[(usize, Option<f32>)]) -> Vec<Option<f32>> {
+        spells.iter().flat_map(|&(n, level)| std::iter::repeat_n(level, n)).collect()
+    }
+
+    #[test]
+    fn a_loud_spell_cuts_the_adapter_and_the_quiet_brings_it_back() {
+        // calibration at -60, then shouting, then 2s of quiet
+        let calls = run(windows(&[(40, Some(-60.0)), (10, Some(0.0)), (40, Some(-60.0))]), Rules::default());
+        assert_eq!(calls, [("wlan0".to_string(), false), ("wlan0".to_string(), true)]);
+    }
+
+    #[test]
+    fn a_microphone_muted_from_the_start_still_gets_cut() {
+        // every calibration is rejected, and the loop starts anyway and
+        // catches the mute
+        let tamper = Some(TamperRule { after: Duration::from_secs(1), cut: true });
+        let muted = windows(&[(3 * 30, Some(-999.0)), (40, Some(-999.0))]);
+        let calls = run(muted, Rules { tamper, ..Rules::default() });
+        assert_eq!(calls, [("wlan0".to_string(), false), ("wlan0".to_string(), true)]);
+        // the same for a microphone that sends nothing at all
+        let tamper = Some(TamperRule { after: Duration::from_secs(1), cut: true });
+        let calls = run(windows(&[(3 * 30, None), (60, None)]), Rules { tamper, ..Rules::default() });
+        assert_eq!(calls, [("wlan0".to_string(), false), ("wlan0".to_string(), true)]);
+    }
+}
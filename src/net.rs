@@ -0,0 +1,400 @@
+// Remote sensors: `shhh sensor` meters a microphone (e.g. on a Raspberry Pi
+// in another room) and streams one level per window to `shhh server`, which
+// runs the usual policy and enforcement on those levels instead of its own mic.
+// Several rooms can feed one server; see `Remote` for how they're combined.
+//
+// The protocol is line-delimited JSON over TCP. The server opens with a random
+// nonce, the sensor proves it knows the shared token with
+// HMAC-SHA256(token, nonce), and from then on only sends levels:
+//
+//   server: {"hello":{"nonce":"<hex>"}}
+//   sensor: {"auth":{"room":"kids","mac":"<hex>"}}
+//   server: "welcome"                  (or {"error":"..."} and close)
+//   sensor: {"level":{"db":-41.2}}     one per window, or "silent"
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::AtomicBool;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{debug, info, warn};
+
+use crate::action::Action;
+use crate::audio::{self, SAMPLE_WINDOW_MS};
+use crate::config::{self, LoudAction, RoomConfig, RoomPolicy};
+use crate::error::Error;
+use crate::i18n;
+use crate::logging;
+use crate::meter::{check_calibration, LevelSource, Meter, Percentile, Rejected, CALIBRATION_MS};
+use crate::monitor::{self, RunArgs};
+use crate::reward::Rewards;
+use crate::watchdog;
+
+pub const DEFAULT_PORT: u16 = 8788;
+const NONCE_LEN: usize = 32;
+// how long recalibrating waits for every room's sensor
+const CALIBRATION_SECS: u64 = 15;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Message {
+    Hello { nonce: String },
+    Auth { room: String, mac: String },
+    Welcome,
+    Error(String),
+    Level { db: f32 },
+    Silent,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ServerArgs {
+    #[command(flatten)]
+    pub run: RunArgs,
+
+    /// Address to accept sensors on
+    #[arg(long, default_value_t = SocketAddr::from(([0, 0, 0, 0], DEFAULT_PORT)))]
+    pub listen: SocketAddr,
+
+    /// Shared secret sensors must prove they know
+    #[arg(long, env = "SHHH_SENSOR_TOKEN", hide_env_values = true)]
+    pub sensor_token: String,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct SensorArgs {
+    /// Server to stream to, `host` or `host:port`
+    #[arg(long)]
+    pub server: String,
+
+    /// Shared secret configured on the server
+    #[arg(long, env = "SHHH_SENSOR_TOKEN", hide_env_values = true)]
+    pub sensor_token: String,
+
+    /// Name this sensor reports itself as
+    #[arg(long, default_value = "default")]
+    pub room: String,
+}
+
+fn send(stream: &mut TcpStream, msg: &Message) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(msg)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+fn recv(reader: &mut impl BufRead) -> Result<Message, anyhow::Error> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        anyhow::bail!("connection closed");
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn mac(token: &str, nonce: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(nonce);
+    mac
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// --- server ---
+
+struct Room {
+    config: RoomConfig,
+    ambient_db: Option<f32>,
+    calibration: Vec<f32>,
+    // latest level and when it arrived
+    level: Option<(f32, Instant)>,
+    last_seen: Instant,
+    over: bool,
+}
+
+// Combines every connected sensor into a single level per window for the
+// monitor loop. Each room is calibrated on its own and its level is expressed
+// in dB over its own ambient, rescaled so the room's configured offsets line
+// up with the loop's ones — the loop's thresholds then mean "this room is
+// over *its* threshold" regardless of which room it is.
+struct Remote {
+    rx: mpsc::Receiver<(String, Option<f32>)>,
+    policy: RoomPolicy,
+    configs: BTreeMap<String, RoomConfig>,
+    rooms: HashMap<String, Room>,
+    // the loop's offsets, which every room is rescaled to
+    soft_offset_db: f32,
+    cut_offset_db: f32,
+}
+
+impl Remote {
+    fn room(&mut self, name: &str) -> &mut Room {
+        let config = match self.policy {
+            RoomPolicy::Any => RoomConfig {
+                soft_offset_db: self.soft_offset_db,
+                cut_offset_db: self.cut_offset_db,
+                ..RoomConfig::default()
+            },
+            RoomPolicy::PerRoom => self.configs.get(name).cloned().unwrap_or_default(),
+        };
+        self.rooms.entry(name.to_string()).or_insert_with(|| Room {
+            config,
+            ambient_db: None,
+            calibration: Vec::new(),
+            level: None,
+            last_seen: Instant::now(),
+            over: false,
+        })
+    }
+
+    fn record(&mut self, name: &str, db: f32) {
+        let room = self.room(name);
+        room.last_seen = Instant::now();
+        // each room calibrates the way the local microphone does, and starts
+        // over until it passes
+        if room.ambient_db.is_none() {
+            room.calibration.push(db);
+            if room.calibration.len() as u64 == CALIBRATION_MS / SAMPLE_WINDOW_MS {
+                match check_calibration(&room.calibration, false) {
+                    Ok(ambient) => {
+                        info!("Room '{}' ambient {:.1} dBFS", name, ambient);
+                        room.ambient_db = Some(ambient);
+                    }
+                    Err(e) => warn!("Room '{}' calibration rejected, trying again: {}.", name, e),
+                }
+                room.calibration.clear();
+            }
+            return;
+        }
+        room.level = Some((db, Instant::now()));
+    }
+}
+
+impl LevelSource for Remote {
+    fn next_level(&mut self) -> Option<f32> {
+        let deadline = Instant::now() + Duration::from_millis(SAMPLE_WINDOW_MS);
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            match self.rx.recv_timeout(left) {
+                Ok((name, Some(db))) => self.record(&name, db),
+                Ok((name, None)) => {
+                    let room = self.room(&name);
+                    room.level = None;
+                    room.last_seen = Instant::now();
+                }
+                Err(_) => break,
+            }
+        }
+        // forget rooms whose sensor went away, so recalibration doesn't wait on them
+        self.rooms.retain(|name, room| {
+            let alive = room.last_seen.elapsed() < Duration::from_secs(5);
+            if !alive {
+                info!("Room '{}' went quiet, dropping it", name);
+            }
+            alive
+        });
+
+        let stale = Duration::from_millis(SAMPLE_WINDOW_MS + 200);
+        let (soft, cut) = (self.soft_offset_db, self.cut_offset_db);
+        let mut loudest: Option<f32> = None;
+        for (name, room) in &mut self.rooms {
+            let (Some(ambient), Some((db, at))) = (room.ambient_db, room.level) else { continue };
+            if at.elapsed() > stale {
+                continue;
+            }
+            let c = &room.config;
+            let rel = soft + (db - ambient - c.soft_offset_db) * (cut - soft) / (c.cut_offset_db - c.soft_offset_db);
+            match c.action {
+                LoudAction::Cut => loudest = Some(loudest.map_or(rel, |l| l.max(rel))),
+                LoudAction::Warn => {
+                    let over = rel >= cut;
+                    if over && !room.over {
+                        info!("Room '{}' is too loud ({:.1} dBFS), warning only", name, db);
+                    }
+                    room.over = over;
+                }
+            }
+        }
+        loudest
+    }
+
+    // recalibrate every connected room; levels are relative, so ambient is 0.
+    // Rooms that haven't finished in time go on calibrating as their levels
+    // come, and only count once they have.
+    fn calibrate(&mut self, tick: &mut dyn FnMut()) -> Result<f32, Rejected> {
+        for room in self.rooms.values_mut() {
+            room.ambient_db = None;
+            room.calibration.clear();
+        }
+        let deadline = Instant::now() + Duration::from_secs(CALIBRATION_SECS);
+        while self.rooms.is_empty() || self.rooms.values().any(|r| r.ambient_db.is_none()) {
+            if Instant::now() >= deadline {
+                return Err(Rejected::Sensors(self.rooms.values().filter(|r| r.ambient_db.is_none()).count()));
+            }
+            self.next_level();
+            tick();
+        }
+        Ok(0.0)
+    }
+
+    fn relative(&self) -> bool {
+        true
+    }
+}
+
+pub fn run_server(stop: Arc<AtomicBool>, args: &ServerArgs, action: Arc<dyn Action>) -> Result<(), anyhow::Error> {
+    if args.run.input_file.is_some() {
+        anyhow::bail!("--input-file replaces the local microphone; it has no effect in server mode");
+    }
+    let config = config::load(args.run.config.as_deref())?;
+    logging::to_file(config.log.as_ref(), args.run.headless())?;
+    i18n::init(config.language.as_deref());
+    let profiles = monitor::profiles(&args.run)?;
+    if profiles.base.absolute.is_some() {
+        // sensor levels arrive relative to each room's own ambient
+        return Err(Error::Config("fixed thresholds (soft_db/cut_db) don't apply to server mode".into()).into());
+    }
+    let listener = TcpListener::bind(args.listen)?;
+    info!("Waiting for sensors on {} ({:?} room policy)", args.listen, config.room_policy);
+
+    let (tx, rx) = mpsc::channel();
+    let token = args.sensor_token.clone();
+    let connected = Arc::new(Mutex::new(HashSet::new()));
+    thread::spawn(move || {
+        for conn in listener.incoming().filter_map(Result::ok) {
+            let (tx, token, connected) = (tx.clone(), token.clone(), connected.clone());
+            thread::spawn(move || {
+                let peer = conn.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                if let Err(e) = serve_sensor(conn, &token, &tx, &connected) {
+                    warn!("Sensor {} disconnected: {}", peer, e);
+                }
+            });
+        }
+    });
+
+    let mut remote = Remote {
+        rx,
+        policy: config.room_policy,
+        configs: config.rooms,
+        rooms: HashMap::new(),
+        soft_offset_db: profiles.base.soft_offset_db,
+        cut_offset_db: profiles.base.cut_offset_db,
+    };
+    let mut control = monitor::start_control(&args.run)?;
+    let rewards = Rewards::from_config(&config.reward);
+    let mut percentile;
+    let levels: &mut dyn LevelSource = match &config.percentile {
+        Some(p) => {
+            percentile = Percentile::new(&mut remote, p);
+            &mut percentile
+        }
+        None => &mut remote,
+    };
+    monitor::run_loop(levels, action, profiles, rewards, watchdog::start(&args.run), &stop, &mut control)
+}
+
+fn serve_sensor(
+    mut stream: TcpStream,
+    token: &str,
+    tx: &mpsc::Sender<(String, Option<f32>)>,
+    connected: &Mutex<HashSet<String>>,
+) -> Result<(), anyhow::Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut nonce = [0u8; NONCE_LEN];
+    getrandom::fill(&mut nonce).map_err(|e| anyhow::anyhow!("no randomness for nonce: {}", e))?;
+    send(&mut stream, &Message::Hello { nonce: hex(&nonce) })?;
+
+    let Message::Auth { room, mac: given } = recv(&mut reader)? else {
+        anyhow::bail!("expected auth");
+    };
+    let valid = unhex(&given).is_some_and(|given| mac(token, &nonce).verify_slice(&given).is_ok());
+    if !valid {
+        send(&mut stream, &Message::Error("authentication failed".into()))?;
+        anyhow::bail!("authentication failed");
+    }
+    // two sensors claiming the same room would fight over its calibration
+    if !connected.lock().map_err(|_| anyhow::anyhow!("poisoned"))?.insert(room.clone()) {
+        send(&mut stream, &Message::Error(format!("room '{}' is already connected", room)))?;
+        anyhow::bail!("rejected, room '{}' is already connected", room);
+    }
+    send(&mut stream, &Message::Welcome)?;
+    info!("Sensor '{}' connected from {}", room, stream.peer_addr()?);
+
+    let result = loop {
+        match recv(&mut reader) {
+            Ok(Message::Level { db }) => {
+                let _ = tx.send((room.clone(), Some(db)));
+            }
+            Ok(Message::Silent) => {
+                let _ = tx.send((room.clone(), None));
+            }
+            Ok(other) => break Err(anyhow::anyhow!("unexpected message {:?}", other)),
+            Err(e) => break Err(e),
+        }
+    };
+    if let Ok(mut c) = connected.lock() {
+        c.remove(&room);
+    }
+    result
+}
+
+// --- sensor ---
+
+pub fn run_sensor(args: &SensorArgs) -> Result<(), anyhow::Error> {
+    let addr = if args.server.contains(':') { args.server.clone() } else { format!("{}:{}", args.server, DEFAULT_PORT) };
+    let config = config::load(None)?;
+    let mut meter = Meter::new(audio::open_input(config.input_device.as_deref())?).with_lowpass(config.lowpass_hz);
+
+    loop {
+        if let Err(e) = stream_levels(&addr, args, &mut meter) {
+            warn!("Server {}: {} — retrying in 5s", addr, e);
+        }
+        // keep draining the mic so we don't send a backlog of stale windows
+        let retry = Instant::now();
+        while retry.elapsed() < Duration::from_secs(5) {
+            meter.next_level();
+        }
+    }
+}
+
+fn stream_levels(addr: &str, args: &SensorArgs, meter: &mut Meter) -> Result<(), anyhow::Error> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let Message::Hello { nonce } = recv(&mut reader)? else {
+        anyhow::bail!("expected hello");
+    };
+    let nonce = unhex(&nonce).ok_or_else(|| anyhow::anyhow!("malformed nonce"))?;
+    let proof = mac(&args.sensor_token, &nonce).finalize().into_bytes();
+    send(&mut stream, &Message::Auth { room: args.room.clone(), mac: hex(&proof) })?;
+    match recv(&mut reader)? {
+        Message::Welcome => info!("Connected to {} as '{}'", addr, args.room),
+        Message::Error(e) => anyhow::bail!(e),
+        other => anyhow::bail!("unexpected message {:?}", other),
+    }
+
+    loop {
+        let msg = match meter.next_level() {
+            Some(db) => {
+                debug!("Current volume: dB={:.1}", db);
+                Message::Level { db }
+            }
+            None => Message::Silent,
+        };
+        send(&mut stream, &msg)?;
+    }
+}
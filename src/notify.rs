@@ -0,0 +1,54 @@
+// Telling someone about something outside the log: a desktop notification in
+// the session shhh runs in, or a JSON POST to a webhook. Both are fire and
+// forget on their own thread, so a slow server or a missing notifier never
+// holds up the monitor loop.
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+
+pub fn desktop(title: &str, body: &str) {
+    let mut cmd = desktop_command(title, body);
+    thread::spawn(move || {
+        if let Err(e) = cmd.status() {
+            warn!("Could not show a notification: {}", e);
+        }
+    });
+}
+
+#[cfg(not(windows))]
+fn desktop_command(title: &str, body: &str) -> Command {
+    let mut cmd = Command::new("notify-send");
+    cmd.args(["--app-name=shhh", title, body]);
+    cmd
+}
+
+// a tray balloon through PowerShell, which every supported Windows has
+#[cfg(windows)]
+fn desktop_command(title: &str, body: &str) -> Command {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; $n = New-Object System.Windows.Forms.NotifyIcon; \
+         $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+         $n.ShowBalloonTip(10000, {}, {}, 'Info'); Start-Sleep 10; $n.Dispose()",
+        quote(title),
+        quote(body)
+    );
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-WindowStyle", "Hidden", "-Command", &script]);
+    cmd
+}
+
+pub fn webhook(url: &str, body: serde_json::Value) {
+    let url = url.to_string();
+    thread::spawn(move || {
+        let result = ureq::post(&url)
+            .timeout(Duration::from_secs(10))
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string());
+        if let Err(e) = result {
+            warn!("Webhook {} failed: {}", url, e);
+        }
+    });
+}
@@ -0,0 +1,152 @@
+// Minimal obs-websocket (v5) client: connect, identify, send requests. The
+// OBS action uses a fresh connection per call; calls only happen when the
+// enforcement state flips, and it keeps working across OBS restarts.
+use std::net::TcpStream;
+
+use data_encoding::BASE64;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+const RPC_VERSION: u64 = 1;
+
+// op codes
+const HELLO: u64 = 0;
+const IDENTIFY: u64 = 1;
+const IDENTIFIED: u64 = 2;
+const REQUEST: u64 = 6;
+const RESPONSE: u64 = 7;
+
+pub struct Client {
+    ws: WebSocket<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+
+impl Client {
+    pub fn connect(url: &str, password: Option<&str>) -> Result<Client, anyhow::Error> {
+        let (ws, _) = tungstenite::connect(url).map_err(|e| anyhow::anyhow!("could not reach OBS at {}: {}", url, e))?;
+        let mut client = Client { ws, next_id: 0 };
+
+        let hello = client.receive(HELLO)?;
+        let mut identify = json!({ "rpcVersion": RPC_VERSION, "eventSubscriptions": 0 });
+        if let Some(auth) = hello.get("authentication") {
+            let Some(password) = password else {
+                anyhow::bail!("OBS asks for a password; set obs.password in the config");
+            };
+            let (challenge, salt) = (auth["challenge"].as_str().unwrap_or(""), auth["salt"].as_str().unwrap_or(""));
+            identify["authentication"] = auth_string(password, salt, challenge).into();
+        }
+        client.send(IDENTIFY, identify)?;
+        client.receive(IDENTIFIED).map_err(|e| anyhow::anyhow!("OBS refused the connection (wrong password?): {}", e))?;
+        Ok(client)
+    }
+
+    // one request; the response data, or the error OBS gave
+    pub fn request(&mut self, request_type: &str, data: Value) -> Result<Value, anyhow::Error> {
+        self.next_id += 1;
+        let id = self.next_id.to_string();
+        self.send(REQUEST, json!({ "requestType": request_type, "requestId": id, "requestData": data }))?;
+        loop {
+            let response = self.receive(RESPONSE)?;
+            if response["requestId"] != id.as_str() {
+                continue;
+            }
+            let status = &response["requestStatus"];
+            if status["result"] != true {
+                anyhow::bail!("OBS {} failed: {}", request_type, status["comment"].as_str().unwrap_or("no reason given"));
+            }
+            return Ok(response.get("responseData").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    fn send(&mut self, op: u64, d: Value) -> Result<(), anyhow::Error> {
+        self.ws.send(Message::text(json!({ "op": op, "d": d }).to_string()))?;
+        Ok(())
+    }
+
+    // the `d` of the next message with this op code, skipping anything else
+    fn receive(&mut self, op: u64) -> Result<Value, anyhow::Error> {
+        loop {
+            match self.ws.read()? {
+                Message::Text(text) => {
+                    let mut message: Value = serde_json::from_str(&text)?;
+                    if message["op"] == op {
+                        return Ok(message["d"].take());
+                    }
+                }
+                Message::Close(frame) => {
+                    anyhow::bail!("OBS closed the connection{}", frame.map(|f| format!(": {}", f.reason)).unwrap_or_default())
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.ws.close(None);
+        let _ = self.ws.flush();
+    }
+}
+
+// base64(sha256(base64(sha256(password + salt)) + challenge))
+fn auth_string(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(&Sha256::digest(format!("{}{}", password, salt)));
+    BASE64.encode(&Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auth_string_matches_the_protocol() {
+        let salt = "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=";
+        let challenge = "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY=";
+        assert_eq!(auth_string("supersecretpassword", salt, challenge), "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4=");
+    }
+
+    // just enough of obs-websocket to identify and answer one request
+    fn fake_obs(password: &'static str) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let mut ws = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            let send = |ws: &mut WebSocket<_>, v: Value| ws.send(Message::text(v.to_string())).unwrap();
+            let hello = json!({ "op": HELLO, "d": { "rpcVersion": 1, "authentication": { "challenge": "c", "salt": "s" } } });
+            send(&mut ws, hello);
+            let identify: Value = serde_json::from_str(ws.read().unwrap().to_text().unwrap()).unwrap();
+            if identify["d"]["authentication"] != auth_string(password, "s", "c") {
+                ws.close(None).unwrap();
+                let _ = ws.flush();
+                return;
+            }
+            send(&mut ws, json!({ "op": IDENTIFIED, "d": { "negotiatedRpcVersion": 1 } }));
+            let request: Value = serde_json::from_str(ws.read().unwrap().to_text().unwrap()).unwrap();
+            let d = &request["d"];
+            send(&mut ws, json!({ "op": RESPONSE, "d": {
+                "requestType": d["requestType"],
+                "requestId": d["requestId"],
+                "requestStatus": { "result": true, "code": 100 },
+                "responseData": { "currentProgramSceneName": "Main" },
+            } }));
+        });
+        url
+    }
+
+    #[test]
+    fn identifies_and_makes_requests() {
+        let url = fake_obs("hunter2");
+        let mut client = Client::connect(&url, Some("hunter2")).unwrap();
+        let scene = client.request("GetCurrentProgramScene", json!({})).unwrap();
+        assert_eq!(scene["currentProgramSceneName"], "Main");
+    }
+
+    #[test]
+    fn a_wrong_password_is_an_error() {
+        let url = fake_obs("hunter2");
+        assert!(Client::connect(&url, Some("letmein")).is_err());
+    }
+}
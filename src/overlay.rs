@@ -0,0 +1,160 @@
+// `shhh overlay`: a small always-on-top bar in a corner of the screen showing
+// the live level against the thresholds, so whoever is being monitored can
+// see a cut coming and pipe down first. It's a separate process that polls the
+// running monitor (the control channel, or the HTTP API when the monitor runs
+// as a service the user can't talk to), so it belongs in the user's session.
+use std::thread;
+use std::time::{Duration, Instant};
+
+use minifb::{Key, Window, WindowOptions};
+
+use crate::ipc::{self, Response, Status};
+
+const WIDTH: usize = 240;
+const HEIGHT: usize = 16;
+// distance from the screen edges
+const MARGIN: isize = 16;
+const POLL: Duration = Duration::from_millis(200);
+// the countdown's blocks: 6x4 pixels, 2 apart
+const TIMER_BLOCK: usize = 6;
+const TIMER_HEIGHT: usize = 4;
+
+// 0xAARRGGBB; the alpha only applies where the platform supports it
+const BACKGROUND: u32 = 0xC0202020;
+const OFFLINE: u32 = 0xC0606060;
+const OK: u32 = 0xE040C040;
+const NEAR: u32 = 0xE0E0C020;
+const LOUD: u32 = 0xE0E03030;
+const MARK: u32 = 0xFFFFFFFF;
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct OverlayArgs {
+    /// Screen corner to sit in
+    #[arg(long, value_enum, default_value = "top-right")]
+    pub corner: Corner,
+
+    /// Poll this HTTP API (e.g. http://127.0.0.1:8787) instead of the control channel
+    #[arg(long, value_name = "URL")]
+    pub api: Option<String>,
+
+    /// Token for --api
+    #[arg(long, env = "SHHH_API_TOKEN", hide_env_values = true, requires = "api")]
+    pub api_token: Option<String>,
+}
+
+pub fn run(args: &OverlayArgs) -> Result<(), anyhow::Error> {
+    let options = WindowOptions { borderless: true, none: true, topmost: true, transparency: true, ..Default::default() };
+    let mut window = Window::new("shhh", WIDTH, HEIGHT, options)
+        .map_err(|e| anyhow::anyhow!("could not open the overlay ({}); is there a desktop session?", e))?;
+    if let Some((w, h)) = screen_size() {
+        let (w, h) = (w as isize, h as isize);
+        let (right, bottom) = (w - WIDTH as isize - MARGIN, h - HEIGHT as isize - MARGIN);
+        let (x, y) = match args.corner {
+            Corner::TopLeft => (MARGIN, MARGIN),
+            Corner::TopRight => (right, MARGIN),
+            Corner::BottomLeft => (MARGIN, bottom),
+            Corner::BottomRight => (right, bottom),
+        };
+        window.set_position(x, y);
+    }
+    window.set_target_fps(10);
+    println!("Showing the level overlay; press Escape on it (or Ctrl+C here) to close.");
+
+    let mut buffer = vec![BACKGROUND; WIDTH * HEIGHT];
+    let mut status = None;
+    let mut last_poll: Option<Instant> = None;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if last_poll.is_none_or(|t| t.elapsed() >= POLL) {
+            // a monitor that isn't running (yet) just shows as grey
+            status = poll(args).ok();
+            last_poll = Some(Instant::now());
+        }
+        draw(&mut buffer, status.as_ref());
+        window.update_with_buffer(&buffer, WIDTH, HEIGHT)?;
+        thread::sleep(Duration::from_millis(20));
+    }
+    Ok(())
+}
+
+fn poll(args: &OverlayArgs) -> Result<Status, anyhow::Error> {
+    if let Some(api) = &args.api {
+        let url = format!("{}/status", api.trim_end_matches('/'));
+        let mut req = ureq::get(&url).timeout(Duration::from_secs(2));
+        if let Some(token) = &args.api_token {
+            req = req.set("Authorization", &format!("Bearer {}", token));
+        }
+        return Ok(serde_json::from_str(&req.call()?.into_string()?)?);
+    }
+    match ipc::send(ipc::Request::Status { json: false })? {
+        Response::Status(s) => Ok(*s),
+        _ => anyhow::bail!("unexpected reply"),
+    }
+}
+
+// The bar spans ambient to the cut threshold, with a tick at the soft one:
+// green below it, yellow above it, red when cut (or at the threshold). While
+// counting down to a cut, a row of blocks along the bottom, one a second.
+fn draw(buffer: &mut [u32], status: Option<&Status>) {
+    buffer.fill(BACKGROUND);
+    let Some(s) = status else {
+        buffer.fill(OFFLINE);
+        return;
+    };
+    let span = (s.max_db - s.ambient_db).max(1.0);
+    let x = |db: f32| (((db - s.ambient_db) / span).clamp(0.0, 1.0) * (WIDTH - 1) as f32) as usize;
+    let fill = x(s.db);
+    let color = if s.enforcing || s.db >= s.max_db {
+        LOUD
+    } else if s.db >= s.min_db {
+        NEAR
+    } else {
+        OK
+    };
+    let soft = x(s.min_db);
+    for row in buffer.chunks_mut(WIDTH) {
+        row[..=fill].fill(color);
+        row[soft] = MARK;
+    }
+    let secs = s.cut_in_secs.unwrap_or(0) as usize;
+    for row in buffer.chunks_mut(WIDTH).skip(HEIGHT - TIMER_HEIGHT) {
+        for block in row.chunks_mut(TIMER_BLOCK + 2).take(secs) {
+            block[..TIMER_BLOCK].fill(MARK);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn screen_size() -> Option<(i32, i32)> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN};
+    let (w, h) = unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+    (w > 0 && h > 0).then_some((w, h))
+}
+
+#[cfg(target_os = "linux")]
+fn screen_size() -> Option<(i32, i32)> {
+    use x11_dl::xlib::Xlib;
+    let xlib = Xlib::open().ok()?;
+    unsafe {
+        let display = (xlib.XOpenDisplay)(std::ptr::null());
+        if display.is_null() {
+            return None;
+        }
+        let screen = (xlib.XDefaultScreen)(display);
+        let size = ((xlib.XDisplayWidth)(display, screen), (xlib.XDisplayHeight)(display, screen));
+        (xlib.XCloseDisplay)(display);
+        Some(size)
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn screen_size() -> Option<(i32, i32)> {
+    None
+}
@@ -0,0 +1,291 @@
+// Third-party actions as WebAssembly plugins: every `.wasm` in the plugin
+// directory (and every `[plugins.<name>]` in the config) is loaded at startup
+// and cut/restored alongside the built-in actions. Plugins run sandboxed in an
+// interpreter and can only reach the outside through the host functions
+// below, so a router or notification integration can live out of tree.
+//
+// ABI version 1. The plugin exports:
+//   memory
+//   shhh_abi_version() -> i32              must return 1
+//   shhh_alloc(len: i32) -> i32            room for the host to write into
+//   shhh_init(ptr: i32, len: i32) -> i32   optional; its settings as JSON
+//   shhh_apply(enforce: i32) -> i32        1 = cut, 0 = restore; idempotent
+// and every i32 result except shhh_alloc's is 0 for success. It may import,
+// from module "shhh":
+//   log(ptr, len)                          a line for the log
+//   fail(ptr, len)                         why the current call failed
+//   http(ptr, len) -> i32                  a JSON {method, url, headers, body}
+//                                          request; the status, or -1
+//   response(ptr, cap) -> i32              copies up to `cap` bytes of the
+//                                          last response body; its full length
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing::info;
+use wasmi::{Caller, Config, Engine, Extern, Instance, Linker, Module, Store};
+
+use crate::action::Action;
+use crate::config::{self, PluginConfig};
+use crate::state::State;
+
+pub const ABI_VERSION: i32 = 1;
+// instructions per call, so a plugin stuck in a loop fails instead of hanging
+const FUEL: u64 = 500_000_000;
+
+// what the host keeps for a plugin between host calls
+#[derive(Default)]
+struct Host {
+    name: String,
+    error: Option<String>,
+    response: Vec<u8>,
+}
+
+pub struct Plugin {
+    name: String,
+    wasm: Mutex<(Store<Host>, Instance)>,
+}
+
+impl Plugin {
+    pub fn load(name: &str, path: &Path, settings: &toml::Table) -> Result<Plugin, anyhow::Error> {
+        let wasm = std::fs::read(path).map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+        Plugin::new(name, &wasm, settings).map_err(|e| anyhow::anyhow!("plugin {} ({}): {}", name, path.display(), e))
+    }
+
+    fn new(name: &str, wasm: &[u8], settings: &toml::Table) -> Result<Plugin, anyhow::Error> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm)?;
+        let mut store = Store::new(&engine, Host { name: name.to_string(), ..Host::default() });
+        let instance = linker(&engine)?.instantiate_and_start(&mut store, &module)?;
+        let mut plugin = Plugin { name: name.to_string(), wasm: Mutex::new((store, instance)) };
+
+        let version = plugin.call::<(), i32>("shhh_abi_version", ())?;
+        if version != ABI_VERSION {
+            anyhow::bail!("built for plugin ABI {}, this shhh speaks {}", version, ABI_VERSION);
+        }
+        let (store, instance) = plugin.wasm.get_mut().map_err(|_| anyhow::anyhow!("poisoned"))?;
+        if instance.get_export(&*store, "shhh_init").is_some() {
+            let json = serde_json::to_vec(settings)?;
+            let ptr = instance.get_typed_func::<i32, i32>(&*store, "shhh_alloc")?.call(&mut *store, json.len() as i32)?;
+            memory(instance, store)?.write(&mut *store, ptr as usize, &json)?;
+            plugin.check("shhh_init", (ptr, json.len() as i32))?;
+        }
+        Ok(plugin)
+    }
+
+    fn call<P: wasmi::WasmParams, R: wasmi::WasmResults>(&self, func: &str, params: P) -> Result<R, anyhow::Error> {
+        let mut wasm = self.wasm.lock().map_err(|_| anyhow::anyhow!("plugin {} panicked earlier", self.name))?;
+        let (store, instance) = &mut *wasm;
+        store.data_mut().error = None;
+        store.set_fuel(FUEL)?;
+        let f = instance.get_typed_func::<P, R>(&*store, func)?;
+        Ok(f.call(&mut *store, params)?)
+    }
+
+    // a call whose non-zero result is a failure, with the plugin's reason
+    fn check<P: wasmi::WasmParams>(&self, func: &str, params: P) -> Result<(), anyhow::Error> {
+        let code = self.call::<P, i32>(func, params)?;
+        if code == 0 {
+            return Ok(());
+        }
+        let error = self.wasm.lock().ok().and_then(|mut w| w.0.data_mut().error.take());
+        anyhow::bail!("{} failed: {}", func, error.unwrap_or_else(|| format!("returned {}", code)))
+    }
+}
+
+impl Action for Plugin {
+    fn describe(&self) -> String {
+        format!("plugin {}", self.name)
+    }
+
+    fn apply(&self, enforce: bool) -> Result<(), anyhow::Error> {
+        self.check("shhh_apply", enforce as i32).map_err(|e| anyhow::anyhow!("plugin {}: {}", self.name, e))
+    }
+
+    // whatever a plugin switched off isn't known to shhh; a run that dies
+    // mid-cut leaves it to the plugin's next restore
+    fn state(&self) -> State {
+        State::default()
+    }
+}
+
+fn memory(instance: &Instance, store: &Store<Host>) -> Result<wasmi::Memory, anyhow::Error> {
+    instance
+        .get_export(store, "memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| anyhow::anyhow!("no exported memory"))
+}
+
+fn read(caller: &Caller<'_, Host>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let mut buf = vec![0; usize::try_from(len).ok()?];
+    memory.read(caller, usize::try_from(ptr).ok()?, &mut buf).ok()?;
+    Some(buf)
+}
+
+#[derive(Deserialize)]
+struct HttpRequest {
+    #[serde(default = "get")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+fn get() -> String {
+    "GET".into()
+}
+
+fn linker(engine: &Engine) -> Result<Linker<Host>, anyhow::Error> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap("shhh", "log", |caller: Caller<'_, Host>, ptr: i32, len: i32| {
+        let text = read(&caller, ptr, len).unwrap_or_default();
+        info!("[{}] {}", caller.data().name, String::from_utf8_lossy(&text));
+    })?;
+    linker.func_wrap("shhh", "fail", |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+        let text = read(&caller, ptr, len).unwrap_or_default();
+        caller.data_mut().error = Some(String::from_utf8_lossy(&text).into_owned());
+    })?;
+    linker.func_wrap("shhh", "http", |mut caller: Caller<'_, Host>, ptr: i32, len: i32| -> i32 {
+        let request = read(&caller, ptr, len).and_then(|json| serde_json::from_slice::<HttpRequest>(&json).ok());
+        let Some(request) = request else {
+            caller.data_mut().error = Some("malformed http request".into());
+            return -1;
+        };
+        let mut req = ureq::request(&request.method, &request.url).timeout(Duration::from_secs(10));
+        for (name, value) in &request.headers {
+            req = req.set(name, value);
+        }
+        let response = match req.send_string(&request.body) {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => {
+                info!("[{}] {} {}: {}", caller.data().name, request.method, request.url, e);
+                return -1;
+            }
+        };
+        let status = response.status() as i32;
+        let mut body = Vec::new();
+        let _ = std::io::Read::read_to_end(&mut response.into_reader(), &mut body);
+        caller.data_mut().response = body;
+        status
+    })?;
+    linker.func_wrap("shhh", "response", |mut caller: Caller<'_, Host>, ptr: i32, cap: i32| -> i32 {
+        let body = std::mem::take(&mut caller.data_mut().response);
+        let n = body.len().min(cap.max(0) as usize);
+        if let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) {
+            let _ = memory.write(&mut caller, ptr.max(0) as usize, &body[..n]);
+        }
+        let len = body.len() as i32;
+        caller.data_mut().response = body;
+        len
+    })?;
+    Ok(linker)
+}
+
+// where drop-in plugins go: `plugins` next to the default config file
+pub fn default_dir() -> PathBuf {
+    config::default_path().with_file_name("plugins")
+}
+
+// the drop-in directory's plugins plus the configured ones, by name
+pub fn load_all(dir: Option<&Path>, configured: &BTreeMap<String, PluginConfig>) -> Result<Vec<Plugin>, anyhow::Error> {
+    let dir = dir.map_or_else(default_dir, Path::to_path_buf);
+    let mut found: Vec<(String, PathBuf)> = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "wasm") {
+                if let Some(name) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) {
+                    found.push((name, path));
+                }
+            }
+        }
+    }
+    for (name, plugin) in configured {
+        let path = plugin.path.clone().unwrap_or_else(|| dir.join(format!("{}.wasm", name)));
+        found.retain(|(n, _)| n != name);
+        found.push((name.clone(), path));
+    }
+    found.sort();
+    // with the service installed plugins run as SYSTEM; only the administrator's
+    #[cfg(windows)]
+    if crate::service::installed() {
+        for path in found.iter().map(|(_, path)| path.as_path()).chain(dir.exists().then_some(dir.as_path())) {
+            crate::acl::check(path)?;
+            crate::acl::check(path.parent().unwrap_or(path))?;
+        }
+    }
+    let empty = toml::Table::new();
+    found
+        .iter()
+        .map(|(name, path)| {
+            let settings = configured.get(name).map_or(&empty, |p| &p.settings);
+            let plugin = Plugin::load(name, path, settings)?;
+            info!("Loaded plugin {} from {}", name, path.display());
+            Ok(plugin)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // fails init without settings, and fails every cut with "no router"
+    const PLUGIN: &str = r#"
+        (module
+          (import "shhh" "fail" (func $fail (param i32 i32)))
+          (memory (export "memory") 1)
+          (data (i32.const 0) "no router")
+          (func (export "shhh_abi_version") (result i32) i32.const 1)
+          (func (export "shhh_alloc") (param i32) (result i32) i32.const 1024)
+          (func (export "shhh_init") (param i32 i32) (result i32)
+            local.get 1
+            i32.const 2
+            i32.eq)
+          (func (export "shhh_apply") (param i32) (result i32)
+            local.get 0
+            if
+              i32.const 0
+              i32.const 9
+              call $fail
+              i32.const 1
+              return
+            end
+            i32.const 0)
+          (func (export "spin") (loop br 0)))
+    "#;
+
+    fn settings() -> toml::Table {
+        toml::from_str("host = \"192.168.0.1\"").unwrap()
+    }
+
+    #[test]
+    fn plugins_are_initialized_and_called() {
+        let plugin = Plugin::new("router", PLUGIN.as_bytes(), &settings()).unwrap();
+        assert_eq!(plugin.describe(), "plugin router");
+        plugin.apply(false).unwrap();
+        let e = plugin.apply(true).unwrap_err().to_string();
+        assert!(e.contains("no router"), "{}", e);
+        // `{}` is too short for this one
+        assert!(Plugin::new("router", PLUGIN.as_bytes(), &toml::Table::new()).is_err());
+    }
+
+    #[test]
+    fn runaway_plugins_run_out_of_fuel() {
+        let plugin = Plugin::new("router", PLUGIN.as_bytes(), &settings()).unwrap();
+        assert!(plugin.call::<(), ()>("spin", ()).is_err());
+    }
+
+    #[test]
+    fn other_abi_versions_are_refused() {
+        let wasm = r#"(module (memory (export "memory") 1) (func (export "shhh_abi_version") (result i32) i32.const 2))"#;
+        assert!(Plugin::new("old", wasm.as_bytes(), &settings()).is_err());
+    }
+}
@@ -0,0 +1,1175 @@
+// The enforcement rules, free of I/O: levels, silence and control requests
+// go in, decisions come out, and run_loop carries them out. Time is passed in
+// rather than read, so every rule can be exercised in a test.
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::config::{
+    AgcAction, ClippingAction, Config, ImpulseAction, LoudAction, PowerAction, ProfileConfig, TamperAction,
+};
+use crate::monitor::{CUT_OFFSET_DB, SOFT_OFFSET_DB};
+
+// default dead zone: once cut, the level has to fall this far below the cut
+// threshold before the network comes back, so a level hovering at the
+// threshold doesn't flap
+pub const HYSTERESIS_DB: f32 = 3.0;
+// without any audio for this long, a cut is lifted (mic unplugged, etc.)
+pub const NO_AUDIO_TIMEOUT: Duration = Duration::from_secs(3);
+// rms_to_db gives -999 for exact zeros; real microphones never get this low
+pub const DIGITAL_SILENCE_DB: f32 = -90.0;
+// a covered mic sits at its noise floor: no louder than ambient and varying
+// less than this across the whole tamper window
+pub const FLAT_SPREAD_DB: f32 = 1.0;
+// a minute without clipping before it's reported as over, so a loud evening
+// doesn't report it with every shout
+const CLIPPING_CLEARS_AFTER: Duration = Duration::from_secs(60);
+// the room's floor (its 10th percentile level) is measured over blocks this
+// long; automatic gain control has to hold it off the calibrated ambient for
+// AGC_BLOCKS of them in a row, which a passing noise or a shout doesn't
+const AGC_BLOCK: Duration = Duration::from_secs(30);
+const AGC_BLOCKS: usize = 4;
+// the longest a cut or pause is held for; asking for longer (a script's
+// `cut(9999999999)`) gets a day
+pub const MAX_HOLD: Duration = Duration::from_secs(24 * 60 * 60);
+
+// the tunable parts of the policy, from the config file and command line
+#[derive(Clone, Debug)]
+pub struct Rules {
+    pub soft_offset_db: f32,
+    pub cut_offset_db: f32,
+    pub dead_zone_db: f32,
+    // fixed (soft, cut) dBFS thresholds that replace the offsets
+    pub absolute: Option<(f32, f32)>,
+    // the shortest a cut lasts
+    pub penalty: Duration,
+    // unbroken time under the soft threshold before a cut is lifted
+    pub restore_quiet: Duration,
+    // report being too loud instead of cutting
+    pub warn_only: bool,
+    // a warning this long before a loud room is cut; zero cuts right away
+    pub countdown: Duration,
+    // unbroken quiet time that earns a reward
+    pub reward_every: Option<Duration>,
+    // the shortest a cut for a spoken keyword lasts
+    pub keyword_cut: Duration,
+    // how long the spoken safe word pauses for
+    pub safe_pause: Duration,
+    // loudness is left to the script: only its cuts count
+    pub scripted: bool,
+    pub tamper: Option<TamperRule>,
+    pub clipping: Option<ClippingRule>,
+    pub agc: Option<AgcRule>,
+    pub impulses: Option<ImpulseRule>,
+    pub strikes: Option<StrikeRule>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            soft_offset_db: SOFT_OFFSET_DB,
+            cut_offset_db: CUT_OFFSET_DB,
+            dead_zone_db: HYSTERESIS_DB,
+            absolute: None,
+            penalty: Duration::ZERO,
+            restore_quiet: Duration::ZERO,
+            warn_only: false,
+            countdown: Duration::ZERO,
+            reward_every: None,
+            keyword_cut: Duration::ZERO,
+            safe_pause: Duration::ZERO,
+            scripted: false,
+            tamper: None,
+            clipping: None,
+            agc: None,
+            impulses: None,
+            strikes: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TamperRule {
+    pub after: Duration,
+    pub cut: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClippingRule {
+    // share of a window's samples at full scale, 0-1
+    pub max_fraction: f32,
+    // clipped windows read low, so don't go by them at all
+    pub skip: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct AgcRule {
+    pub max_drift_db: f32,
+    // move the ambient level (and the thresholds) along with the drift
+    pub compensate: bool,
+}
+
+// isolated impulses don't count as loud
+#[derive(Clone, Debug)]
+pub struct ImpulseRule {
+    pub max_ignored: usize,
+    pub within: Duration,
+}
+
+// this many cuts within `within` is a strike-out (see power.rs)
+#[derive(Clone, Debug)]
+pub struct StrikeRule {
+    pub strikes: usize,
+    pub within: Duration,
+}
+
+impl Rules {
+    pub fn from_config(config: &Config) -> Rules {
+        let tamper = (config.tamper.action != TamperAction::Off).then(|| TamperRule {
+            after: Duration::from_secs(config.tamper.after_secs),
+            cut: config.tamper.action == TamperAction::Cut,
+        });
+        let clipping = (config.clipping.action != ClippingAction::Off).then(|| ClippingRule {
+            max_fraction: config.clipping.max_pct / 100.0,
+            skip: config.clipping.action == ClippingAction::Skip,
+        });
+        // fixed thresholds have no calibrated ambient to drift from
+        let agc = (config.agc.action != AgcAction::Off && config.soft_db.is_none()).then(|| AgcRule {
+            max_drift_db: config.agc.max_drift_db,
+            compensate: config.agc.action == AgcAction::Compensate,
+        });
+        let impulses = (config.impulses.action == ImpulseAction::Ignore).then(|| ImpulseRule {
+            max_ignored: config.impulses.max_ignored,
+            within: Duration::from_secs(config.impulses.within_secs),
+        });
+        let strikes = (config.power.action != PowerAction::Off).then(|| StrikeRule {
+            strikes: config.power.strikes,
+            within: Duration::from_secs(config.power.within_mins * 60),
+        });
+        Rules {
+            soft_offset_db: config.soft_offset_db.unwrap_or(SOFT_OFFSET_DB),
+            cut_offset_db: config.cut_offset_db.unwrap_or(CUT_OFFSET_DB),
+            dead_zone_db: config.dead_zone_db.unwrap_or(HYSTERESIS_DB),
+            absolute: config.soft_db.zip(config.cut_db).map(|(soft, cut)| to_dbfs(config, soft, cut)),
+            penalty: Duration::from_secs(config.penalty_secs.unwrap_or(0)),
+            restore_quiet: Duration::from_secs(config.restore_quiet_secs.unwrap_or(0)),
+            warn_only: false,
+            countdown: Duration::from_secs(config.countdown.secs),
+            reward_every: (config.reward.every_mins > 0).then(|| Duration::from_secs(config.reward.every_mins * 60)),
+            keyword_cut: Duration::from_secs(config.speech.as_ref().map_or(0, |s| s.cut_secs)),
+            safe_pause: Duration::from_secs(config.speech.as_ref().map_or(0, |s| s.safe_pause_mins * 60)),
+            scripted: config.script.as_ref().is_some_and(|s| s.replace_rules),
+            tamper,
+            clipping,
+            agc,
+            impulses,
+            strikes,
+        }
+    }
+
+    // (soft, cut) thresholds relative to the ambient level, unless fixed
+    pub fn thresholds(&self, ambient_db: f32) -> (f32, f32) {
+        self.absolute.unwrap_or((ambient_db + self.soft_offset_db, ambient_db + self.cut_offset_db))
+    }
+
+    // these rules with a profile's settings on top
+    pub fn with_profile(&self, profile: &ProfileConfig) -> Rules {
+        Rules {
+            soft_offset_db: profile.soft_offset_db.unwrap_or(self.soft_offset_db),
+            cut_offset_db: profile.cut_offset_db.unwrap_or(self.cut_offset_db),
+            dead_zone_db: profile.dead_zone_db.unwrap_or(self.dead_zone_db),
+            penalty: profile.penalty_secs.map_or(self.penalty, Duration::from_secs),
+            restore_quiet: profile.restore_quiet_secs.map_or(self.restore_quiet, Duration::from_secs),
+            warn_only: profile.action.map_or(self.warn_only, |a| a == LoudAction::Warn),
+            ..self.clone()
+        }
+    }
+
+    // the ambient level that fixed thresholds imply, for what still needs
+    // one (tamper detection, `status`) when calibration is skipped
+    pub fn nominal_ambient(&self) -> Option<f32> {
+        self.absolute.map(|(soft, _)| soft - self.soft_offset_db)
+    }
+}
+
+// absolute thresholds are in dB SPL when the config gives the conversion
+pub fn to_dbfs(config: &Config, soft: f32, cut: f32) -> (f32, f32) {
+    let offset = config.spl_offset_db.unwrap_or(0.0);
+    (soft - offset, cut - offset)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Enforce,
+    Restore(Reason),
+    // too loud, but the rules say to only report it
+    Warn,
+    // quiet for this long without a break
+    Reward(Duration),
+    // report only; whether it also cuts comes as a separate Enforce
+    Tamper(Tamper),
+    TamperCleared,
+    // the microphone gain is too high; report only
+    Clipping,
+    ClippingCleared,
+    // the room's level has settled this many dB off the calibrated ambient,
+    // the way automatic gain control moves it; compensated means the
+    // thresholds have moved with it
+    GainDrift { db: i32, compensated: bool },
+    // the cut just made was one too many (see StrikeRule)
+    StruckOut,
+    // loud enough to cut, in this many seconds unless it quiets down;
+    // once when it starts and again as each second passes
+    Countdown(u64),
+    // it quieted down in time
+    CountdownCancelled,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    Quiet,
+    NoAudio,
+    Paused,
+    // `ctl restore`
+    Forced,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tamper {
+    // no samples at all (unplugged, device disabled)
+    NoAudio,
+    // samples, but exact zeros (muted in the OS mixer)
+    Muted,
+    // a flat level at the noise floor (covered)
+    Flat,
+}
+
+impl fmt::Display for Tamper {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Tamper::NoAudio => "no audio from the microphone (unplugged or disabled?)",
+            Tamper::Muted => "the microphone is muted (digital silence)",
+            Tamper::Flat => "the microphone level is flat at the noise floor (covered?)",
+        })
+    }
+}
+
+pub struct Policy {
+    pub ambient_db: f32,
+    pub min_db: f32,
+    pub max_db: f32,
+    paused: bool,
+    // end of a timed pause (the safe word); None for an open-ended one
+    paused_until: Option<Instant>,
+    enforcing: bool,
+    // when the current cut started, for the penalty
+    cut_at: Option<Instant>,
+    // a keyword cut lasts until at least then, however quiet it gets
+    held_until: Option<Instant>,
+    // since when a cut has been under the soft threshold without a break
+    calm_since: Option<Instant>,
+    // too loud under warn-only rules, reported once per episode
+    warned: bool,
+    // when a countdown to a cut runs out, and the last second announced
+    countdown: Option<(Instant, u64)>,
+    // start of the current quiet streak, and of the part not yet rewarded
+    quiet_since: Option<(Instant, Instant)>,
+    pct: i32,
+    last_level: Instant,
+    rules: Rules,
+    // start and level range of the current run of floor-level windows
+    floor_since: Option<(Instant, f32, f32)>,
+    tampered: Option<Tamper>,
+    // the last window that clipped while clipping is reported, and whether
+    // the one about to be stepped did
+    clipped_at: Option<Instant>,
+    clipped: bool,
+    // start, levels and windows without audio of the current AGC block, the
+    // drift of each block in a row that was off the same way, and whether
+    // that run has been reported
+    gain_block: (Instant, Vec<f32>, usize),
+    drifts: Vec<f32>,
+    drift_reported: bool,
+    // recent loud impulses, and whether the window about to be stepped is one
+    impulses: Vec<Instant>,
+    impulsive: bool,
+    // the window about to be stepped wasn't the enrolled speaker
+    someone_else: bool,
+    // recent cuts, for the strike rule
+    cuts: Vec<Instant>,
+}
+
+impl Policy {
+    pub fn new(ambient_db: f32, rules: Rules, now: Instant) -> Policy {
+        let (min_db, max_db) = rules.thresholds(ambient_db);
+        Policy {
+            ambient_db,
+            min_db,
+            max_db,
+            paused: false,
+            paused_until: None,
+            enforcing: false,
+            cut_at: None,
+            held_until: None,
+            calm_since: None,
+            warned: false,
+            countdown: None,
+            quiet_since: None,
+            pct: 100,
+            last_level: now,
+            rules,
+            floor_since: None,
+            tampered: None,
+            clipped_at: None,
+            clipped: false,
+            gain_block: (now, Vec::new(), 0),
+            drifts: Vec::new(),
+            drift_reported: false,
+            impulses: Vec::new(),
+            impulsive: false,
+            someone_else: false,
+            cuts: Vec::new(),
+        }
+    }
+
+    pub fn recalibrate(&mut self, ambient_db: f32) {
+        self.ambient_db = ambient_db;
+        (self.min_db, self.max_db) = self.rules.thresholds(ambient_db);
+        self.gain_block = (self.gain_block.0, Vec::new(), 0);
+        self.drifts.clear();
+        self.drift_reported = false;
+    }
+
+    // switch thresholds and penalty (a profile change); a cut in progress
+    // is judged by the new ones from the next window on
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        (self.min_db, self.max_db) = self.rules.thresholds(self.ambient_db);
+    }
+
+    // how much of the coming window was at full scale (0-1); reported when it
+    // starts and once it has stopped for a while. Call before `step`.
+    pub fn clipping(&mut self, fraction: f32, now: Instant) -> Vec<Decision> {
+        let Some(rule) = &self.rules.clipping else { return Vec::new() };
+        self.clipped = fraction > rule.max_fraction;
+        if self.clipped {
+            let started = self.clipped_at.replace(now).is_none();
+            return if started { vec![Decision::Clipping] } else { Vec::new() };
+        }
+        if self.clipped_at.is_some_and(|at| now.duration_since(at) >= CLIPPING_CLEARS_AFTER) {
+            self.clipped_at = None;
+            return vec![Decision::ClippingCleared];
+        }
+        Vec::new()
+    }
+
+    // the coming window's loudness was a short impulse rather than noise that
+    // keeps up. Call before `step`.
+    pub fn impulse(&mut self) {
+        self.impulsive = self.rules.impulses.is_some();
+    }
+
+    // the coming window sounded like someone other than the enrolled speaker
+    // (see voice.rs), so its loudness isn't theirs. Call before `step`.
+    pub fn someone_else(&mut self) {
+        self.someone_else = true;
+    }
+
+    // a loud impulse with few others recently, which the rules let go
+    fn ignored_impulse(&mut self, db: f32, now: Instant) -> bool {
+        let (Some(rule), true) = (&self.rules.impulses, std::mem::take(&mut self.impulsive)) else { return false };
+        if db < self.max_db {
+            return false;
+        }
+        self.impulses.retain(|&at| now.duration_since(at) < rule.within);
+        self.impulses.push(now);
+        self.impulses.len() <= rule.max_ignored
+    }
+
+    // one window's level
+    pub fn step(&mut self, db: f32, now: Instant) -> Vec<Decision> {
+        self.last_level = now;
+        if self.ignored_impulse(db, now) {
+            return Vec::new();
+        }
+        // over the soft threshold, but someone else: neither loud nor quiet
+        if std::mem::take(&mut self.someone_else) && db >= self.min_db {
+            return Vec::new();
+        }
+        if self.clipped && self.rules.clipping.as_ref().is_some_and(|r| r.skip) {
+            // loud, whatever it read as
+            self.quiet_since = None;
+            return Vec::new();
+        }
+        if self.paused_until.is_some_and(|until| now >= until) {
+            self.resume();
+        }
+        self.pct = pct(db, self.min_db, self.max_db);
+        let mut decisions = self.check_floor(db, now);
+        decisions.extend(self.check_gain(db, now));
+        decisions.extend(self.track_quiet(db, now));
+        if self.paused {
+            return decisions;
+        }
+        if self.tamper_cuts() {
+            decisions.extend(self.hold(now));
+            return decisions;
+        }
+        let loud = if self.rules.scripted {
+            false
+        } else if self.enforcing {
+            db > self.max_db - self.rules.dead_zone_db
+        } else {
+            db >= self.max_db
+        };
+        self.calm_since = match self.calm_since {
+            _ if !self.enforcing || db >= self.min_db => None,
+            since => Some(since.unwrap_or(now)),
+        };
+        let served = self.cut_at.is_none_or(|at| now.duration_since(at) >= self.rules.penalty)
+            && self.held_until.is_none_or(|until| now >= until)
+            && self.restore_in(now).is_none_or(|left| left == 0);
+        match (loud, self.enforcing) {
+            (true, false) if self.rules.warn_only && !self.warned => decisions.push(Decision::Warn),
+            (true, false) if self.rules.warn_only => {}
+            (_, false) => decisions.extend(self.count_down(db, loud, now)),
+            (false, true) if served => decisions.extend(self.release(Reason::Quiet)),
+            _ => {}
+        }
+        self.warned = loud && self.rules.warn_only && !self.enforcing;
+        decisions
+    }
+
+    // a loud window starts the countdown (or, without one, cuts); it keeps
+    // going while the level stays over the soft threshold and cuts when it
+    // runs out
+    fn count_down(&mut self, db: f32, loud: bool, now: Instant) -> Vec<Decision> {
+        let until = match self.countdown {
+            None if !loud => return Vec::new(),
+            None if self.rules.countdown.is_zero() => return self.hold(now),
+            None => now + self.rules.countdown,
+            Some(_) if db < self.min_db || self.rules.warn_only => {
+                self.countdown = None;
+                return vec![Decision::CountdownCancelled];
+            }
+            Some((until, _)) => until,
+        };
+        if now >= until {
+            return self.hold(now);
+        }
+        let left = until.duration_since(now).as_secs_f32().ceil() as u64;
+        if self.countdown.replace((until, left)).is_some_and(|(_, announced)| announced == left) {
+            return Vec::new();
+        }
+        vec![Decision::Countdown(left)]
+    }
+
+    // whole seconds until a cut has served its penalty (or a keyword's), while
+    // it hasn't
+    pub fn penalty_left(&self, now: Instant) -> Option<u64> {
+        let served = self.cut_at.filter(|_| self.enforcing)? + self.rules.penalty;
+        let left = self.held_until.map_or(served, |held| held.max(served)).saturating_duration_since(now);
+        (!left.is_zero()).then(|| left.as_secs_f32().ceil() as u64)
+    }
+
+    // whole seconds of unbroken quiet a cut still needs before it's lifted,
+    // when the rules ask for any
+    pub fn restore_in(&self, now: Instant) -> Option<u64> {
+        if !self.enforcing || self.rules.restore_quiet.is_zero() {
+            return None;
+        }
+        let calm = self.calm_since.map_or(Duration::ZERO, |since| now.duration_since(since));
+        Some(self.rules.restore_quiet.saturating_sub(calm).as_secs_f32().ceil() as u64)
+    }
+
+    // whole seconds until a cut, while counting down to one
+    pub fn cut_in(&self, now: Instant) -> Option<u64> {
+        let (until, _) = self.countdown?;
+        Some(until.saturating_duration_since(now).as_secs_f32().ceil() as u64)
+    }
+
+    // a configured word was heard: that's a violation however quietly it was
+    // said, and the cut it brings lasts at least `keyword_cut`
+    pub fn keyword(&mut self, now: Instant) -> Vec<Decision> {
+        self.cut_for(now, self.rules.keyword_cut)
+    }
+
+    // cut (or warn, under warn-only rules) for at least `min`, whatever the
+    // level; a cut already going on is extended if need be
+    pub fn cut_for(&mut self, now: Instant, min: Duration) -> Vec<Decision> {
+        if self.paused {
+            return Vec::new();
+        }
+        self.quiet_since = None;
+        if self.rules.warn_only {
+            return vec![Decision::Warn];
+        }
+        let until = now + min.min(MAX_HOLD);
+        self.held_until = Some(self.held_until.map_or(until, |t| t.max(until)));
+        self.hold(now)
+    }
+
+    // anything at or over the cut threshold (cut or not) breaks a streak,
+    // and so does a pause or a missing/tampered microphone
+    fn track_quiet(&mut self, db: f32, now: Instant) -> Option<Decision> {
+        if db >= self.max_db || self.enforcing || self.paused || self.tampered.is_some() {
+            self.quiet_since = None;
+            return None;
+        }
+        let (since, unrewarded) = *self.quiet_since.get_or_insert((now, now));
+        let every = self.rules.reward_every?;
+        if now.duration_since(unrewarded) < every {
+            return None;
+        }
+        self.quiet_since = Some((since, now));
+        Some(Decision::Reward(now.duration_since(since)))
+    }
+
+    // how long it has been quiet without a break
+    pub fn quiet_for(&self, now: Instant) -> Duration {
+        self.quiet_since.map_or(Duration::ZERO, |(since, _)| now.duration_since(since))
+    }
+
+    // a window without any audio
+    pub fn silence(&mut self, now: Instant) -> Vec<Decision> {
+        self.quiet_since = None;
+        self.gain_block.2 += 1;
+        let mut decisions = Vec::new();
+        let Some(rule) = &self.rules.tamper else {
+            if self.no_audio(now) {
+                decisions.extend(self.release(Reason::NoAudio));
+            }
+            return decisions;
+        };
+        if now.duration_since(self.last_level) >= rule.after && self.tampered.is_none() {
+            self.tampered = Some(Tamper::NoAudio);
+            decisions.push(Decision::Tamper(Tamper::NoAudio));
+        }
+        if self.paused {
+            return decisions;
+        }
+        if self.tamper_cuts() {
+            decisions.extend(self.hold(now));
+        } else if !rule.cut && self.no_audio(now) {
+            // when tampering isn't punished, a dead mic lifts the cut as before
+            decisions.extend(self.release(Reason::NoAudio));
+        }
+        decisions
+    }
+
+    // track runs of suspiciously quiet windows; any normal one ends them
+    fn check_floor(&mut self, db: f32, now: Instant) -> Vec<Decision> {
+        let Some(rule) = &self.rules.tamper else { return Vec::new() };
+        let at_floor = db <= self.ambient_db || db <= DIGITAL_SILENCE_DB;
+        let run = match self.floor_since {
+            Some((since, lo, hi)) if at_floor && hi.max(db) - lo.min(db) <= FLAT_SPREAD_DB => {
+                Some((since, lo.min(db), hi.max(db)))
+            }
+            _ if at_floor => Some((now, db, db)),
+            _ => None,
+        };
+        self.floor_since = run;
+
+        let tamper = match run {
+            Some((since, _, hi)) if now.duration_since(since) >= rule.after => {
+                Some(if hi <= DIGITAL_SILENCE_DB { Tamper::Muted } else { Tamper::Flat })
+            }
+            _ => None,
+        };
+        match (tamper, self.tampered) {
+            (Some(t), None) => {
+                self.tampered = Some(t);
+                vec![Decision::Tamper(t)]
+            }
+            (None, Some(_)) => {
+                self.tampered = None;
+                vec![Decision::TamperCleared]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // the room's floor, block by block, against the calibrated ambient; a
+    // muted or dead microphone is tampering, not gain
+    fn check_gain(&mut self, db: f32, now: Instant) -> Option<Decision> {
+        let rule = self.rules.agc.as_ref()?;
+        match db > DIGITAL_SILENCE_DB {
+            true => self.gain_block.1.push(db),
+            false => self.gain_block.2 += 1,
+        }
+        if now.duration_since(self.gain_block.0) < AGC_BLOCK {
+            return None;
+        }
+        let (_, mut levels, silent) = std::mem::replace(&mut self.gain_block, (now, Vec::new(), 0));
+        // a block spent mostly without audio says nothing either way
+        if levels.len() <= silent {
+            return None;
+        }
+        levels.sort_by(f32::total_cmp);
+        let drift = levels[levels.len() / 10] - self.ambient_db;
+        let same_way = self.drifts.last().is_none_or(|d| d.signum() == drift.signum());
+        if drift.abs() < rule.max_drift_db || !same_way {
+            self.drifts.clear();
+            self.drift_reported = false;
+        }
+        if drift.abs() < rule.max_drift_db {
+            return None;
+        }
+        self.drifts.push(drift);
+        if self.drifts.len() < AGC_BLOCKS || self.drift_reported {
+            return None;
+        }
+        let drift = self.drifts.iter().sum::<f32>() / self.drifts.len() as f32;
+        let compensated = rule.compensate;
+        if compensated {
+            self.recalibrate(self.ambient_db + drift);
+        } else {
+            self.drift_reported = true;
+        }
+        Some(Decision::GainDrift { db: drift.round() as i32, compensated })
+    }
+
+    fn tamper_cuts(&self) -> bool {
+        self.tampered.is_some() && self.rules.tamper.as_ref().is_some_and(|r| r.cut)
+    }
+
+    fn hold(&mut self, now: Instant) -> Vec<Decision> {
+        if self.enforcing {
+            return Vec::new();
+        }
+        self.enforcing = true;
+        self.cut_at = Some(now);
+        self.countdown = None;
+        let mut decisions = vec![Decision::Enforce];
+        decisions.extend(self.strike(now));
+        decisions
+    }
+
+    // every kind of cut counts, and a strike-out starts the count afresh
+    fn strike(&mut self, now: Instant) -> Option<Decision> {
+        let rule = self.rules.strikes.as_ref()?;
+        self.cuts.retain(|&at| now.duration_since(at) < rule.within);
+        self.cuts.push(now);
+        if self.cuts.len() < rule.strikes {
+            return None;
+        }
+        self.cuts.clear();
+        Some(Decision::StruckOut)
+    }
+
+    pub fn tampered(&self) -> Option<Tamper> {
+        self.tampered
+    }
+
+    pub fn no_audio(&self, now: Instant) -> bool {
+        now.duration_since(self.last_level) > NO_AUDIO_TIMEOUT
+    }
+
+    pub fn pause(&mut self) -> Vec<Decision> {
+        self.paused = true;
+        self.paused_until = None;
+        self.countdown = None;
+        self.release(Reason::Paused)
+    }
+
+    // the safe word: lift any cut and pause for `safe_pause`, then carry on
+    pub fn safe_word(&mut self, now: Instant) -> Vec<Decision> {
+        self.pause_for(now, self.rules.safe_pause)
+    }
+
+    pub fn pause_for(&mut self, now: Instant, duration: Duration) -> Vec<Decision> {
+        let decisions = self.pause();
+        self.paused_until = Some(now + duration.min(MAX_HOLD));
+        decisions
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.paused_until = None;
+    }
+
+    // lift a cut now; the next loud window cuts again
+    pub fn restore(&mut self) -> Vec<Decision> {
+        self.release(Reason::Forced)
+    }
+
+    fn release(&mut self, reason: Reason) -> Vec<Decision> {
+        if !self.enforcing {
+            return Vec::new();
+        }
+        self.enforcing = false;
+        self.cut_at = None;
+        self.held_until = None;
+        self.calm_since = None;
+        vec![Decision::Restore(reason)]
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn enforcing(&self) -> bool {
+        self.enforcing
+    }
+
+    // 100% at or below the soft threshold, 0% at the cut threshold
+    pub fn pct(&self) -> i32 {
+        self.pct
+    }
+
+    // how far past the soft threshold the level is, 0-100, for actions that
+    // degrade the connection instead of cutting it; full during a cut, nothing
+    // while paused or when being loud doesn't cut
+    pub fn shaping(&self) -> u8 {
+        if self.enforcing {
+            100
+        } else if self.paused || self.rules.warn_only || self.rules.scripted {
+            0
+        } else {
+            (100 - self.pct).clamp(0, 100) as u8
+        }
+    }
+
+    pub fn state(&self) -> String {
+        if self.paused {
+            "PAUSED".to_string()
+        } else if self.enforcing {
+            "CUT".to_string()
+        } else if self.countdown.is_some() {
+            "COUNTDOWN".to_string()
+        } else {
+            format!("OK {}%", self.pct)
+        }
+    }
+}
+
+fn pct(db: f32, min_db: f32, max_db: f32) -> i32 {
+    if db <= min_db {
+        100
+    } else if db >= max_db {
+        0
+    } else {
+        let v = 1.0 - (db - min_db) / (max_db - min_db);
+        (100.0 * v).round() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMBIENT: f32 = -60.0;
+    const SOFT: f32 = AMBIENT + SOFT_OFFSET_DB;
+    const CUT: f32 = AMBIENT + CUT_OFFSET_DB;
+
+    fn policy() -> (Policy, Instant) {
+        let t0 = Instant::now();
+        (Policy::new(AMBIENT, Rules::default(), t0), t0)
+    }
+
+    fn tamper_policy(cut: bool) -> (Policy, Instant) {
+        let t0 = Instant::now();
+        let rules = Rules { tamper: Some(TamperRule { after: Duration::from_secs(10), cut }), ..Rules::default() };
+        (Policy::new(AMBIENT, rules, t0), t0)
+    }
+
+    // one window every 500ms from `from` for `secs` seconds
+    fn feed(p: &mut Policy, t0: Instant, from: u64, secs: u64, db: impl Fn(u64) -> f32) -> Vec<Decision> {
+        (from..from + secs * 2).flat_map(|i| p.step(db(i), ms(t0, i * 500))).collect()
+    }
+
+    fn ms(t0: Instant, ms: u64) -> Instant {
+        t0 + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn thresholds_follow_ambient() {
+        let (p, _) = policy();
+        assert_eq!((p.min_db, p.max_db), (SOFT, CUT));
+    }
+
+    #[test]
+    fn pct_scales_between_thresholds() {
+        let (mut p, t0) = policy();
+        p.step(AMBIENT, t0);
+        assert_eq!(p.pct(), 100);
+        p.step(SOFT, t0);
+        assert_eq!(p.pct(), 100);
+        p.step((SOFT + CUT) / 2.0, t0);
+        assert_eq!(p.pct(), 50);
+        assert_eq!(p.state(), "OK 50%");
+    }
+
+    #[test]
+    fn shaping_grows_past_the_soft_threshold() {
+        let (mut p, t0) = policy();
+        p.step(AMBIENT, t0);
+        assert_eq!(p.shaping(), 0);
+        p.step(SOFT + (CUT - SOFT) * 0.75, t0);
+        assert_eq!(p.shaping(), 75);
+        p.step(CUT, ms(t0, 500));
+        assert_eq!(p.shaping(), 100);
+        p.pause();
+        assert_eq!(p.shaping(), 0);
+    }
+
+    #[test]
+    fn cuts_at_threshold_only() {
+        let (mut p, t0) = policy();
+        assert!(p.step(CUT - 0.1, t0).is_empty());
+        assert_eq!(p.step(CUT, ms(t0, 500)), vec![Decision::Enforce]);
+        assert!(p.enforcing());
+        // staying loud doesn't repeat the decision
+        assert!(p.step(CUT + 10.0, ms(t0, 1000)).is_empty());
+    }
+
+    #[test]
+    fn hysteresis_holds_cut_until_clearly_quieter() {
+        let (mut p, t0) = policy();
+        p.step(CUT, t0);
+        assert!(p.step(CUT - HYSTERESIS_DB + 0.5, ms(t0, 500)).is_empty());
+        assert!(p.enforcing());
+        assert_eq!(p.step(CUT - HYSTERESIS_DB - 0.5, ms(t0, 1000)), vec![Decision::Restore(Reason::Quiet)]);
+        assert!(!p.enforcing());
+        // and re-cutting needs the full threshold again
+        assert!(p.step(CUT - 0.5, ms(t0, 1500)).is_empty());
+    }
+
+    #[test]
+    fn offsets_and_dead_zone_are_configurable() {
+        let t0 = Instant::now();
+        let rules = Rules { soft_offset_db: 10.0, cut_offset_db: 20.0, dead_zone_db: 6.0, ..Rules::default() };
+        let mut p = Policy::new(AMBIENT, rules, t0);
+        assert_eq!((p.min_db, p.max_db), (AMBIENT + 10.0, AMBIENT + 20.0));
+        assert_eq!(p.step(AMBIENT + 20.0, t0), vec![Decision::Enforce]);
+        assert!(p.step(AMBIENT + 15.0, ms(t0, 500)).is_empty());
+        assert_eq!(p.step(AMBIENT + 13.5, ms(t0, 1000)), vec![Decision::Restore(Reason::Quiet)]);
+    }
+
+    #[test]
+    fn absolute_thresholds_ignore_ambient() {
+        let t0 = Instant::now();
+        let rules = Rules { absolute: Some((-40.0, -20.0)), ..Rules::default() };
+        let mut p = Policy::new(AMBIENT, rules, t0);
+        assert_eq!((p.min_db, p.max_db), (-40.0, -20.0));
+        p.recalibrate(-30.0);
+        assert_eq!((p.min_db, p.max_db), (-40.0, -20.0));
+        assert_eq!(p.step(-20.0, t0), vec![Decision::Enforce]);
+    }
+
+    #[test]
+    fn penalty_keeps_a_short_cut_going() {
+        let t0 = Instant::now();
+        let mut p = Policy::new(AMBIENT, Rules { penalty: Duration::from_secs(5), ..Rules::default() }, t0);
+        p.step(CUT, t0);
+        assert!(feed(&mut p, t0, 1, 2, |_| AMBIENT).is_empty());
+        assert!(p.enforcing());
+        assert_eq!(p.penalty_left(ms(t0, 2500)), Some(3));
+        assert_eq!(feed(&mut p, t0, 5, 3, |_| AMBIENT), vec![Decision::Restore(Reason::Quiet)]);
+        assert_eq!(p.penalty_left(ms(t0, 5500)), None);
+        // control requests don't wait for it
+        p.step(CUT, ms(t0, 10_000));
+        assert_eq!(p.restore(), vec![Decision::Restore(Reason::Forced)]);
+    }
+
+    #[test]
+    fn profiles_switch_thresholds_and_penalty() {
+        let t0 = Instant::now();
+        let mut p = Policy::new(AMBIENT, Rules::default(), t0);
+        let strict = ProfileConfig { cut_offset_db: Some(25.0), penalty_secs: Some(10), ..ProfileConfig::default() };
+        p.set_rules(Rules::default().with_profile(&strict));
+        assert_eq!((p.min_db, p.max_db), (SOFT, AMBIENT + 25.0));
+        assert_eq!(p.step(AMBIENT + 25.0, t0), vec![Decision::Enforce]);
+        assert!(p.step(AMBIENT, ms(t0, 9_000)).is_empty());
+        assert_eq!(p.step(AMBIENT, ms(t0, 10_000)), vec![Decision::Restore(Reason::Quiet)]);
+    }
+
+    #[test]
+    fn warn_only_profiles_report_once_and_never_cut() {
+        let (mut p, t0) = policy();
+        let lenient = ProfileConfig { action: Some(LoudAction::Warn), ..ProfileConfig::default() };
+        p.set_rules(Rules::default().with_profile(&lenient));
+        assert_eq!(p.step(CUT, t0), vec![Decision::Warn]);
+        assert!(p.step(CUT + 5.0, ms(t0, 500)).is_empty());
+        assert!(!p.enforcing());
+        p.step(AMBIENT, ms(t0, 1000));
+        assert_eq!(p.step(CUT, ms(t0, 1500)), vec![Decision::Warn]);
+    }
+
+    #[test]
+    fn quiet_streaks_earn_rewards_and_loudness_breaks_them() {
+        let t0 = Instant::now();
+        let rules = Rules { reward_every: Some(Duration::from_secs(10)), ..Rules::default() };
+        let mut p = Policy::new(AMBIENT, rules, t0);
+        let rewards = |d: Vec<Decision>| d.into_iter().filter(|d| matches!(d, Decision::Reward(_))).collect::<Vec<_>>();
+        assert_eq!(rewards(feed(&mut p, t0, 0, 21, |_| AMBIENT)), vec![
+            Decision::Reward(Duration::from_secs(10)),
+            Decision::Reward(Duration::from_secs(20)),
+        ]);
+        // a cut starts it over
+        p.step(CUT, ms(t0, 21_500));
+        assert_eq!(p.quiet_for(ms(t0, 21_500)), Duration::ZERO);
+        p.step(CUT - 10.0, ms(t0, 22_000));
+        assert!(rewards(feed(&mut p, t0, 45, 9, |_| AMBIENT)).is_empty());
+    }
+
+    #[test]
+    fn keywords_cut_however_quiet_it_is() {
+        let t0 = Instant::now();
+        let mut p = Policy::new(AMBIENT, Rules { keyword_cut: Duration::from_secs(30), ..Rules::default() }, t0);
+        p.step(AMBIENT, t0);
+        assert_eq!(p.keyword(t0), vec![Decision::Enforce]);
+        assert!(feed(&mut p, t0, 1, 29, |_| AMBIENT).is_empty());
+        assert_eq!(p.step(AMBIENT, ms(t0, 30_000)), vec![Decision::Restore(Reason::Quiet)]);
+        // only reported under warn-only rules, and ignored while paused
+        p.set_rules(Rules { warn_only: true, ..Rules::default() });
+        assert_eq!(p.keyword(ms(t0, 31_000)), vec![Decision::Warn]);
+        p.pause();
+        assert!(p.keyword(ms(t0, 32_000)).is_empty());
+    }
+
+    #[test]
+    fn the_safe_word_pauses_for_a_while() {
+        let t0 = Instant::now();
+        let mut p = Policy::new(AMBIENT, Rules { safe_pause: Duration::from_secs(60), ..Rules::default() }, t0);
+        p.step(CUT, t0);
+        assert_eq!(p.safe_word(ms(t0, 500)), vec![Decision::Restore(Reason::Paused)]);
+        assert!(p.step(CUT, ms(t0, 30_000)).is_empty());
+        assert!(p.paused());
+        assert_eq!(p.step(CUT, ms(t0, 61_000)), vec![Decision::Enforce]);
+        // a plain pause has no end
+        p.pause();
+        assert!(p.step(CUT, ms(t0, 999_000)).is_empty());
+    }
+
+    #[test]
+    fn scripted_rules_leave_cutting_to_the_script() {
+        let t0 = Instant::now();
+        let mut p = Policy::new(AMBIENT, Rules { scripted: true, ..Rules::default() }, t0);
+        assert!(p.step(CUT + 10.0, t0).is_empty());
+        assert_eq!(p.cut_for(ms(t0, 500), Duration::from_secs(2)), vec![Decision::Enforce]);
+        assert!(p.step(CUT + 10.0, ms(t0, 1000)).is_empty());
+        assert_eq!(p.step(CUT + 10.0, ms(t0, 2500)), vec![Decision::Restore(Reason::Quiet)]);
+        // forever is a day
+        assert_eq!(p.cut_for(ms(t0, 3000), Duration::MAX), vec![Decision::Enforce]);
+        assert_eq!(p.held_until, Some(ms(t0, 3000) + MAX_HOLD));
+        p.pause_for(ms(t0, 3000), Duration::MAX);
+        assert_eq!(p.paused_until, Some(ms(t0, 3000) + MAX_HOLD));
+    }
+
+    #[test]
+    fn silence_restores_after_timeout() {
+        let (mut p, t0) = policy();
+        p.step(CUT, t0);
+        assert!(p.silence(ms(t0, 2900)).is_empty());
+        assert!(!p.no_audio(ms(t0, 2900)));
+        assert_eq!(p.silence(ms(t0, 3100)), vec![Decision::Restore(Reason::NoAudio)]);
+        assert!(p.no_audio(ms(t0, 3100)));
+        assert!(p.silence(ms(t0, 3600)).is_empty());
+    }
+
+    #[test]
+    fn silence_without_cut_decides_nothing() {
+        let (mut p, t0) = policy();
+        assert!(p.silence(ms(t0, 10_000)).is_empty());
+    }
+
+    #[test]
+    fn forced_restore_lasts_until_next_loud_window() {
+        let (mut p, t0) = policy();
+        p.step(CUT, t0);
+        assert_eq!(p.restore(), vec![Decision::Restore(Reason::Forced)]);
+        assert!(p.restore().is_empty());
+        assert_eq!(p.step(CUT, ms(t0, 500)), vec![Decision::Enforce]);
+    }
+
+    #[test]
+    fn pause_restores_and_suppresses_cuts() {
+        let (mut p, t0) = policy();
+        p.step(CUT, t0);
+        assert_eq!(p.pause(), vec![Decision::Restore(Reason::Paused)]);
+        assert!(p.step(CUT + 10.0, ms(t0, 500)).is_empty());
+        assert_eq!(p.state(), "PAUSED");
+        p.resume();
+        assert_eq!(p.step(CUT, ms(t0, 1000)), vec![Decision::Enforce]);
+    }
+
+    #[test]
+    fn muted_mic_is_reported() {
+        let (mut p, t0) = tamper_policy(false);
+        assert!(feed(&mut p, t0, 0, 9, |_| -999.0).is_empty());
+        assert_eq!(feed(&mut p, t0, 18, 2, |_| -999.0), vec![Decision::Tamper(Tamper::Muted)]);
+        assert!(!p.enforcing());
+        assert_eq!(p.step(AMBIENT + 5.0, ms(t0, 11_000)), vec![Decision::TamperCleared]);
+    }
+
+    #[test]
+    fn covered_mic_cuts_when_configured() {
+        let (mut p, t0) = tamper_policy(true);
+        let flat = |i: u64| AMBIENT - 8.0 + (i % 3) as f32 * 0.3;
+        let decisions = feed(&mut p, t0, 0, 11, flat);
+        assert_eq!(decisions, vec![Decision::Tamper(Tamper::Flat), Decision::Enforce]);
+        assert_eq!(p.tampered(), Some(Tamper::Flat));
+        // uncovered: a normal level clears it and lifts the cut
+        assert_eq!(
+            p.step(AMBIENT + 5.0, ms(t0, 12_000)),
+            vec![Decision::TamperCleared, Decision::Restore(Reason::Quiet)]
+        );
+    }
+
+    #[test]
+    fn normal_quiet_room_is_not_tampering() {
+        let (mut p, t0) = tamper_policy(true);
+        // quiet, but varying by a few dB like a real room
+        let room = |i: u64| AMBIENT - (i % 5) as f32 * 1.5;
+        assert!(feed(&mut p, t0, 0, 60, room).is_empty());
+    }
+
+    #[test]
+    fn unplugged_mic_keeps_the_cut_when_tamper_cuts() {
+        let (mut p, t0) = tamper_policy(true);
+        p.step(CUT, t0);
+        // the plain no-audio rule would restore here
+        assert!(p.silence(ms(t0, 5_000)).is_empty());
+        assert!(p.enforcing());
+        assert_eq!(p.silence(ms(t0, 10_000)), vec![Decision::Tamper(Tamper::NoAudio)]);
+        assert!(p.silence(ms(t0, 20_000)).is_empty());
+        assert!(p.enforcing());
+    }
+
+    #[test]
+    fn unplugged_mic_is_reported_after_restore_when_warning() {
+        let (mut p, t0) = tamper_policy(false);
+        p.step(CUT, t0);
+        assert_eq!(p.silence(ms(t0, 3_100)), vec![Decision::Restore(Reason::NoAudio)]);
+        assert_eq!(p.silence(ms(t0, 10_000)), vec![Decision::Tamper(Tamper::NoAudio)]);
+        assert_eq!(p.step(AMBIENT, ms(t0, 10_500)), vec![Decision::TamperCleared]);
+    }
+
+    #[test]
+    fn clipping_is_reported_and_skipped() {
+        let t0 = Instant::now();
+        let rules = Rules { clipping: Some(ClippingRule { max_fraction: 0.01, skip: true }), ..Rules::default() };
+        let mut p = Policy::new(AMBIENT, rules, t0);
+        assert_eq!(p.clipping(0.05, t0), vec![Decision::Clipping]);
+        // a clipped window is neither judged nor counted as quiet
+        assert!(p.step(CUT + 10.0, t0).is_empty());
+        assert_eq!(p.state(), "OK 100%");
+        assert!(p.clipping(0.05, ms(t0, 500)).is_empty());
+        assert!(p.clipping(0.0, ms(t0, 30_000)).is_empty());
+        assert_eq!(p.step(CUT, ms(t0, 30_000)), vec![Decision::Enforce]);
+        assert_eq!(p.clipping(0.0, ms(t0, 60_500)), vec![Decision::ClippingCleared]);
+        assert!(p.clipping(0.0, ms(t0, 61_000)).is_empty());
+    }
+
+    #[test]
+    fn settled_gain_drift_is_reported_or_compensated() {
+        for compensate in [false, true] {
+            let t0 = Instant::now();
+            let rules = Rules { agc: Some(AgcRule { max_drift_db: 6.0, compensate }), ..Rules::default() };
+            let mut p = Policy::new(AMBIENT, rules, t0);
+            let drifts = |decisions: Vec<Decision>| -> Vec<Decision> {
+                decisions.into_iter().filter(|d| matches!(d, Decision::GainDrift { .. })).collect()
+            };
+            assert!(feed(&mut p, t0, 0, 60, |_| AMBIENT).is_empty());
+            // shouts are over long before a block ends
+            assert!(drifts(feed(&mut p, t0, 120, 60, |i| if i % 20 == 0 { CUT + 5.0 } else { AMBIENT })).is_empty());
+            let drifted = drifts(feed(&mut p, t0, 240, 150, |_| AMBIENT + 10.0));
+            assert_eq!(drifted, vec![Decision::GainDrift { db: 10, compensated: compensate }]);
+            let ambient = if compensate { AMBIENT + 10.0 } else { AMBIENT };
+            assert_eq!((p.ambient_db, p.min_db), (ambient, ambient + SOFT_OFFSET_DB));
+        }
+    }
+
+    #[test]
+    fn repeated_cuts_strike_out() {
+        let t0 = Instant::now();
+        let strikes = Some(StrikeRule { strikes: 3, within: Duration::from_secs(60) });
+        let mut p = Policy::new(AMBIENT, Rules { strikes, ..Rules::default() }, t0);
+        let mut cut = |at: u64| {
+            let decisions = p.step(CUT, ms(t0, at));
+            assert_eq!(p.step(AMBIENT, ms(t0, at + 500)), vec![Decision::Restore(Reason::Quiet)]);
+            decisions
+        };
+        assert_eq!(cut(0), vec![Decision::Enforce]);
+        assert_eq!(cut(10_000), vec![Decision::Enforce]);
+        assert_eq!(cut(20_000), vec![Decision::Enforce, Decision::StruckOut]);
+        // counted afresh after a strike-out, and old cuts drop out of the count
+        assert_eq!(cut(30_000), vec![Decision::Enforce]);
+        assert_eq!(cut(100_000), vec![Decision::Enforce]);
+        assert_eq!(cut(140_000), vec![Decision::Enforce]);
+        assert_eq!(cut(150_000), vec![Decision::Enforce, Decision::StruckOut]);
+    }
+
+    #[test]
+    fn restoring_waits_for_a_quiet_streak() {
+        let t0 = Instant::now();
+        let mut p = Policy::new(AMBIENT, Rules { restore_quiet: Duration::from_secs(5), ..Rules::default() }, t0);
+        assert_eq!(p.step(CUT, t0), vec![Decision::Enforce]);
+        assert_eq!(p.restore_in(t0), Some(5));
+        assert!(feed(&mut p, t0, 1, 3, |_| AMBIENT).is_empty());
+        assert_eq!(p.restore_in(ms(t0, 3000)), Some(3));
+        // over the soft threshold, though under the cut one, starts it over
+        assert!(p.step(SOFT + 1.0, ms(t0, 3500)).is_empty());
+        assert_eq!(p.restore_in(ms(t0, 3500)), Some(5));
+        assert!(feed(&mut p, t0, 8, 4, |_| AMBIENT).is_empty());
+        assert_eq!(p.step(AMBIENT, ms(t0, 9000)), vec![Decision::Restore(Reason::Quiet)]);
+        assert_eq!(p.restore_in(ms(t0, 9000)), None);
+    }
+
+    #[test]
+    fn countdown_comes_before_the_cut() {
+        let t0 = Instant::now();
+        let mut p = Policy::new(AMBIENT, Rules { countdown: Duration::from_secs(3), ..Rules::default() }, t0);
+        assert_eq!(p.step(CUT, t0), vec![Decision::Countdown(3)]);
+        assert_eq!(p.state(), "COUNTDOWN");
+        assert!(p.step(CUT, ms(t0, 500)).is_empty());
+        // between the thresholds keeps it going
+        assert_eq!(p.step(SOFT + 1.0, ms(t0, 1000)), vec![Decision::Countdown(2)]);
+        assert_eq!(p.step(SOFT - 1.0, ms(t0, 1500)), vec![Decision::CountdownCancelled]);
+        assert_eq!(p.cut_in(ms(t0, 1500)), None);
+        // and if it doesn't quiet down, it cuts
+        feed(&mut p, t0, 4, 2, |_| CUT);
+        assert!(!p.enforcing());
+        assert_eq!(p.step(CUT, ms(t0, 5000)), vec![Decision::Enforce]);
+    }
+
+    #[test]
+    fn someone_elses_noise_is_let_go() {
+        let (mut p, t0) = policy();
+        p.someone_else();
+        assert!(p.step(CUT + 10.0, t0).is_empty());
+        // only the window it was said of
+        assert_eq!(p.step(CUT, ms(t0, 500)), vec![Decision::Enforce]);
+        p.someone_else();
+        assert!(p.step(SOFT + 1.0, ms(t0, 1000)).is_empty());
+        assert!(p.enforcing());
+    }
+
+    #[test]
+    fn isolated_impulses_are_ignored() {
+        let t0 = Instant::now();
+        let impulses = Some(ImpulseRule { max_ignored: 2, within: Duration::from_secs(30) });
+        let rules = Rules { impulses, ..Rules::default() };
+        let mut p = Policy::new(AMBIENT, rules, t0);
+        let bang = |p: &mut Policy, at: u64| {
+            p.impulse();
+            p.step(CUT + 10.0, ms(t0, at))
+        };
+        assert!(bang(&mut p, 0).is_empty());
+        assert!(bang(&mut p, 10_000).is_empty());
+        // a third within 30s is banging, not an accident
+        assert_eq!(bang(&mut p, 20_000), vec![Decision::Enforce]);
+        assert_eq!(p.step(AMBIENT, ms(t0, 20_500)), vec![Decision::Restore(Reason::Quiet)]);
+        assert!(bang(&mut p, 60_000).is_empty());
+        // sustained noise isn't an impulse
+        assert_eq!(p.step(CUT + 10.0, ms(t0, 60_500)), vec![Decision::Enforce]);
+    }
+
+    #[test]
+    fn recalibrate_moves_thresholds() {
+        let (mut p, t0) = policy();
+        p.recalibrate(AMBIENT + 10.0);
+        assert!(p.step(CUT, t0).is_empty());
+        assert_eq!(p.step(CUT + 10.0, t0), vec![Decision::Enforce]);
+    }
+}
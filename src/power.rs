@@ -0,0 +1,121 @@
+// The nuclear option: with `[power]` set to sleep or shutdown, the policy
+// counts cuts, and a strike-out (`strikes` of them within `within_mins`)
+// puts the computer to sleep or shuts it down. It warns first, in the log and
+// as a desktop notification, and counts down `warning_secs`; pausing or
+// restoring through `shhh ctl` meanwhile calls it off. A consumer of the
+// monitor's event bus, so the countdown never holds up the loop.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use tracing::{error, info, warn};
+
+use crate::bus::Event;
+use crate::config::{PowerAction, PowerConfig};
+use crate::i18n::tr;
+use crate::notify;
+use crate::policy::{Decision, Reason};
+
+// the second notification, this long before it happens
+const LAST_WARNING: Duration = Duration::from_secs(10);
+
+pub struct Power {
+    config: PowerConfig,
+    dry_run: bool,
+    // the countdown going on, and how to call it off
+    countdown: Option<(JoinHandle<()>, Arc<AtomicBool>)>,
+}
+
+impl Power {
+    // None unless the config asks for it
+    pub fn from_config(config: &PowerConfig, dry_run: bool) -> Option<Power> {
+        (config.action != PowerAction::Off).then(|| Power { config: config.clone(), dry_run, countdown: None })
+    }
+
+    pub fn consume(&mut self, event: Event) {
+        let Event::Decisions(decisions) = event else { return };
+        self.countdown.take_if(|(countdown, _)| countdown.is_finished());
+        for decision in decisions {
+            match decision {
+                Decision::StruckOut if self.countdown.is_none() => self.count_down(),
+                Decision::Restore(Reason::Paused | Reason::Forced) => {
+                    if let Some((_, cancelled)) = self.countdown.take() {
+                        cancelled.store(true, Ordering::SeqCst);
+                        info!("{}", tr!("power-cancelled"));
+                        notify::desktop("shhh", &tr!("power-cancelled"));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn count_down(&mut self) {
+        let (config, dry_run) = (self.config.clone(), self.dry_run);
+        let what = if config.action == PowerAction::Sleep { "sleep" } else { "shutdown" };
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = cancelled.clone();
+        let warning = move |secs: u64| {
+            let message = tr!(
+                "power-warning",
+                strikes = config.strikes,
+                mins = config.within_mins,
+                what = what,
+                secs = secs
+            );
+            warn!("{}", message);
+            notify::desktop("shhh", &message);
+        };
+        let countdown = thread::spawn(move || {
+            let total = Duration::from_secs(config.warning_secs);
+            warning(total.as_secs());
+            let mut left = total;
+            while !left.is_zero() {
+                if left == LAST_WARNING && total > LAST_WARNING {
+                    warning(left.as_secs());
+                }
+                thread::sleep(Duration::from_secs(1));
+                left -= Duration::from_secs(1);
+                if flag.load(Ordering::SeqCst) {
+                    return;
+                }
+            }
+            if dry_run {
+                info!("[dry run] would {}", if config.action == PowerAction::Sleep { "sleep" } else { "shut down" });
+                return;
+            }
+            info!("{}", tr!("power-now", what = what));
+            if let Err(e) = go(config.action) {
+                error!("Could not {}: {}", what, e);
+            }
+        });
+        self.countdown = Some((countdown, cancelled));
+    }
+}
+
+#[cfg(not(windows))]
+fn go(action: PowerAction) -> Result<(), anyhow::Error> {
+    let verb = if action == PowerAction::Sleep { "suspend" } else { "poweroff" };
+    let output = std::process::Command::new("systemctl").arg(verb).output()?;
+    if !output.status.success() {
+        anyhow::bail!("systemctl {} failed: {}", verb, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn go(action: PowerAction) -> Result<(), anyhow::Error> {
+    if action == PowerAction::Sleep {
+        // not hibernating, not forcing apps, wake timers allowed
+        if !unsafe { windows_sys::Win32::System::Power::SetSuspendState(false, false, false) } {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        return Ok(());
+    }
+    let output = std::process::Command::new("shutdown").args(["/s", "/t", "0"]).output()?;
+    if !output.status.success() {
+        anyhow::bail!("shutdown /s failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+// Cutting adapters (and editing the hosts file) needs administrator/root
+// rights. Without them the backend calls fail, so check up front and either
+// refuse clearly or, with `--elevate`, relaunch through UAC or sudo.
+use std::ffi::OsString;
+
+use crate::error::Error;
+
+// our own command line minus `--elevate`, for the relaunch
+fn relaunch_args() -> Vec<OsString> {
+    std::env::args_os().skip(1).filter(|a| a != "--elevate").collect()
+}
+
+pub fn require(elevate: bool) -> Result<(), anyhow::Error> {
+    if is_elevated() {
+        return Ok(());
+    }
+    if elevate {
+        relaunch_elevated()?;
+    }
+    Err(Error::NotElevated.into())
+}
+
+#[cfg(unix)]
+pub fn is_elevated() -> bool {
+    if unsafe { libc::geteuid() } == 0 {
+        return true;
+    }
+    // CAP_NET_ADMIN (bit 12) is enough for `ip link`, e.g. via setcap
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    status
+        .lines()
+        .find_map(|l| l.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+        .is_some_and(|caps| caps & (1 << 12) != 0)
+}
+
+// replaces this process with `sudo shhh ...`; only returns on failure
+#[cfg(unix)]
+fn relaunch_elevated() -> Result<(), anyhow::Error> {
+    use std::os::unix::process::CommandExt;
+    let err = std::process::Command::new("sudo").arg("--").arg(std::env::current_exe()?).args(relaunch_args()).exec();
+    Err(anyhow::anyhow!("could not run sudo: {}", err))
+}
+
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token: HANDLE = std::ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut len = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            size_of::<TOKEN_ELEVATION>() as u32,
+            &mut len,
+        );
+        CloseHandle(token);
+        ok != 0 && elevation.TokenIsElevated != 0
+    }
+}
+
+// starts an elevated copy through the UAC prompt (it gets its own console)
+// and exits this one
+#[cfg(windows)]
+fn relaunch_elevated() -> Result<(), anyhow::Error> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::UI::Shell::ShellExecuteW;
+    use windows_sys::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+    let wide = |s: &std::ffi::OsStr| s.encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let exe = wide(std::env::current_exe()?.as_os_str());
+    let params: Vec<String> = relaunch_args().iter().map(|a| quote(&a.to_string_lossy())).collect();
+    let params = wide(params.join(" ").as_ref());
+    let verb = wide("runas".as_ref());
+    let result = unsafe {
+        ShellExecuteW(std::ptr::null_mut(), verb.as_ptr(), exe.as_ptr(), params.as_ptr(), std::ptr::null(), SW_SHOWNORMAL)
+    };
+    // anything up to 32 is an error code (e.g. the prompt was declined)
+    if result as usize <= 32 {
+        anyhow::bail!("elevation was declined or failed (code {})", result as usize);
+    }
+    println!("Continuing in an elevated window.");
+    std::process::exit(0);
+}
+
+// CommandLineToArgvW quoting
+#[cfg(windows)]
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    let mut out = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                out.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                out.push('"');
+                backslashes = 0;
+                continue;
+            }
+            _ => {}
+        }
+        if c != '\\' {
+            out.extend(std::iter::repeat_n('\\', backslashes));
+            out.push(c);
+            backslashes = 0;
+        }
+    }
+    out.extend(std::iter::repeat_n('\\', backslashes * 2));
+    out.push('"');
+    out
+}
@@ -0,0 +1,209 @@
+// Named profiles from the config (`[profiles.<name>]`), each taking over the
+// thresholds, penalty and action during its hours: a lenient "daytime" and a
+// strict "nighttime", say. Outside every profile's hours the top-level
+// settings apply. One can also be picked by hand (`--profile`, `ctl profile`),
+// which holds until it's switched back to `auto`. With `[calls]`, the call
+// profile takes over from the hours while a video call is detected. run_loop
+// asks which one is in force every window, so a change lands within a window.
+use chrono::Timelike;
+
+use crate::calls::Watcher;
+use crate::config::{self, Config};
+use crate::error::Error;
+use crate::policy::Rules;
+
+// minutes past local midnight, start inclusive, end exclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Span {
+    start: u16,
+    end: u16,
+}
+
+impl Span {
+    fn parse(text: &str) -> Option<Span> {
+        let (start, end) = text.split_once('-')?;
+        Some(Span { start: minute(start.trim())?, end: minute(end.trim())? })
+    }
+
+    // an end at or before the start wraps past midnight; equal means all day
+    fn contains(self, minute: u16) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+// "HH:MM"
+pub fn minute(text: &str) -> Option<u16> {
+    let (h, m) = text.split_once(':')?;
+    let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+pub struct Profile {
+    pub name: String,
+    hours: Vec<Span>,
+    pub rules: Rules,
+}
+
+pub struct Profiles {
+    // the top-level rules, for when no profile is in force
+    pub base: Rules,
+    profiles: Vec<Profile>,
+    // picked by hand, overriding the hours (and a call)
+    pinned: Option<usize>,
+    // the call profile, and what says whether there's a call on
+    call: Option<usize>,
+    calls: Option<Watcher>,
+}
+
+// what `--profile`/`ctl profile` take to go back to the schedule
+pub const AUTO: &str = "auto";
+
+impl Profiles {
+    pub fn new(base: Rules, config: &Config) -> Result<Profiles, Error> {
+        let mut profiles = Vec::new();
+        for (name, profile) in &config.profiles {
+            let hours = profile
+                .hours
+                .iter()
+                .map(|h| {
+                    Span::parse(h).ok_or_else(|| {
+                        Error::Config(format!("profiles.{}: hours must look like \"21:00-07:00\", not \"{}\"", name, h))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let rules = base.with_profile(profile);
+            let (soft, cut) = rules.absolute.unwrap_or((rules.soft_offset_db, rules.cut_offset_db));
+            config::check_thresholds(soft, cut, rules.dead_zone_db, rules.absolute.is_some())
+                .map_err(|e| Error::Config(format!("profiles.{}: {}", name, e)))?;
+            profiles.push(Profile { name: name.clone(), hours, rules });
+        }
+        let call = config.calls.as_ref().map(|calls| {
+            profiles.iter().position(|p| p.name == calls.profile).unwrap_or_else(|| {
+                let rules = Rules { warn_only: true, ..base.clone() };
+                profiles.push(Profile { name: calls.profile.clone(), hours: Vec::new(), rules });
+                profiles.len() - 1
+            })
+        });
+        // with overlapping hours it would be down to name order which wins
+        for minute in 0..24 * 60 {
+            let mut active = profiles.iter().filter(|p| p.hours.iter().any(|s| s.contains(minute)));
+            if let (Some(a), Some(b)) = (active.next(), active.next()) {
+                return Err(Error::Config(format!(
+                    "profiles {} and {} overlap at {:02}:{:02}",
+                    a.name,
+                    b.name,
+                    minute / 60,
+                    minute % 60
+                )));
+            }
+        }
+        Ok(Profiles { base, profiles, pinned: None, call, calls: None })
+    }
+
+    // the profile whose hours include `minute` past midnight, if any
+    pub fn at(&self, minute: u16) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.hours.iter().any(|s| s.contains(minute)))
+    }
+
+    // the profile in force: the pinned one, else the call one during a
+    // call, else by the clock
+    pub fn active(&self, minute: u16) -> Option<&Profile> {
+        let in_call = self.calls.as_ref().is_some_and(|w| w.current().is_some());
+        let call = self.call.filter(|_| in_call);
+        self.pinned.or(call).map(|i| &self.profiles[i]).or_else(|| self.at(minute))
+    }
+
+    pub fn watch_calls(&mut self, watcher: Watcher) {
+        self.calls = Some(watcher);
+    }
+
+    // hold a profile regardless of the time, or follow the hours again
+    pub fn pin(&mut self, name: &str) -> Result<(), Error> {
+        if name == AUTO {
+            self.pinned = None;
+            return Ok(());
+        }
+        let Some(i) = self.profiles.iter().position(|p| p.name == name) else {
+            let names = self.names();
+            let known = if names.is_empty() { "none".to_string() } else { names.join(", ") };
+            let hint = config::closest(name, names.iter().map(String::as_str))
+                .map(|c| format!("; did you mean '{}'?", c))
+                .unwrap_or_default();
+            return Err(Error::Config(format!("no profile named '{}'{} (profiles in the config: {})", name, hint, known)));
+        };
+        self.pinned = Some(i);
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.profiles.iter().map(|p| p.name.clone()).collect()
+    }
+}
+
+pub fn minute_now() -> u16 {
+    let now = chrono::Local::now();
+    (now.hour() * 60 + now.minute()) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProfileConfig;
+
+    fn profiles(entries: &[(&str, &[&str])]) -> Result<Profiles, Error> {
+        let mut config = Config::default();
+        for (name, hours) in entries {
+            let hours = hours.iter().map(|h| h.to_string()).collect();
+            config.profiles.insert(name.to_string(), ProfileConfig { hours, ..ProfileConfig::default() });
+        }
+        Profiles::new(Rules::default(), &config)
+    }
+
+    #[test]
+    fn hours_wrap_past_midnight() {
+        let p = profiles(&[("night", &["21:00-07:00"]), ("day", &["07:00-17:00"])]).unwrap();
+        let name = |m: u16| p.at(m).map(|p| p.name.as_str());
+        assert_eq!(name(23 * 60), Some("night"));
+        assert_eq!(name(6 * 60 + 59), Some("night"));
+        assert_eq!(name(7 * 60), Some("day"));
+        assert_eq!(name(18 * 60), None);
+    }
+
+    #[test]
+    fn a_pinned_profile_holds_all_day() {
+        let mut p = profiles(&[("night", &["21:00-07:00"]), ("movie", &[])]).unwrap();
+        p.pin("movie").unwrap();
+        assert_eq!(p.active(23 * 60).map(|p| p.name.as_str()), Some("movie"));
+        p.pin(AUTO).unwrap();
+        assert_eq!(p.active(23 * 60).map(|p| p.name.as_str()), Some("night"));
+        assert!(p.pin("nope").is_err());
+    }
+
+    #[test]
+    fn a_call_takes_over_from_the_hours() {
+        let mut config = Config { calls: Some(Default::default()), ..Config::default() };
+        let hours = vec!["21:00-07:00".to_string()];
+        config.profiles.insert("night".into(), ProfileConfig { hours, ..ProfileConfig::default() });
+        let mut p = Profiles::new(Rules::default(), &config).unwrap();
+        let watcher = Watcher::default();
+        p.watch_calls(watcher.clone());
+        watcher.set(Some("zoom".into()));
+        let call = p.active(23 * 60).unwrap();
+        assert_eq!(call.name, "call");
+        // no [profiles.call], so the built-in one: warn only
+        assert!(call.rules.warn_only);
+        watcher.set(None);
+        assert_eq!(p.active(23 * 60).map(|p| p.name.as_str()), Some("night"));
+    }
+
+    #[test]
+    fn overlaps_and_bad_hours_are_rejected() {
+        assert!(profiles(&[("a", &["20:00-23:00"]), ("b", &["22:00-06:00"])]).is_err());
+        assert!(profiles(&[("a", &["25:00-06:00"])]).is_err());
+        assert!(profiles(&[("a", &["evening"])]).is_err());
+    }
+}
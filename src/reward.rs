@@ -0,0 +1,37 @@
+// Positive reinforcement: the policy says when a quiet streak has earned a
+// reward, this hands it out. Every reward is logged; `[reward]` in the config
+// adds a desktop notification and/or a webhook, e.g. one that adds screen time
+// in a parental-control app.
+use tracing::info;
+
+use crate::config::RewardConfig;
+use crate::i18n::tr;
+use crate::notify;
+use crate::policy::Decision;
+
+pub struct Rewards {
+    notify: bool,
+    webhook: Option<String>,
+}
+
+impl Rewards {
+    pub fn from_config(config: &RewardConfig) -> Rewards {
+        Rewards { notify: config.notify, webhook: config.webhook.clone() }
+    }
+
+    pub fn carry_out(&self, decisions: &[Decision]) {
+        for decision in decisions {
+            if let Decision::Reward(streak) = decision {
+                let mins = streak.as_secs() / 60;
+                let message = tr!("reward", mins = mins);
+                info!("{}", tr!("reward-earned", message = message.as_str()));
+                if self.notify {
+                    notify::desktop("shhh", &message);
+                }
+                if let Some(url) = &self.webhook {
+                    notify::webhook(url, serde_json::json!({ "event": "reward", "streak_mins": mins }));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+// Custom policy logic in a Rhai script (`[script]` in the config), for rules
+// the config can't express: "louder than X for 30 s between 10pm and 7am on a
+// weekday". The script defines `on_window(w)`, which is called with each
+// window's metrics after the built-in rules ran, and calls the functions below
+// to act. `this` inside it is a map that keeps its contents between calls, for
+// whatever the script needs to remember. See the README for the full API.
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Timelike};
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use tracing::{info, warn};
+
+// per call, so a runaway loop can't stall the meter
+const MAX_OPERATIONS: u64 = 100_000;
+
+// what the script asked for in one call
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    // cut for at least this long
+    Cut(Duration),
+    Restore,
+    // pause, for this long or until resumed
+    Pause(Option<Duration>),
+    Resume,
+    Warn(String),
+    Notify(String),
+}
+
+// one window as the script sees it
+pub struct Window<'a> {
+    pub db: f32,
+    pub ambient_db: f32,
+    pub soft_db: f32,
+    pub cut_db: f32,
+    pub state: &'a str,
+    pub cutting: bool,
+    pub paused: bool,
+    pub quiet_secs: u64,
+    pub profile: Option<&'a str>,
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    memory: Dynamic,
+    commands: Rc<RefCell<Vec<Command>>>,
+    started: Instant,
+    last_error: Option<String>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Script, anyhow::Error> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+        Script::compile(&source).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    fn compile(source: &str) -> Result<Script, anyhow::Error> {
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let secs = |secs: i64| Duration::from_secs(secs.max(0) as u64);
+        let queue = |command: Command| {
+            let commands = commands.clone();
+            move || commands.borrow_mut().push(command.clone())
+        };
+        let c = commands.clone();
+        engine.register_fn("cut", move |s: i64| c.borrow_mut().push(Command::Cut(secs(s))));
+        engine.register_fn("restore", queue(Command::Restore));
+        engine.register_fn("pause", queue(Command::Pause(None)));
+        let c = commands.clone();
+        engine.register_fn("pause", move |mins: i64| c.borrow_mut().push(Command::Pause(Some(secs(mins.saturating_mul(60))))));
+        engine.register_fn("resume", queue(Command::Resume));
+        let c = commands.clone();
+        engine.register_fn("warn", move |message: &str| c.borrow_mut().push(Command::Warn(message.to_string())));
+        let c = commands.clone();
+        engine.register_fn("notify", move |message: &str| c.borrow_mut().push(Command::Notify(message.to_string())));
+        engine.on_print(|text| info!("[script] {}", text));
+
+        let ast = engine.compile(source)?;
+        if !ast.iter_functions().any(|f| f.name == "on_window" && f.params.len() == 1) {
+            anyhow::bail!("the script has to define on_window(w)");
+        }
+        // top-level statements run once, up front
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast).map_err(|e| anyhow::anyhow!("{}", e))?;
+        commands.borrow_mut().clear();
+        Ok(Script {
+            engine,
+            ast,
+            scope,
+            memory: Map::new().into(),
+            commands,
+            started: Instant::now(),
+            last_error: None,
+        })
+    }
+
+    // A failing call is reported (once until it changes) and asks for nothing;
+    // the built-in rules carry on either way.
+    pub fn on_window(&mut self, window: &Window) -> Vec<Command> {
+        let w = self.metrics(window);
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.memory);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, "on_window", (w,));
+        let commands = std::mem::take(&mut *self.commands.borrow_mut());
+        match result {
+            Ok(_) => {
+                self.last_error = None;
+                commands
+            }
+            Err(e) => {
+                let e = e.to_string();
+                if self.last_error.as_ref() != Some(&e) {
+                    warn!("Script error: {}", e);
+                    self.last_error = Some(e);
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    fn metrics(&self, window: &Window) -> Map {
+        let now = chrono::Local::now();
+        let mut w = Map::new();
+        let mut set = |key: &str, value: Dynamic| {
+            w.insert(key.into(), value);
+        };
+        set("db", (window.db as f64).into());
+        set("ambient", (window.ambient_db as f64).into());
+        set("soft", (window.soft_db as f64).into());
+        set("cut", (window.cut_db as f64).into());
+        set("state", window.state.into());
+        set("cutting", window.cutting.into());
+        set("paused", window.paused.into());
+        set("quiet_secs", (window.quiet_secs as i64).into());
+        set("profile", window.profile.map_or(Dynamic::UNIT, |p| p.into()));
+        set("time", self.started.elapsed().as_secs_f64().into());
+        set("hour", (now.hour() as i64).into());
+        set("minute", (now.minute() as i64).into());
+        // 1 = Monday ... 7 = Sunday
+        set("weekday", (now.weekday().number_from_monday() as i64).into());
+        w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(db: f32) -> Window<'static> {
+        Window {
+            db,
+            ambient_db: -60.0,
+            soft_db: -45.0,
+            cut_db: -15.0,
+            state: "OK",
+            cutting: false,
+            paused: false,
+            quiet_secs: 0,
+            profile: None,
+        }
+    }
+
+    #[test]
+    fn scripts_act_and_remember() {
+        let source = r#"
+            fn on_window(w) {
+                if w.db > w.soft {
+                    this.loud = (this.loud ?? 0) + 1;
+                } else {
+                    this.loud = 0;
+                }
+                if this.loud >= 3 {
+                    cut(30);
+                    warn("loud for 3 windows");
+                }
+            }
+        "#;
+        let mut script = Script::compile(source).unwrap();
+        assert!(script.on_window(&window(-30.0)).is_empty());
+        assert!(script.on_window(&window(-30.0)).is_empty());
+        assert_eq!(script.on_window(&window(-30.0)), [
+            Command::Cut(Duration::from_secs(30)),
+            Command::Warn("loud for 3 windows".into())
+        ]);
+        assert!(script.on_window(&window(-60.0)).is_empty());
+        // minutes that overflow are as long as it gets, not a crash
+        let mut script = Script::compile("fn on_window(w) { pause(9223372036854775807); }").unwrap();
+        assert_eq!(script.on_window(&window(-60.0)), [Command::Pause(Some(Duration::from_secs(i64::MAX as u64)))]);
+    }
+
+    #[test]
+    fn bad_scripts_are_caught() {
+        assert!(Script::compile("fn on_window(w) { loop {} }").unwrap().on_window(&window(-60.0)).is_empty());
+        assert!(Script::compile("fn something_else() {}").is_err());
+        assert!(Script::compile("fn on_window(w) {").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+// Windows service mode: starts at boot, keeps running across logoff and is
+// restarted by the service control manager if it crashes.
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use clap::Subcommand;
+use tracing::error;
+use windows_service::service::{
+    ServiceAccess, ServiceAction as ScAction, ServiceActionType, ServiceControl,
+    ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceFailureActions,
+    ServiceFailureResetPeriod, ServiceInfo, ServiceStartType, ServiceState, ServiceStatus,
+    ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use crate::acl;
+use crate::config;
+use crate::eventlog::{self, Kind};
+use crate::monitor::RunArgs;
+
+const SERVICE_NAME: &str = "shhh";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+// the SCM calls service_main without our command line, so `service run`
+// parks its options here first
+static RUN_ARGS: OnceLock<RunArgs> = OnceLock::new();
+
+#[derive(Subcommand)]
+pub enum ServiceAction {
+    /// Register shhh as an auto-start service that restarts on failure
+    Install {
+        /// Options passed to `shhh run` by the service (after `--`)
+        #[arg(last = true)]
+        run_args: Vec<OsString>,
+    },
+    /// Stop and remove the service
+    Uninstall,
+    /// Entry point used by the service control manager
+    Run(Box<RunArgs>),
+}
+
+pub fn handle(action: ServiceAction) -> Result<(), anyhow::Error> {
+    match action {
+        ServiceAction::Install { run_args } => install(run_args),
+        ServiceAction::Uninstall => uninstall(),
+        ServiceAction::Run(args) => {
+            let _ = RUN_ARGS.set(*args);
+            service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+            Ok(())
+        }
+    }
+}
+
+fn install(run_args: Vec<OsString>) -> Result<(), anyhow::Error> {
+    // the config, plugins and state the service (SYSTEM) reads; by default
+    // any user could add files there
+    let dir = config::default_path().parent().map(Path::to_path_buf).unwrap_or_default();
+    acl::lock_down(&dir)?;
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from("shhh noise monitor"),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: [OsString::from("service"), OsString::from("run")].into_iter().chain(run_args).collect(),
+        dependencies: vec![],
+        account_name: None, // LocalSystem, so logging off doesn't stop it
+        account_password: None,
+    };
+    let service = manager.create_service(
+        &info,
+        ServiceAccess::CHANGE_CONFIG | ServiceAccess::START,
+    )?;
+    service.set_description("Cuts the network connection when the microphone gets too loud")?;
+    eventlog::register()?;
+
+    // restart after 5s on the first failures, then back off to a minute
+    let restart = |secs| ScAction { action_type: ServiceActionType::Restart, delay: Duration::from_secs(secs) };
+    service.update_failure_actions(ServiceFailureActions {
+        reset_period: ServiceFailureResetPeriod::After(Duration::from_secs(24 * 60 * 60)),
+        reboot_msg: None,
+        command: None,
+        actions: Some(vec![restart(5), restart(5), restart(60)]),
+    })?;
+    // also restart when we exit with an error instead of crashing outright
+    service.set_failure_actions_on_non_crash_failures(true)?;
+
+    service.start::<&OsStr>(&[])?;
+    println!("Installed and started service '{}'.", SERVICE_NAME);
+    Ok(())
+}
+
+// whether the service is registered, i.e. SYSTEM reads the default config
+pub fn installed() -> bool {
+    ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .and_then(|manager| manager.open_service(SERVICE_NAME, ServiceAccess::QUERY_STATUS))
+        .is_ok()
+}
+
+fn uninstall() -> Result<(), anyhow::Error> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+    service.delete()?;
+    eventlog::deregister();
+    println!("Service '{}' marked for deletion.", SERVICE_NAME);
+    Ok(())
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_args: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        error!("Service error: {}", e);
+    }
+}
+
+fn run_service() -> Result<(), anyhow::Error> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let handler_stop = stop.clone();
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| {
+        match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                handler_stop.store(true, Ordering::SeqCst);
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        }
+    })?;
+
+    let set_state = |state, accept, exit_code| {
+        status_handle.set_service_status(ServiceStatus {
+            service_type: SERVICE_TYPE,
+            current_state: state,
+            controls_accepted: accept,
+            exit_code,
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        })
+    };
+
+    set_state(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        ServiceExitCode::Win32(0),
+    )?;
+    eventlog::report(Kind::Info, eventlog::STARTED, "The shhh service started.");
+    let args = RUN_ARGS.get_or_init(RunArgs::default);
+    let args = &RunArgs { supervised: true, ..args.clone() };
+    let result = crate::action::build(args).and_then(|action| crate::monitor::run_monitor(stop, args, action));
+    match &result {
+        Ok(()) => eventlog::report(Kind::Info, eventlog::STOPPED, "The shhh service stopped."),
+        Err(e) => eventlog::report(Kind::Error, eventlog::STOPPED, &format!("The shhh service stopped: {}", e)),
+    }
+    // a non-zero exit code makes the SCM apply the restart failure actions
+    let exit_code = if result.is_ok() { 0 } else { 1 };
+    set_state(ServiceState::Stopped, ServiceControlAccept::empty(), ServiceExitCode::Win32(exit_code))?;
+    result
+}
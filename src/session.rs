@@ -0,0 +1,35 @@
+// Locking the screen, for `lock_screen`. On Windows, LockWorkStation from a
+// process on the user's desktop, or disconnecting the console session (which
+// shows the lock screen the same way) from the service, which has none; on
+// Linux, logind's lock signal, which the desktop's screen locker acts on.
+#[cfg(not(windows))]
+pub fn lock() -> Result<(), anyhow::Error> {
+    // root locks every session; a user can only lock their own
+    let what = if unsafe { libc::geteuid() } == 0 { "lock-sessions" } else { "lock-session" };
+    let output = std::process::Command::new("loginctl")
+        .arg(what)
+        .output()
+        .map_err(|e| anyhow::anyhow!("could not run loginctl (is systemd-logind running?): {}", e))?;
+    if !output.status.success() {
+        anyhow::bail!("loginctl {} failed: {}", what, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn lock() -> Result<(), anyhow::Error> {
+    use windows_sys::Win32::System::RemoteDesktop::{WTSDisconnectSession, WTSGetActiveConsoleSessionId};
+    use windows_sys::Win32::System::Shutdown::LockWorkStation;
+
+    unsafe {
+        if LockWorkStation() != 0 {
+            return Ok(());
+        }
+        let session = WTSGetActiveConsoleSessionId();
+        // u32::MAX: nobody at the console (switching users), so nothing to lock
+        if session == u32::MAX || WTSDisconnectSession(std::ptr::null_mut(), session, 0) != 0 {
+            return Ok(());
+        }
+    }
+    Err(anyhow::anyhow!("could not lock the session: {}", std::io::Error::last_os_error()))
+}
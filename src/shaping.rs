@@ -0,0 +1,153 @@
+// Traffic shaping for `mode = "degrade"`: rather than dropping the connection,
+// make it bad. Latency, jitter and packet loss grow with how far the level is
+// past the soft threshold and peak during a cut, so a game gets painfully
+// laggy well before it would have been disconnected. On Linux that's a `tc`
+// netem qdisc on each interface (outgoing traffic, which is what a game's
+// inputs are). Windows has no built-in way to delay packets, so it throttles
+// all outgoing traffic with a QoS policy instead, scaled the same way.
+use std::process::Command;
+
+use crate::config::DegradeConfig;
+
+// the levels shaping moves between, so a fluctuating level doesn't
+// reconfigure the qdisc every second
+pub const STEPS: u8 = 4;
+
+// `level` (0-100) down to the step at or below it
+pub fn quantize(level: u8) -> u8 {
+    let step = 100 / STEPS;
+    level.min(100) / step * step
+}
+
+// a percentage (the volume, the brightness) at `level`: the cap falls from
+// 100% at level 0 to `min` at a cut, and a value already below it stays
+pub fn capped(value: u8, level: u8, min: u8) -> u8 {
+    let cap = 100 - (100 - min.min(100) as u32) * level.min(100) as u32 / 100;
+    value.min(cap as u8)
+}
+
+// what netem adds at `level` (1-100)
+#[cfg_attr(windows, allow(dead_code))]
+fn netem_args(config: &DegradeConfig, level: u8) -> Vec<String> {
+    let scale = level.min(100) as f32 / 100.0;
+    let mut args = vec![
+        "delay".to_string(),
+        format!("{}ms", (config.delay_ms as f32 * scale).round()),
+        format!("{}ms", (config.jitter_ms as f32 * scale).round()),
+    ];
+    if config.loss_pct > 0.0 {
+        args.extend(["loss".to_string(), format!("{:.1}%", config.loss_pct * scale)]);
+    }
+    args
+}
+
+// from 10 Mbit/s down to `rate_kbps` at a cut, evenly on a log scale
+#[cfg_attr(not(windows), allow(dead_code))]
+fn rate_bps(config: &DegradeConfig, level: u8) -> u64 {
+    let (top, floor) = (10_000.0f64, config.rate_kbps.clamp(8, 10_000) as f64);
+    let kbps = top * (floor / top).powf(level.min(100) as f64 / 100.0);
+    (kbps * 1000.0).round() as u64
+}
+
+// level 0 lifts the shaping; errors from every interface are collected
+pub fn set(ifaces: &[String], config: &DegradeConfig, level: u8) -> Result<(), anyhow::Error> {
+    if level == 0 {
+        return clear(ifaces);
+    }
+    platform::set(ifaces, config, level)
+}
+
+pub fn clear(ifaces: &[String]) -> Result<(), anyhow::Error> {
+    platform::clear(ifaces)
+}
+
+fn run(cmd: &mut Command) -> Result<(), String> {
+    let output = cmd.output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(not(windows))]
+mod platform {
+    use super::*;
+
+    pub fn set(ifaces: &[String], config: &DegradeConfig, level: u8) -> Result<(), anyhow::Error> {
+        let errors: Vec<String> = ifaces
+            .iter()
+            .filter_map(|iface| {
+                let mut tc = Command::new("tc");
+                tc.args(["qdisc", "replace", "dev", iface, "root", "netem"]).args(netem_args(config, level));
+                run(&mut tc).err().map(|e| format!("tc netem on {} failed: {}", iface, e))
+            })
+            .collect();
+        errors.is_empty().then_some(()).ok_or_else(|| anyhow::anyhow!(errors.join("; ")))
+    }
+
+    // nothing to delete is fine: that's the state we want
+    pub fn clear(ifaces: &[String]) -> Result<(), anyhow::Error> {
+        let errors: Vec<String> = ifaces
+            .iter()
+            .filter_map(|iface| run(Command::new("tc").args(["qdisc", "del", "dev", iface, "root", "netem"])).err())
+            .filter(|e| !e.contains("No such file or directory") && !e.contains("handle of zero"))
+            .collect();
+        errors.is_empty().then_some(()).ok_or_else(|| anyhow::anyhow!("tc qdisc del failed: {}", errors.join("; ")))
+    }
+}
+
+// a policy in the active (non-persistent) store, so a reboot clears it too
+#[cfg(windows)]
+mod platform {
+    use super::*;
+
+    const POLICY: &str = "shhh";
+
+    fn powershell(script: &str) -> Result<(), anyhow::Error> {
+        run(Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", script]))
+            .map_err(|e| anyhow::anyhow!("QoS policy: {}", e))
+    }
+
+    pub fn set(_ifaces: &[String], config: &DegradeConfig, level: u8) -> Result<(), anyhow::Error> {
+        powershell(&format!(
+            "Remove-NetQosPolicy -Name {p} -PolicyStore ActiveStore -Confirm:$false -ErrorAction SilentlyContinue; \
+             New-NetQosPolicy -Name {p} -Default -ThrottleRateActionBitsPerSecond {bps} -PolicyStore ActiveStore \
+             -ErrorAction Stop | Out-Null",
+            p = POLICY,
+            bps = rate_bps(config, level)
+        ))
+    }
+
+    pub fn clear(_ifaces: &[String]) -> Result<(), anyhow::Error> {
+        powershell(&format!(
+            "Remove-NetQosPolicy -Name {} -PolicyStore ActiveStore -Confirm:$false -ErrorAction SilentlyContinue",
+            POLICY
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shaping_scales_with_the_level() {
+        let config = DegradeConfig { delay_ms: 400, jitter_ms: 100, loss_pct: 10.0, rate_kbps: 100 };
+        assert_eq!(netem_args(&config, 100), ["delay", "400ms", "100ms", "loss", "10.0%"]);
+        assert_eq!(netem_args(&config, 25), ["delay", "100ms", "25ms", "loss", "2.5%"]);
+        assert_eq!(rate_bps(&config, 100), 100_000);
+        assert_eq!(rate_bps(&config, 50), 1_000_000);
+        assert_eq!([quantize(10), quantize(25), quantize(74), quantize(100)], [0, 25, 50, 100]);
+    }
+
+    #[test]
+    fn caps_fall_with_the_level() {
+        assert_eq!(capped(80, 0, 10), 80);
+        // halfway to a cut: capped at 55%
+        assert_eq!(capped(80, 50, 10), 55);
+        assert_eq!(capped(40, 50, 10), 40);
+        assert_eq!(capped(80, 100, 10), 10);
+        assert_eq!(capped(80, 100, 0), 0);
+    }
+}
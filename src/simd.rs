@@ -0,0 +1,82 @@
+// The meter's passes over whole windows of samples, written to vectorize.
+// std::simd is nightly-only, so it's done the manual way: LANES independent
+// accumulators over fixed-size chunks, which LLVM turns into SSE/AVX (x86_64)
+// or NEON (aarch64) instructions on stable. A single running sum can't be
+// vectorized, since reordering float additions changes the result.
+//
+// Squares are summed in f32 for a BLOCK at a time and then added up in f64,
+// so a long window loses no more precision than the plain f64 loop did.
+// `cargo bench` compares these with the plain loops.
+
+const LANES: usize = 8;
+// samples per f32 partial sum: 128 per lane, each at most 1.0
+const BLOCK: usize = 1024;
+
+pub fn sum_squares(samples: &[f32]) -> f64 {
+    let mut total = 0.0;
+    for block in samples.chunks(BLOCK) {
+        let mut acc = [0f32; LANES];
+        let chunks = block.chunks_exact(LANES);
+        let rest = chunks.remainder();
+        for chunk in chunks {
+            for (a, &s) in acc.iter_mut().zip(chunk) {
+                *a += s * s;
+            }
+        }
+        total += acc.iter().map(|&a| a as f64).sum::<f64>() + rest.iter().map(|&s| (s * s) as f64).sum::<f64>();
+    }
+    total
+}
+
+// root mean square, 0 for no samples
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (sum_squares(samples) / samples.len() as f64).sqrt() as f32
+}
+
+// how many samples are at or past ±`level`
+pub fn count_over(samples: &[f32], level: f32) -> usize {
+    let mut acc = [0u32; LANES];
+    let chunks = samples.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for (a, &s) in acc.iter_mut().zip(chunk) {
+            *a += (s.abs() >= level) as u32;
+        }
+    }
+    acc.iter().map(|&a| a as usize).sum::<usize>() + rest.iter().filter(|s| s.abs() >= level).count()
+}
+
+// the largest magnitude
+pub fn peak(samples: &[f32]) -> f32 {
+    let mut acc = [0f32; LANES];
+    let chunks = samples.chunks_exact(LANES);
+    let rest = chunks.remainder();
+    for chunk in chunks {
+        for (a, &s) in acc.iter_mut().zip(chunk) {
+            *a = a.max(s.abs());
+        }
+    }
+    rest.iter().chain(&acc).fold(0.0, |peak, s| peak.max(s.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernels_match_the_plain_loops() {
+        // odd lengths leave a remainder past the last chunk and block
+        for len in [0, 7, 8, 1000, 4099] {
+            let samples: Vec<f32> = (0..len).map(|i| ((i * 7919 % 2003) as f32 / 1001.5 - 1.0) * 0.99).collect();
+            let plain: f64 = samples.iter().map(|&s| (s as f64) * (s as f64)).sum();
+            assert!((sum_squares(&samples) - plain).abs() <= plain * 1e-6, "{}", len);
+            assert_eq!(count_over(&samples, 0.5), samples.iter().filter(|s| s.abs() >= 0.5).count());
+            assert_eq!(peak(&samples), samples.iter().fold(0.0f32, |p, s| p.max(s.abs())));
+        }
+        assert_eq!(rms(&[0.5, -0.5, 0.5, -0.5]), 0.5);
+        assert_eq!(rms(&[]), 0.0);
+    }
+}
@@ -0,0 +1,188 @@
+// `shhh spectrogram`: a scrolling spectrogram in the terminal, one row every
+// 125ms, low frequencies on the left, for telling at a glance whether the
+// level comes from bass through the floor (a subwoofer downstairs) or from
+// voices in the room. Each row ends with the share of the energy below
+// BASS_HZ and the level.
+//
+// There's no terminal UI to put it in, so it's a command of its own that
+// opens the microphone (or a WAV file) itself; it runs alongside the monitor
+// wherever the audio system shares the device (Windows, PulseAudio,
+// PipeWire).
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::audio::{self, rms_to_db};
+use crate::config;
+
+const FFT_SIZE: usize = 4096;
+const ROWS_PER_SEC: u32 = 8;
+const LOW_HZ: f32 = 40.0;
+const HIGH_HZ: f32 = 12_000.0;
+const BASS_HZ: f32 = 250.0;
+// band levels (dBFS) from the bottom of the colour ramp to the top
+const FLOOR_DB: f32 = -100.0;
+const CEILING_DB: f32 = -20.0;
+// xterm 256-colour ramp: black, blues, purples, reds, yellow, white
+const RAMP: [u8; 24] = [
+    16, 17, 18, 19, 20, 21, 57, 93, 129, 165, 201, 200, 199, 198, 197, 196, 202, 208, 214, 220, 226, 227, 229, 231,
+];
+
+#[derive(clap::Args, Debug)]
+pub struct SpectrogramArgs {
+    /// Microphone to listen to, by (part of) its name in `shhh devices` (default: as for `run`)
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+    /// Replay a WAV file instead of listening to the microphone
+    #[arg(long, value_name = "WAV")]
+    pub input_file: Option<PathBuf>,
+    /// Frequency bands, one column each
+    #[arg(long, default_value_t = 64, value_parser = clap::value_parser!(u16).range(8..=400))]
+    pub bands: u16,
+}
+
+pub fn run(args: &SpectrogramArgs) -> Result<(), anyhow::Error> {
+    let input = match &args.input_file {
+        Some(path) => audio::open_file(path, 1.0)?,
+        None => audio::open_input(args.device.as_deref().or(config::load(None)?.input_device.as_deref()))?,
+    };
+    let (rate, channels) = (input.sample_rate, input.channels.max(1) as usize);
+    let edges = band_edges(args.bands as usize, rate);
+    let hop = (rate / ROWS_PER_SEC) as usize;
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", header(&edges, rate))?;
+
+    // the last FFT_SIZE frames, mixed down to mono
+    let mut recent: VecDeque<f32> = std::iter::repeat_n(0.0, FFT_SIZE).collect();
+    let (mut frame, mut since_row) = (Vec::with_capacity(channels), 0);
+    for sample in input.rx.iter() {
+        frame.push(sample);
+        if frame.len() < channels {
+            continue;
+        }
+        recent.pop_front();
+        recent.push_back(frame.drain(..).sum::<f32>() / channels as f32);
+        since_row += 1;
+        if since_row == hop {
+            since_row = 0;
+            writeln!(stdout, "{}", row(&spectrum(recent.make_contiguous()), &edges, rate))?;
+        }
+    }
+    Ok(())
+}
+
+// `bands` + 1 log-spaced edges, in Hz
+fn band_edges(bands: usize, rate: u32) -> Vec<f32> {
+    let high = HIGH_HZ.min(rate as f32 / 2.0);
+    (0..=bands).map(|i| LOW_HZ * (high / LOW_HZ).powf(i as f32 / bands as f32)).collect()
+}
+
+fn bin_hz(rate: u32) -> f32 {
+    rate as f32 / FFT_SIZE as f32
+}
+
+// power of each bin up to Nyquist, scaled so a full-scale sine's bin is 1
+fn spectrum(samples: &[f32]) -> Vec<f32> {
+    let hann = |i: usize| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32).cos();
+    let gain: f32 = (0..FFT_SIZE).map(hann).sum::<f32>() / 2.0;
+    let mut data: Vec<(f32, f32)> = samples.iter().enumerate().map(|(i, &s)| (s * hann(i), 0.0)).collect();
+    fft(&mut data);
+    data[..FFT_SIZE / 2].iter().map(|&(re, im)| (re * re + im * im) / (gain * gain)).collect()
+}
+
+// in-place iterative radix-2 FFT; the length has to be a power of two
+pub fn fft(data: &mut [(f32, f32)]) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (data[start + k], data[start + k + len / 2]);
+                let t = (b.0 * cos - b.1 * sin, b.0 * sin + b.1 * cos);
+                data[start + k] = (a.0 + t.0, a.1 + t.1);
+                data[start + k + len / 2] = (a.0 - t.0, a.1 - t.1);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// the loudest bin in each band (a narrow band may hold none, so at least
+// the nearest one), in dBFS
+fn band_levels(power: &[f32], edges: &[f32], rate: u32) -> Vec<f32> {
+    let bin = |hz: f32| ((hz / bin_hz(rate)).round() as usize).min(power.len() - 1);
+    edges
+        .windows(2)
+        .map(|edge| {
+            let (from, to) = (bin(edge[0]), bin(edge[1]).max(bin(edge[0]) + 1).min(power.len()));
+            10.0 * power[from..to].iter().copied().fold(1e-20, f32::max).log10()
+        })
+        .collect()
+}
+
+fn row(power: &[f32], edges: &[f32], rate: u32) -> String {
+    let mut row = String::new();
+    for db in band_levels(power, edges, rate) {
+        let level = ((db - FLOOR_DB) / (CEILING_DB - FLOOR_DB)).clamp(0.0, 1.0);
+        row.push_str(&format!("\x1b[38;5;{}m█", RAMP[(level * (RAMP.len() - 1) as f32).round() as usize]));
+    }
+    let bass_bins = (BASS_HZ / bin_hz(rate)) as usize;
+    let first = (LOW_HZ / bin_hz(rate)) as usize;
+    let total: f32 = power[first..].iter().sum();
+    let bass = power[first..bass_bins].iter().sum::<f32>() / total.max(1e-20);
+    // through the Hann window, the bins' powers add up to 3x the mean square
+    let db = rms_to_db((total / 3.0).sqrt());
+    row.push_str(&format!("\x1b[0m bass {:3.0}% {:6.1} dB", bass * 100.0, db));
+    row
+}
+
+// frequency ticks over the columns they fall in
+fn header(edges: &[f32], rate: u32) -> String {
+    let mut header = vec![' '; edges.len() - 1];
+    for (hz, label) in [(100.0, "100"), (BASS_HZ, "250"), (1000.0, "1k"), (4000.0, "4k")] {
+        let Some(column) = edges.windows(2).position(|e| e[0] <= hz && hz < e[1]) else { continue };
+        let end = (column + label.len()).min(header.len());
+        header.splice(column..end, label.chars().take(end - column));
+    }
+    let high = HIGH_HZ.min(rate as f32 / 2.0);
+    format!("{} Hz ({:.0} to {:.0})", header.into_iter().collect::<String>(), LOW_HZ, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(hz: f32, amplitude: f32, rate: u32) -> Vec<f32> {
+        (0..FFT_SIZE).map(|i| amplitude * (2.0 * std::f32::consts::PI * hz * i as f32 / rate as f32).sin()).collect()
+    }
+
+    #[test]
+    fn bass_and_voices_land_in_their_bands() {
+        let rate = 48_000;
+        let edges = band_edges(64, rate);
+        assert_eq!((edges[0], edges[64]), (LOW_HZ, HIGH_HZ));
+
+        let levels = band_levels(&spectrum(&sine(60.0, 0.5, rate)), &edges, rate);
+        let band = |hz: f32| edges.windows(2).position(|e| e[0] <= hz && hz < e[1]).unwrap();
+        // a half-scale sine is -6 dBFS
+        assert!((levels[band(60.0)] + 6.0).abs() < 1.0);
+        assert!(levels[band(1_000.0)] < -60.0);
+        assert!(row(&spectrum(&sine(60.0, 0.5, rate)), &edges, rate).contains("bass 100%"));
+        assert!(row(&spectrum(&sine(1_000.0, 0.5, rate)), &edges, rate).contains("bass   0%"));
+        assert!(header(&edges, rate).contains("1k"));
+    }
+}
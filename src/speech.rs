@@ -0,0 +1,120 @@
+// Keyword spotting: a Vosk recognizer, restricted to the configured phrases,
+// listens to the same audio as the meter on its own thread and reports the
+// keywords (and the safe word) it hears. Everything stays on the machine; the model is a directory
+// downloaded once (https://alphacephei.com/vosk/models). Only built with
+// `--features speech`, since it links against libvosk.
+#![cfg_attr(not(feature = "speech"), allow(dead_code))]
+
+use std::sync::mpsc;
+
+use crate::config::SpeechConfig;
+
+// windows queued for the recognizer; if it falls further behind than this,
+// windows are skipped rather than piling up
+const BACKLOG: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Heard {
+    Keyword(String),
+    SafeWord,
+}
+
+pub struct Listener {
+    windows: mpsc::SyncSender<Vec<f32>>,
+    heard: mpsc::Receiver<Heard>,
+}
+
+impl Listener {
+    #[cfg(feature = "speech")]
+    pub fn start(config: &SpeechConfig, sample_rate: u32, channels: u16) -> Result<Listener, anyhow::Error> {
+        use vosk::{DecodingState, Model, Recognizer};
+
+        vosk::set_log_level(vosk::LogLevel::Warn);
+        let model = Model::new(config.model.to_string_lossy())
+            .ok_or_else(|| anyhow::anyhow!("could not load the speech model in {}", config.model.display()))?;
+        let safe_word = config.safe_word.as_deref().map(normalize);
+        let phrases: Vec<String> = config.keywords.iter().map(|k| normalize(k)).chain(safe_word.clone()).collect();
+        // anything else comes out as [unk], which keeps it fast and accurate
+        let grammar: Vec<&str> = phrases.iter().map(String::as_str).chain(["[unk]"]).collect();
+        let mut recognizer = Recognizer::new_with_grammar(&model, sample_rate as f32, &grammar)
+            .ok_or_else(|| anyhow::anyhow!("the speech model rejected the keyword list"))?;
+
+        let (windows, rx) = mpsc::sync_channel::<Vec<f32>>(BACKLOG);
+        let (tx, heard) = mpsc::channel();
+        std::thread::Builder::new().name("speech".into()).spawn(move || {
+            let _model = model;
+            for window in rx {
+                let state = recognizer.accept_waveform(&to_mono(&window, channels));
+                if let Ok(DecodingState::Finalized) = state {
+                    let text = recognizer.result().single().map(|r| r.text.to_string()).unwrap_or_default();
+                    for phrase in spotted(&text, &phrases) {
+                        let heard = match &safe_word {
+                            Some(word) if word == phrase => Heard::SafeWord,
+                            _ => Heard::Keyword(phrase.clone()),
+                        };
+                        if tx.send(heard).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        })?;
+        tracing::info!(
+            "Listening for {} keyword(s){}.",
+            config.keywords.len(),
+            if config.safe_word.is_some() { " and the safe word" } else { "" }
+        );
+        Ok(Listener { windows, heard })
+    }
+
+    #[cfg(not(feature = "speech"))]
+    pub fn start(_config: &SpeechConfig, _sample_rate: u32, _channels: u16) -> Result<Listener, anyhow::Error> {
+        anyhow::bail!("[speech] needs a build with speech recognition (cargo build --features speech)")
+    }
+
+    pub fn feed(&self, window: &[f32]) {
+        // full: the recognizer is behind, skip this one
+        let _ = self.windows.try_send(window.to_vec());
+    }
+
+    pub fn heard(&self) -> Vec<Heard> {
+        self.heard.try_iter().collect()
+    }
+}
+
+// Vosk wants 16-bit mono
+fn to_mono(window: &[f32], channels: u16) -> Vec<i16> {
+    window
+        .chunks(channels.max(1) as usize)
+        .map(|frame| (frame.iter().sum::<f32>() / frame.len() as f32).clamp(-1.0, 1.0))
+        .map(|s| (s * i16::MAX as f32) as i16)
+        .collect()
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().map(str::to_lowercase).collect::<Vec<_>>().join(" ")
+}
+
+// the configured phrases that occur in `text` as whole words
+fn spotted<'a>(text: &str, phrases: &'a [String]) -> Vec<&'a String> {
+    let text = format!(" {} ", normalize(text));
+    phrases.iter().filter(|p| text.contains(&format!(" {} ", p))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phrases_match_whole_words_only() {
+        let phrases: Vec<String> = ["darn", "shut up"].iter().map(|p| normalize(p)).collect();
+        assert_eq!(spotted("oh darn it", &phrases), [&phrases[0]]);
+        assert_eq!(spotted("just  SHUT up", &phrases), [&phrases[1]]);
+        assert!(spotted("darned [unk]", &phrases).is_empty());
+    }
+
+    #[test]
+    fn frames_are_mixed_down() {
+        assert_eq!(to_mono(&[1.0, 0.0, -0.5, -0.5], 2), [i16::MAX / 2, -i16::MAX / 2]);
+    }
+}
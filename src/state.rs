@@ -0,0 +1,222 @@
+// What shhh currently has switched off, persisted so that a crash or reboot
+// mid-cut doesn't leave the machine offline: the next start (or `shhh enable`)
+// undoes whatever the file says before doing anything else.
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::display;
+use crate::hosts;
+use crate::iface::{set_iface, System};
+use crate::shaping;
+use crate::volume;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub disabled_interfaces: Vec<String>,
+    pub hosts_blocked: bool,
+    #[serde(default)]
+    pub shaped_interfaces: Vec<String>,
+    // the volume before `[duck]` turned it down
+    #[serde(default)]
+    pub ducked_volume: Option<u8>,
+    // the brightness before `[dim]` turned it down (and maybe off)
+    #[serde(default)]
+    pub dimmed_brightness: Option<u8>,
+    // apps muted by `[mute]`
+    #[serde(default)]
+    pub muted_apps: Vec<String>,
+}
+
+// tests that cut share the file
+#[cfg(test)]
+pub static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+pub fn path() -> PathBuf {
+    #[cfg(all(windows, not(test)))]
+    let path = PathBuf::from(std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into()))
+        .join("shhh")
+        .join("state.json");
+    // same split as the control socket: root (the unit) vs. a regular user
+    #[cfg(all(unix, not(test)))]
+    let path = if unsafe { libc::geteuid() } == 0 {
+        PathBuf::from("/var/lib/shhh/state.json")
+    } else {
+        // the user's own state directory, which outlives a reboot, rather
+        // than a name in /tmp anyone could get to first
+        let env = |name| std::env::var_os(name).map(PathBuf::from).filter(|dir| dir.is_absolute());
+        env("XDG_STATE_HOME")
+            .or_else(|| env("HOME").map(|home| home.join(".local").join("state")))
+            .or_else(|| env("XDG_RUNTIME_DIR"))
+            .unwrap_or_else(std::env::temp_dir)
+            .join("shhh")
+            .join("state.json")
+    };
+    // tests cut too, and mustn't touch a real run's file
+    #[cfg(test)]
+    let path = std::env::temp_dir().join(format!("shhh-test-{}.state.json", std::process::id()));
+    path
+}
+
+// written *before* enforcing, so a crash halfway through is still covered
+pub fn save(state: &State) -> Result<(), anyhow::Error> {
+    let path = path();
+    if *state == State::default() {
+        return match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    create_dir(&path)?;
+    write(&path, &serde_json::to_string(state)?)
+}
+
+fn create_dir(path: &Path) -> Result<(), anyhow::Error> {
+    if let Some(dir) = path.parent() {
+        // the service's directory, where the config goes too: only for
+        // SYSTEM and Administrators (see acl.rs)
+        #[cfg(all(windows, not(test)))]
+        if !dir.exists() {
+            crate::acl::lock_down(dir)?;
+        }
+        #[cfg(not(unix))]
+        std::fs::create_dir_all(dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+            // a directory of someone else's could have the file swapped
+            // (tests keep theirs in the shared temp directory)
+            #[cfg(not(test))]
+            if std::os::unix::fs::MetadataExt::uid(&std::fs::metadata(dir)?) != unsafe { libc::geteuid() } {
+                anyhow::bail!("{} belongs to someone else", dir.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Unix: neither follows a symlink put in place of the file nor touches a
+// file someone else owns
+fn write(path: &Path, text: &str) -> Result<(), anyhow::Error> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(path)?;
+        // emptied only once it's known to be ours
+        owned(&file, path)?;
+        file.set_len(0)?;
+        file.write_all(text.as_bytes())?;
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    Ok(std::fs::write(path, text)?)
+}
+
+fn read(path: &Path) -> std::io::Result<String> {
+    #[cfg(unix)]
+    {
+        use std::io::Read;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new().read(true).custom_flags(libc::O_NOFOLLOW).open(path)?;
+        owned(&file, path)?;
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        Ok(text)
+    }
+    #[cfg(not(unix))]
+    std::fs::read_to_string(path)
+}
+
+#[cfg(unix)]
+fn owned(file: &std::fs::File, path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    if file.metadata()?.uid() != unsafe { libc::geteuid() } {
+        let message = format!("{} belongs to someone else", path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, message));
+    }
+    Ok(())
+}
+
+// the ambient level of the last calibration that passed its checks, next to
+// the state file, for a start where none does
+fn ambient_path() -> PathBuf {
+    path().with_extension("ambient")
+}
+
+pub fn last_ambient() -> Option<f32> {
+    read(&ambient_path()).ok()?.trim().parse().ok()
+}
+
+pub fn remember_ambient(db: f32) {
+    let path = ambient_path();
+    if let Err(e) = create_dir(&path).and_then(|_| write(&path, &format!("{:.1}\n", db))) {
+        debug!("Could not remember the ambient level in {}: {}", path.display(), e);
+    }
+}
+
+fn load() -> Result<State, anyhow::Error> {
+    match read(&path()) {
+        Ok(text) => Ok(serde_json::from_str(&text)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// undo whatever a previous run left in force, then forget about it
+pub fn reconcile() -> Result<(), anyhow::Error> {
+    let state = load().map_err(|e| anyhow::anyhow!("could not read {}: {}", path().display(), e))?;
+    if !state.disabled_interfaces.is_empty() {
+        info!("Re-enabling {} left disabled by a previous run.", state.disabled_interfaces.join(", "));
+        set_iface(&System, &state.disabled_interfaces, true)?;
+    }
+    if state.hosts_blocked {
+        info!("Removing the domain blocklist left by a previous run.");
+        hosts::set_blocked(&[])?;
+    }
+    if !state.shaped_interfaces.is_empty() {
+        info!("Removing the traffic shaping left on {} by a previous run.", state.shaped_interfaces.join(", "));
+        shaping::clear(&state.shaped_interfaces)?;
+    }
+    if let Some(volume) = state.ducked_volume {
+        info!("Turning the volume back up to {}% after a previous run.", volume);
+        volume::set(volume)?;
+    }
+    if let Some(brightness) = state.dimmed_brightness {
+        info!("Turning the screen back on and up to {}% after a previous run.", brightness);
+        display::blank(false)?;
+        display::set(brightness)?;
+    }
+    if !state.muted_apps.is_empty() {
+        info!("Unmuting {} after a previous run.", state.muted_apps.join(", "));
+        volume::mute_apps(&state.muted_apps, false)?;
+    }
+    save(&State::default())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_symlink_in_place_of_the_file_is_refused() {
+        let dir = std::env::temp_dir().join(format!("shhh-test-{}-link", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (target, link) = (dir.join("target"), dir.join("state.json"));
+        std::fs::write(&target, "keep").unwrap();
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert!(write(&link, "{}").is_err());
+        assert!(read(&link).is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "keep");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
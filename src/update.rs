@@ -0,0 +1,213 @@
+// `shhh self-update`: fetch the latest GitHub release for this platform and
+// swap it in for the running binary, so machines nobody looks at don't run
+// old versions forever.
+//
+// A release carries the binaries (`shhh-<os>-<arch>[.exe]`), a `SHA256SUMS`
+// listing them and `SHA256SUMS.sig`, a base64 Ed25519 signature of that file.
+// Builds made with SHHH_RELEASE_KEY (the base64 public key) set only accept a
+// release signed with it; other builds can only check the checksum, which
+// catches a broken download but not a forged release.
+//
+// Linux renames the new file over the old one. Windows won't replace a
+// running executable but will rename it, so the old one moves aside as
+// `shhh.exe.old` and is deleted at the next start.
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use data_encoding::{BASE64, HEXLOWER_PERMISSIVE};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::privilege;
+
+const REPO: &str = "kevinmershon/shhh";
+const RELEASE_KEY: Option<&str> = option_env!("SHHH_RELEASE_KEY");
+
+#[derive(clap::Args, Debug)]
+pub struct UpdateArgs {
+    /// Only say whether there's a newer release
+    #[arg(long)]
+    pub check: bool,
+    /// Reinstall the latest release even if it isn't newer
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn download(&self, name: &str) -> Result<Vec<u8>, anyhow::Error> {
+        let asset = self
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .ok_or_else(|| anyhow::anyhow!("release {} has no {}", self.tag_name, name))?;
+        let mut bytes = Vec::new();
+        agent().get(&asset.browser_download_url).call()?.into_reader().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+fn agent() -> ureq::Agent {
+    // GitHub refuses requests without a User-Agent
+    ureq::AgentBuilder::new()
+        .timeout(Duration::from_secs(120))
+        .user_agent(concat!("shhh/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+pub fn asset_name() -> String {
+    format!("shhh-{}-{}{}", std::env::consts::OS, std::env::consts::ARCH, std::env::consts::EXE_SUFFIX)
+}
+
+pub fn run(args: &UpdateArgs, elevate: bool) -> Result<(), anyhow::Error> {
+    let url = format!("https://api.github.com/repos/{}/releases/latest", REPO);
+    let release: Release = serde_json::from_reader(agent().get(&url).call()?.into_reader())?;
+    let (running, latest) = (env!("CARGO_PKG_VERSION"), release.tag_name.trim_start_matches('v'));
+    if !newer(latest, running) && !args.force {
+        println!("shhh {} is up to date.", running);
+        return Ok(());
+    }
+    if args.check {
+        println!("shhh {} is available (this is {}).", latest, running);
+        return Ok(());
+    }
+
+    println!("Downloading shhh {}...", latest);
+    let name = asset_name();
+    let sums = release.download("SHA256SUMS")?;
+    match RELEASE_KEY {
+        Some(key) => verify_signature(key, &sums, &release.download("SHA256SUMS.sig")?)?,
+        None => println!("This build has no release key; only checking the checksum."),
+    }
+    let binary = release.download(&name)?;
+    verify_checksum(&String::from_utf8_lossy(&sums), &name, &binary)?;
+
+    let exe = std::env::current_exe()?;
+    match install(&exe, &binary) {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            privilege::require(elevate)?;
+            install(&exe, &binary)?
+        }
+        result => result?,
+    }
+    println!("Updated {} to {}; restart shhh (or its service) to use it.", exe.display(), latest);
+    Ok(())
+}
+
+// "0.10.1" > "0.9.3"; anything after a `-` (a pre-release) is ignored
+fn newer(latest: &str, running: &str) -> bool {
+    let parts = |v: &str| -> Vec<u64> {
+        v.split('-').next().unwrap_or_default().split('.').map(|p| p.parse().unwrap_or(0)).collect()
+    };
+    parts(latest) > parts(running)
+}
+
+fn verify_signature(key: &str, sums: &[u8], signature: &[u8]) -> Result<(), anyhow::Error> {
+    let key: [u8; 32] = BASE64
+        .decode(key.trim().as_bytes())
+        .ok()
+        .and_then(|k| k.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("this build's release key isn't a base64 Ed25519 key"))?;
+    let signature: [u8; 64] = BASE64
+        .decode(String::from_utf8_lossy(signature).trim().as_bytes())
+        .ok()
+        .and_then(|s| s.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("SHA256SUMS.sig isn't a base64 Ed25519 signature"))?;
+    VerifyingKey::from_bytes(&key)?
+        .verify_strict(sums, &Signature::from_bytes(&signature))
+        .map_err(|_| anyhow::anyhow!("SHA256SUMS isn't signed with the release key; not updating"))
+}
+
+// `sha256sum` output: "<hex>  <name>", or "<hex> *<name>" in binary mode
+fn verify_checksum(sums: &str, name: &str, binary: &[u8]) -> Result<(), anyhow::Error> {
+    let expected = sums
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .find(|(_, file)| file.trim_start().trim_start_matches('*') == name)
+        .and_then(|(hex, _)| HEXLOWER_PERMISSIVE.decode(hex.as_bytes()).ok())
+        .ok_or_else(|| anyhow::anyhow!("SHA256SUMS doesn't list {}", name))?;
+    if Sha256::digest(binary).as_slice() != expected {
+        anyhow::bail!("{} doesn't match its checksum; not updating", name);
+    }
+    Ok(())
+}
+
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let mut name = exe.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// written next to the old binary first, so the swap is a rename on the same
+// filesystem and a failed download never leaves half a binary behind
+fn install(exe: &Path, binary: &[u8]) -> io::Result<()> {
+    let new = sibling(exe, ".new");
+    std::fs::write(&new, binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&new, std::fs::Permissions::from_mode(0o755))?;
+        std::fs::rename(&new, exe)
+    }
+    #[cfg(windows)]
+    {
+        let old = sibling(exe, ".old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(exe, &old)?;
+        std::fs::rename(&new, exe).inspect_err(|_| {
+            let _ = std::fs::rename(&old, exe);
+        })
+    }
+}
+
+// the binary an update moved aside, once nothing runs it
+#[cfg(windows)]
+pub fn clean_up() {
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = std::fs::remove_file(sibling(&exe, ".old"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(newer("0.10.0", "0.9.3"));
+        assert!(newer("1.0.0", "0.99.0"));
+        assert!(!newer("0.1.0", "0.1.0"));
+        assert!(!newer("0.1.0-rc1", "0.1.0"));
+    }
+
+    #[test]
+    fn releases_are_checked_against_sums_and_key() {
+        let binary = b"new shhh";
+        let sums = format!("00  other\n{} *{}\n", HEXLOWER_PERMISSIVE.encode(&Sha256::digest(binary)), asset_name());
+        assert!(verify_checksum(&sums, &asset_name(), binary).is_ok());
+        assert!(verify_checksum(&sums, &asset_name(), b"tampered").is_err());
+        assert!(verify_checksum(&sums, "missing", binary).is_err());
+
+        let signer = SigningKey::from_bytes(&[7; 32]);
+        let key = BASE64.encode(signer.verifying_key().as_bytes());
+        let signature = BASE64.encode(&signer.sign(sums.as_bytes()).to_bytes());
+        assert!(verify_signature(&key, sums.as_bytes(), signature.as_bytes()).is_ok());
+        assert!(verify_signature(&key, b"other sums", signature.as_bytes()).is_err());
+        let stranger = BASE64.encode(SigningKey::from_bytes(&[8; 32]).verifying_key().as_bytes());
+        assert!(verify_signature(&stranger, sums.as_bytes(), signature.as_bytes()).is_err());
+    }
+}
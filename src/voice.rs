@@ -0,0 +1,215 @@
+// Speaker-targeted enforcement: with `[voice]`, only the enrolled person being
+// loud counts, so the toddler screaming doesn't cost the teenager their
+// internet. `shhh enroll` records a short sample of their voice and saves its
+// voiceprint: the average shape of its spectrum (mel cepstra) over the louder
+// frames, and how much that varies. Every window gets the same treatment, and
+// one whose shape is too far from the enrolled voice, someone else or the TV,
+// is let go however loud it is.
+//
+// A likeness rather than real speaker recognition: enroll on the microphone
+// shhh listens through, speaking normally, in an otherwise quiet room.
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::audio;
+use crate::config::{self, VoiceConfig};
+use crate::spectrogram::fft;
+
+// analysis frames, rounded up to a power of two for the FFT
+const FRAME_MS: u32 = 32;
+// where voices are, and how finely to slice it
+const LOW_HZ: f32 = 100.0;
+const HIGH_HZ: f32 = 4_000.0;
+const MEL_BANDS: usize = 26;
+// c1 to c12; c0 is the loudness, which the level already covers
+const CEPSTRA: usize = 12;
+// frames this far under the loudest are the room, not the voice
+const VOICED_DB: f32 = 20.0;
+const MIN_VOICED: usize = 4;
+// a steady enrollment (a hum, a drone) would otherwise match nothing but itself
+const MIN_SPREAD: f32 = 1.0;
+
+#[derive(clap::Args, Debug)]
+pub struct EnrollArgs {
+    /// Where to save the voiceprint (what `print` in `[voice]` points to)
+    #[arg(long, value_name = "PATH")]
+    pub out: PathBuf,
+    /// How long to record
+    #[arg(long, value_name = "SECS", default_value_t = 15)]
+    pub secs: u32,
+    /// Microphone to record from, by (part of) its name in `shhh devices` (default: as for `run`)
+    #[arg(long, value_name = "NAME")]
+    pub device: Option<String>,
+    /// Take the voice from a WAV file instead of recording it
+    #[arg(long, value_name = "WAV")]
+    pub input_file: Option<PathBuf>,
+}
+
+pub fn enroll(args: &EnrollArgs) -> Result<(), anyhow::Error> {
+    let input = match &args.input_file {
+        Some(path) => audio::open_file(path, 100.0)?,
+        None => audio::open_input(args.device.as_deref().or(config::load(None)?.input_device.as_deref()))?,
+    };
+    if args.input_file.is_none() {
+        println!("Recording for {} s: talk the way you normally do...", args.secs);
+    }
+    let wanted = input.sample_rate as usize * input.channels as usize * args.secs as usize;
+    let samples: Vec<f32> = input.rx.iter().take(wanted).collect();
+    let print = Voiceprint::of(&samples, input.sample_rate, input.channels)
+        .ok_or_else(|| anyhow::anyhow!("not enough voice in the recording to go by"))?;
+    std::fs::write(&args.out, serde_json::to_string_pretty(&print)?)
+        .map_err(|e| anyhow::anyhow!("could not write {}: {}", args.out.display(), e))?;
+    println!("Saved the voiceprint to {}.", args.out.display());
+    Ok(())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Voiceprint {
+    // each cepstral coefficient's mean and standard deviation over the voiced
+    // frames
+    mean: Vec<f32>,
+    spread: Vec<f32>,
+}
+
+impl Voiceprint {
+    // None without enough voiced frames to go by
+    pub fn of(samples: &[f32], rate: u32, channels: u16) -> Option<Voiceprint> {
+        let channels = channels.max(1) as usize;
+        let mono: Vec<f32> = samples.chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect();
+        let size = (rate * FRAME_MS / 1000).next_power_of_two() as usize;
+        let bank = mel_bank(size, rate);
+        let frames: Vec<(f32, Vec<f32>)> = mono.chunks_exact(size).map(|frame| cepstra(frame, &bank)).collect();
+        let loudest = frames.iter().map(|(db, _)| *db).fold(f32::MIN, f32::max);
+        let voiced: Vec<&Vec<f32>> =
+            frames.iter().filter(|(db, _)| *db >= loudest - VOICED_DB).map(|(_, cepstra)| cepstra).collect();
+        if voiced.len() < MIN_VOICED {
+            return None;
+        }
+        let count = voiced.len() as f32;
+        let mean: Vec<f32> = (0..CEPSTRA).map(|i| voiced.iter().map(|c| c[i]).sum::<f32>() / count).collect();
+        let spread = (0..CEPSTRA)
+            .map(|i| (voiced.iter().map(|c| (c[i] - mean[i]).powi(2)).sum::<f32>() / count).sqrt())
+            .collect();
+        Some(Voiceprint { mean, spread })
+    }
+
+    // how far another recording's average is from this voice, in standard
+    // deviations of this voice (the root mean square over the coefficients)
+    pub fn distance(&self, other: &Voiceprint) -> f32 {
+        let squares = self.mean.iter().zip(&self.spread).zip(&other.mean).map(|((mean, spread), their)| {
+            ((their - mean) / spread.max(MIN_SPREAD)).powi(2)
+        });
+        (squares.sum::<f32>() / CEPSTRA as f32).sqrt()
+    }
+
+    pub fn load(path: &Path) -> Result<Voiceprint, anyhow::Error> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+        let print: Voiceprint = serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("{} isn't a voiceprint from `shhh enroll`: {}", path.display(), e))?;
+        if print.mean.len() != CEPSTRA || print.spread.len() != CEPSTRA {
+            anyhow::bail!("{} isn't a voiceprint from `shhh enroll`", path.display());
+        }
+        Ok(print)
+    }
+}
+
+// the enrolled voice, against which the meter checks each window
+pub struct Voice {
+    print: Voiceprint,
+    max_distance: f32,
+    rate: u32,
+    channels: u16,
+}
+
+impl Voice {
+    pub fn new(config: &VoiceConfig, rate: u32, channels: u16) -> Result<Voice, anyhow::Error> {
+        Ok(Voice { print: Voiceprint::load(&config.print)?, max_distance: config.max_distance, rate, channels })
+    }
+
+    // a window too unlike the enrolled voice; one without enough sound in it
+    // to tell isn't
+    pub fn someone_else(&self, window: &[f32]) -> bool {
+        let Some(print) = Voiceprint::of(window, self.rate, self.channels) else { return false };
+        let distance = self.print.distance(&print);
+        debug!("Voice distance {:.1}", distance);
+        distance > self.max_distance
+    }
+}
+
+// triangular filters, evenly spaced in mels, as weights over the FFT bins
+fn mel_bank(size: usize, rate: u32) -> Vec<Vec<f32>> {
+    let mel = |hz: f32| 2595.0 * (1.0 + hz / 700.0).log10();
+    let hz = |mel: f32| 700.0 * (10f32.powf(mel / 2595.0) - 1.0);
+    let (low, high) = (mel(LOW_HZ), mel(HIGH_HZ.min(rate as f32 / 2.0)));
+    let edges: Vec<f32> = (0..MEL_BANDS + 2).map(|i| hz(low + (high - low) * i as f32 / (MEL_BANDS + 1) as f32)).collect();
+    let bin_hz = rate as f32 / size as f32;
+    edges
+        .windows(3)
+        .map(|edge| {
+            (0..size / 2)
+                .map(|bin| {
+                    let f = bin as f32 * bin_hz;
+                    let rising = (f - edge[0]) / (edge[1] - edge[0]);
+                    let falling = (edge[2] - f) / (edge[2] - edge[1]);
+                    rising.min(falling).max(0.0)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// a frame's level (dB) and its cepstral coefficients
+fn cepstra(frame: &[f32], bank: &[Vec<f32>]) -> (f32, Vec<f32>) {
+    let n = frame.len();
+    let hann = |i: usize| 0.5 - 0.5 * (2.0 * PI * i as f32 / (n - 1) as f32).cos();
+    let mut data: Vec<(f32, f32)> = frame.iter().enumerate().map(|(i, &s)| (s * hann(i), 0.0)).collect();
+    fft(&mut data);
+    let power: Vec<f32> = data[..n / 2].iter().map(|&(re, im)| re * re + im * im).collect();
+    let energies: Vec<f32> =
+        bank.iter().map(|weights| weights.iter().zip(&power).map(|(w, p)| w * p).sum::<f32>().max(1e-12).ln()).collect();
+    let level = 10.0 * power.iter().sum::<f32>().max(1e-12).log10();
+    let bands = energies.len() as f32;
+    let cepstra = (1..=CEPSTRA)
+        .map(|k| {
+            energies.iter().enumerate().map(|(i, e)| e * (PI * k as f32 * (i as f32 + 0.5) / bands).cos()).sum::<f32>()
+        })
+        .collect();
+    (level, cepstra)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a buzz at `pitch` Hz whose harmonics fall off `tilt` dB per octave, with
+    // a slow wobble in pitch so no two stretches are quite the same
+    fn voice(pitch: f32, tilt: f32, secs: f32, seed: f32) -> Vec<f32> {
+        let rate = 16_000.0;
+        let mut phase = 0.0;
+        (0..(secs * rate) as usize)
+            .map(|i| {
+                let t = i as f32 / rate;
+                phase += 2.0 * PI * pitch * (1.0 + 0.2 * (2.0 * PI * 3.0 * t + seed).sin()) / rate;
+                (1..40)
+                    .map(|k| 10f32.powf(-tilt * (k as f32).log2() / 20.0) * (k as f32 * phase).sin())
+                    .sum::<f32>()
+                    * 0.05
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_enrolled_voice_is_told_from_another() {
+        let print = Voiceprint::of(&voice(120.0, 6.0, 5.0, 0.0), 16_000, 1).unwrap();
+        let same = Voiceprint::of(&voice(125.0, 6.0, 0.5, 1.0), 16_000, 1).unwrap();
+        let other = Voiceprint::of(&voice(300.0, 12.0, 0.5, 1.0), 16_000, 1).unwrap();
+        assert!(print.distance(&same) < 1.0, "{}", print.distance(&same));
+        assert!(print.distance(&other) > 2.0, "{}", print.distance(&other));
+        // too little to go by
+        assert!(Voiceprint::of(&voice(120.0, 6.0, 0.05, 0.0), 16_000, 1).is_none());
+    }
+}
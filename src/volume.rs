@@ -0,0 +1,196 @@
+// The speakers' volume, for `[duck]`, and single apps' sound, for `[mute]`.
+// On Linux that's the default sink and the streams playing to any sink
+// through `pactl` (PulseAudio, or PipeWire's pulse server), which only works
+// in the session of the user whose sound it is; on Windows the default
+// playback device's endpoint volume and the audio sessions of every playback
+// device through Core Audio, which are the same for every session.
+pub use platform::{get, mute_apps, set};
+
+// "Volume: front-left: 32768 /  50% / -18.06 dB,   front-right: ..." -> 50
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_pactl(output: &str) -> Option<u8> {
+    let pct = output.split('%').next()?.rsplit(|c: char| !c.is_ascii_digit()).next()?;
+    Some(pct.parse::<u32>().ok()?.min(100) as u8)
+}
+
+// the index and program names (binary, app name) of each stream in
+// `pactl list sink-inputs`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn sink_inputs(text: &str) -> Vec<(u32, Vec<String>)> {
+    let mut inputs: Vec<(u32, Vec<String>)> = Vec::new();
+    for line in text.lines() {
+        if let Some(index) = line.strip_prefix("Sink Input #") {
+            inputs.extend(index.trim().parse().ok().map(|index| (index, Vec::new())));
+        } else if let (Some((_, names)), Some((key, value))) = (inputs.last_mut(), line.trim().split_once(" = ")) {
+            if matches!(key, "application.process.binary" | "application.name") {
+                names.push(value.trim_matches('"').to_string());
+            }
+        }
+    }
+    inputs
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::Command;
+
+    use crate::calls::matching;
+
+    fn pactl(args: &[&str]) -> Result<String, anyhow::Error> {
+        let output = Command::new("pactl")
+            .args(args)
+            .output()
+            .map_err(|e| anyhow::anyhow!("could not run pactl (is PulseAudio or PipeWire installed?): {}", e))?;
+        if !output.status.success() {
+            anyhow::bail!("pactl {}: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    pub fn get() -> Result<u8, anyhow::Error> {
+        let output = pactl(&["get-sink-volume", "@DEFAULT_SINK@"])?;
+        super::parse_pactl(&output).ok_or_else(|| anyhow::anyhow!("unexpected pactl output: {}", output.trim()))
+    }
+
+    pub fn set(pct: u8) -> Result<(), anyhow::Error> {
+        pactl(&["set-sink-volume", "@DEFAULT_SINK@", &format!("{}%", pct.min(100))]).map(|_| ())
+    }
+
+    // how many streams it (un)muted
+    pub fn mute_apps(apps: &[String], mute: bool) -> Result<usize, anyhow::Error> {
+        let mut count = 0;
+        for (index, names) in super::sink_inputs(&pactl(&["list", "sink-inputs"])?) {
+            if names.iter().any(|name| matching(name, apps).is_some()) {
+                pactl(&["set-sink-input-mute", &index.to_string(), if mute { "1" } else { "0" }])?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ptr;
+
+    use windows::core::Interface;
+    use windows::Win32::Media::Audio::Endpoints::IAudioEndpointVolume;
+    use windows::Win32::Media::Audio::{
+        eMultimedia, eRender, IAudioSessionControl2, IAudioSessionManager2, IMMDevice, IMMDeviceEnumerator,
+        ISimpleAudioVolume, MMDeviceEnumerator, DEVICE_STATE_ACTIVE,
+    };
+    use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_MULTITHREADED};
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    use crate::calls::matching;
+
+    fn enumerator() -> Result<IMMDeviceEnumerator, anyhow::Error> {
+        unsafe {
+            // already initialised on this thread (either way) is fine
+            let _ = CoInitializeEx(ptr::null(), COINIT_MULTITHREADED);
+            Ok(CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?)
+        }
+    }
+
+    // the windows crate has no safe way from a raw pointer to an interface
+    unsafe fn activate<T: Interface>(device: &IMMDevice) -> Result<T, anyhow::Error> {
+        let mut object = ptr::null_mut();
+        device.Activate(&T::IID, CLSCTX_ALL, ptr::null(), &mut object)?;
+        if object.is_null() {
+            anyhow::bail!("the playback device doesn't support that");
+        }
+        Ok(std::mem::transmute_copy::<*mut std::ffi::c_void, T>(&object))
+    }
+
+    fn endpoint() -> Result<IAudioEndpointVolume, anyhow::Error> {
+        unsafe { activate(&enumerator()?.GetDefaultAudioEndpoint(eRender, eMultimedia)?) }
+    }
+
+    // the program behind a process id (nothing for the system sounds, pid 0)
+    fn program(pid: u32) -> Option<String> {
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+            if process.is_null() {
+                return None;
+            }
+            let mut buf = [0u16; 1024];
+            let mut len = buf.len() as u32;
+            let ok = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buf.as_mut_ptr(), &mut len);
+            CloseHandle(process);
+            (ok != 0).then(|| String::from_utf16_lossy(&buf[..len as usize]))
+        }
+    }
+
+    pub fn get() -> Result<u8, anyhow::Error> {
+        let level = unsafe { endpoint()?.GetMasterVolumeLevelScalar()? };
+        Ok((level * 100.0).round().clamp(0.0, 100.0) as u8)
+    }
+
+    pub fn set(pct: u8) -> Result<(), anyhow::Error> {
+        unsafe { endpoint()?.SetMasterVolumeLevelScalar(pct.min(100) as f32 / 100.0, ptr::null())? };
+        Ok(())
+    }
+
+    // every playback device, since a game may well not use the default one;
+    // how many sessions it (un)muted
+    pub fn mute_apps(apps: &[String], mute: bool) -> Result<usize, anyhow::Error> {
+        let mut count = 0;
+        unsafe {
+            let devices = enumerator()?.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+            for i in 0..devices.GetCount()? {
+                let manager: IAudioSessionManager2 = activate(&devices.Item(i)?)?;
+                let sessions = manager.GetSessionEnumerator()?;
+                for j in 0..sessions.GetCount()? {
+                    let session = sessions.GetSession(j)?;
+                    let pid = session.cast::<IAudioSessionControl2>()?.GetProcessId().unwrap_or(0);
+                    if program(pid).is_some_and(|program| matching(&program, apps).is_some()) {
+                        session.cast::<ISimpleAudioVolume>()?.SetMute(mute, ptr::null())?;
+                        count += 1;
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    pub fn get() -> Result<u8, anyhow::Error> {
+        anyhow::bail!("changing the volume isn't supported on this platform")
+    }
+
+    pub fn set(_pct: u8) -> Result<(), anyhow::Error> {
+        get().map(|_| ())
+    }
+
+    pub fn mute_apps(_apps: &[String], _mute: bool) -> Result<usize, anyhow::Error> {
+        anyhow::bail!("muting apps isn't supported on this platform")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pactl_volume_is_read() {
+        let stereo = "Volume: front-left: 32768 /  50% / -18.06 dB,   front-right: 32768 /  50% / -18.06 dB\n";
+        assert_eq!(parse_pactl(stereo), Some(50));
+        assert_eq!(parse_pactl("Volume: mono: 98304 / 150% / 10.57 dB"), Some(100));
+        assert_eq!(parse_pactl("No sink"), None);
+    }
+
+    #[test]
+    fn sink_inputs_name_their_programs() {
+        let text = "Sink Input #7\n\tDriver: PipeWire\n\tMute: no\n\tProperties:\n\t\tapplication.name = \"Spotify\"\n\
+                    \t\tapplication.process.binary = \"spotify\"\n\nSink Input #12\n\tProperties:\n\
+                    \t\tapplication.name = \"Firefox\"\n";
+        let inputs = sink_inputs(text);
+        assert_eq!(inputs[0], (7, vec!["Spotify".to_string(), "spotify".to_string()]));
+        assert_eq!(inputs[1], (12, vec!["Firefox".to_string()]));
+    }
+}
@@ -0,0 +1,156 @@
+// Companion process that restores connectivity if the monitor dies without
+// getting the chance to (panic, OOM kill, `taskkill /f`). The monitor writes a
+// byte to the watchdog's stdin every window, and a final "bye" byte when it
+// stops on purpose; when the heartbeats stop or the pipe closes without a bye,
+// the watchdog undoes whatever the state file says and exits.
+//
+// With `--protect` (and no service manager to do it for us) the two also
+// restart each other: the watchdog relaunches a monitor that was killed, and
+// the monitor respawns a watchdog that was.
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+
+use crate::monitor::RunArgs;
+use crate::state;
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+const BEAT: u8 = b'.';
+const BYE: u8 = b'x';
+
+pub struct Heartbeat {
+    _child: Child,
+    pipe: Option<ChildStdin>,
+    respawn: bool,
+}
+
+impl Heartbeat {
+    pub fn beat(&mut self) {
+        if self.send(BEAT) {
+            return;
+        }
+        if self.respawn {
+            warn!("Watchdog was killed; starting a new one.");
+            match spawn(true) {
+                Ok(heartbeat) => *self = heartbeat,
+                Err(e) => {
+                    warn!("Watchdog unavailable: {}", e);
+                    self.pipe = None;
+                    self.respawn = false;
+                }
+            }
+        } else if self.pipe.take().is_some() {
+            warn!("Watchdog went away; continuing without it.");
+        }
+    }
+
+    // an authorized stop: the watchdog should neither restore nor restart
+    pub fn bye(&mut self) {
+        self.send(BYE);
+    }
+
+    fn send(&mut self, byte: u8) -> bool {
+        let Some(pipe) = &mut self.pipe else { return false };
+        pipe.write_all(&[byte]).and_then(|()| pipe.flush()).is_ok()
+    }
+}
+
+// spawn the watchdog for a monitor about to start, unless disabled (or
+// pointless, in a dry run)
+pub fn start(args: &RunArgs) -> Option<Heartbeat> {
+    if args.no_watchdog || args.dry_run {
+        return None;
+    }
+    // systemd and the SCM already restart a killed monitor
+    let supervised = args.supervised || std::env::var_os("NOTIFY_SOCKET").is_some();
+    match spawn(args.protect && !supervised) {
+        Ok(heartbeat) => Some(heartbeat),
+        Err(e) => {
+            warn!("Watchdog unavailable: {}", e);
+            None
+        }
+    }
+}
+
+fn spawn(respawn: bool) -> Result<Heartbeat, anyhow::Error> {
+    let mut cmd = Command::new(std::env::current_exe()?);
+    cmd.arg("watchdog").stdin(Stdio::piped());
+    if respawn {
+        // how to bring us back
+        cmd.arg("--").args(std::env::args_os().skip(1));
+    }
+    detach(&mut cmd);
+    let mut child = cmd.spawn()?;
+    let pipe = child.stdin.take();
+    Ok(Heartbeat { _child: child, pipe, respawn })
+}
+
+// keep a terminal Ctrl+C from reaching the other process
+fn detach(cmd: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+enum Message {
+    Beat,
+    Bye,
+}
+
+// `shhh watchdog [-- <monitor args>]`, spawned by the monitor; with monitor
+// args it relaunches a monitor that died without saying bye
+pub fn run(timeout: Duration, monitor_args: Vec<OsString>) -> Result<(), anyhow::Error> {
+    // outlive stop signals so the monitor's own exit is what ends us
+    ctrlc::set_handler(|| {}).ok();
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 64];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let message = if buf[..n].contains(&BYE) { Message::Bye } else { Message::Beat };
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut said_bye = false;
+    let died = loop {
+        match rx.recv_timeout(timeout) {
+            Ok(Message::Beat) => {}
+            Ok(Message::Bye) => said_bye = true,
+            Err(mpsc::RecvTimeoutError::Timeout) if said_bye => break false,
+            // hung rather than dead, so don't start a second one next to it
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                warn!("Watchdog: no heartbeat for {}s, restoring.", timeout.as_secs());
+                break false;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break !said_bye,
+        }
+    };
+    // a clean exit clears the state file first, so this is a no-op then
+    state::reconcile()?;
+
+    if died && !monitor_args.is_empty() {
+        warn!("Watchdog: the monitor was stopped without `shhh ctl stop`; restarting it.");
+        let mut cmd = Command::new(std::env::current_exe()?);
+        cmd.args(monitor_args).stdin(Stdio::null());
+        detach(&mut cmd);
+        cmd.spawn()?;
+    }
+    Ok(())
+}